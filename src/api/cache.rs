@@ -0,0 +1,122 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Web service responses kept on disk, keyed by request URL.
+#[derive(Clone)]
+pub struct ResponseCache {
+    tree: sled::Tree,
+}
+
+/// A stored response body and the validators the server sent with it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CachedResponse {
+    pub body: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// When the response was fetched or last revalidated.
+    pub stored_at: DateTime<Utc>,
+}
+
+impl CachedResponse {
+    pub fn new(body: String, etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self {
+            body,
+            etag,
+            last_modified,
+            stored_at: Utc::now(),
+        }
+    }
+
+    /// Whether the response is younger than `ttl` and can be used without
+    /// asking the server.
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        let age = Utc::now().signed_duration_since(self.stored_at);
+        age.to_std().is_ok_and(|age| age < ttl)
+    }
+}
+
+impl ResponseCache {
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = sled::open(path)
+            .with_context(|| format!("Failed to open response cache at {}", path.display()))?;
+        let tree = db
+            .open_tree("responses")
+            .context("Unable to open responses tree")?;
+        Ok(Self { tree })
+    }
+
+    /// The stored response for `url`. An entry that cannot be read counts as
+    /// missing.
+    pub fn get(&self, url: &str) -> Option<CachedResponse> {
+        let bytes = self.tree.get(url).ok()??;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub fn put(&self, url: &str, response: &CachedResponse) -> Result<()> {
+        let value = serde_json::to_vec(response).context("Failed to serialize cached response")?;
+        self.tree
+            .insert(url, value)
+            .context("Failed to persist cached response")?;
+        Ok(())
+    }
+
+    /// Marks the response for `url` as confirmed current by the server.
+    pub fn touch(&self, url: &str, mut response: CachedResponse) -> Result<CachedResponse> {
+        response.stored_at = Utc::now();
+        self.put(url, &response)?;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> ResponseCache {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("temporary database");
+        ResponseCache {
+            tree: db.open_tree("responses").unwrap(),
+        }
+    }
+
+    #[test]
+    fn stores_responses_by_url() {
+        let cache = cache();
+        let url = "https://musicbrainz.org/ws/2/artist?query=Portishead&fmt=json";
+        let response = CachedResponse::new("{}".into(), Some("\"abc\"".into()), None);
+
+        assert_eq!(cache.get(url), None);
+        cache.put(url, &response).unwrap();
+        assert_eq!(cache.get(url), Some(response));
+        assert_eq!(cache.get("https://musicbrainz.org/ws/2/artist"), None);
+    }
+
+    #[test]
+    fn freshness_follows_ttl() {
+        let mut response = CachedResponse::new(String::new(), None, None);
+        assert!(response.is_fresh(Duration::from_secs(60)));
+
+        response.stored_at = Utc::now() - chrono::Duration::hours(2);
+        assert!(response.is_fresh(Duration::from_secs(3 * 3600)));
+        assert!(!response.is_fresh(Duration::from_secs(3600)));
+        assert!(!response.is_fresh(Duration::ZERO));
+    }
+
+    #[test]
+    fn touch_restarts_the_clock() {
+        let cache = cache();
+        let mut response = CachedResponse::new("{}".into(), None, Some("yesterday".into()));
+        response.stored_at = Utc::now() - chrono::Duration::days(30);
+
+        let touched = cache.touch("url", response).unwrap();
+        assert!(touched.is_fresh(Duration::from_secs(60)));
+        assert_eq!(cache.get("url"), Some(touched));
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode, Url, header};
+use serde::Deserialize;
+
+use super::retry::RetryPolicy;
+use crate::config::{AppConfig, CoverArtSize};
+
+/// Reads image listings from the Cover Art Archive and downloads images.
+#[derive(Clone)]
+pub struct CoverArtArchiveClient {
+    source: Arc<dyn ArchiveSource>,
+    base_url: String,
+}
+
+/// A downloaded image and the content type the server reported for it.
+pub struct DownloadedImage {
+    pub bytes: Vec<u8>,
+    pub content_type: Option<String>,
+}
+
+/// Where the client gets listings and images from.
+#[async_trait]
+pub trait ArchiveSource: Send + Sync {
+    /// The listing at `url`, or `None` when the archive has no images there.
+    async fn index(&self, url: &str) -> Result<Option<CoverArtIndex>>;
+
+    /// The image at `url`.
+    async fn download(&self, url: &str) -> Result<DownloadedImage>;
+}
+
+/// Talks to the archive over HTTP, retrying transient failures.
+struct HttpArchive {
+    http: Client,
+    retry: RetryPolicy,
+}
+
+impl CoverArtArchiveClient {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        Ok(Self::with_source(
+            config.cover_art_url(),
+            Arc::new(HttpArchive::new(config)?),
+        ))
+    }
+
+    /// A client for the archive at `base_url` reading from `source`.
+    pub fn with_source(base_url: &str, source: Arc<dyn ArchiveSource>) -> Self {
+        Self {
+            source,
+            base_url: base_url.to_string(),
+        }
+    }
+
+    /// The images of a release, or `None` when it has none.
+    pub async fn release_index(&self, release_id: &str) -> Result<Option<CoverArtIndex>> {
+        self.source
+            .index(&format!("{}/release/{release_id}", self.base_url))
+            .await
+    }
+
+    /// The images of the release the archive picked to represent a release
+    /// group, or `None` when no release in the group has a front image.
+    pub async fn release_group_index(
+        &self,
+        release_group_id: &str,
+    ) -> Result<Option<CoverArtIndex>> {
+        self.source
+            .index(&format!(
+                "{}/release-group/{release_group_id}",
+                self.base_url
+            ))
+            .await
+    }
+
+    pub async fn download(&self, url: &str) -> Result<DownloadedImage> {
+        self.source.download(url).await
+    }
+}
+
+impl HttpArchive {
+    fn new(config: &AppConfig) -> Result<Self> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_str(config.user_agent())
+                .context("invalid user agent header value for cover art client")?,
+        );
+        headers.insert(
+            "X-Client-Id",
+            header::HeaderValue::from_str(config.client_id())
+                .context("invalid client identifier for cover art client")?,
+        );
+        headers.insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static("image/jpeg, image/png, application/json"),
+        );
+
+        let http = Client::builder()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(60))
+            .build()
+            .context("unable to create HTTP client for cover art downloads")?;
+
+        Ok(Self {
+            http,
+            retry: RetryPolicy::default(),
+        })
+    }
+}
+
+#[async_trait]
+impl ArchiveSource for HttpArchive {
+    async fn index(&self, url: &str) -> Result<Option<CoverArtIndex>> {
+        let url = Url::parse(url).context("invalid cover art URL")?;
+        let response = self
+            .retry
+            .send(|| self.http.get(url.clone()).send())
+            .await
+            .context("failed to request cover art listing")?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!("listing returned status {}", response.status()));
+        }
+
+        let index = response
+            .json()
+            .await
+            .context("failed to parse cover art listing")?;
+        Ok(Some(index))
+    }
+
+    async fn download(&self, url: &str) -> Result<DownloadedImage> {
+        let response = self
+            .retry
+            .send(|| self.http.get(url).send())
+            .await
+            .context("failed to request cover art")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("returned status {}", response.status()));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let bytes = response
+            .bytes()
+            .await
+            .context("failed to read cover art bytes")?;
+
+        Ok(DownloadedImage {
+            bytes: bytes.to_vec(),
+            content_type,
+        })
+    }
+}
+
+/// The archive's JSON listing for a release.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct CoverArtIndex {
+    pub images: Vec<IndexImage>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct IndexImage {
+    id: Option<ImageId>,
+    /// Image types such as `Front`, `Back` or `Booklet`.
+    pub types: Vec<String>,
+    pub front: bool,
+    pub comment: String,
+    /// URL of the original upload.
+    pub image: String,
+    /// Thumbnail URLs keyed by size: `250`, `500`, `1200`, and the legacy
+    /// aliases `small` (250) and `large` (500).
+    pub thumbnails: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ImageId {
+    Text(String),
+    Number(u64),
+}
+
+impl IndexImage {
+    pub fn id(&self) -> String {
+        match &self.id {
+            Some(ImageId::Text(id)) => id.clone(),
+            Some(ImageId::Number(id)) => id.to_string(),
+            None => String::new(),
+        }
+    }
+
+    pub fn has_type(&self, wanted: &str) -> bool {
+        (wanted.eq_ignore_ascii_case("front") && self.front)
+            || self
+                .types
+                .iter()
+                .any(|image_type| image_type.eq_ignore_ascii_case(wanted))
+    }
+
+    /// The URL closest to `size`, preferring the next larger thumbnail and
+    /// falling back to the original. Returns the URL and the size it has.
+    pub fn url_for(&self, size: CoverArtSize) -> (&str, CoverArtSize) {
+        let candidates: &[(CoverArtSize, &[&str])] = &[
+            (CoverArtSize::Px250, &["250", "small"]),
+            (CoverArtSize::Px500, &["500", "large"]),
+            (CoverArtSize::Px1200, &["1200"]),
+        ];
+
+        candidates
+            .iter()
+            .filter(|(candidate, _)| *candidate >= size)
+            .find_map(|(candidate, keys)| {
+                keys.iter()
+                    .find_map(|key| self.thumbnails.get(*key))
+                    .filter(|url| !url.is_empty())
+                    .map(|url| (url.as_str(), *candidate))
+            })
+            .unwrap_or((self.image.as_str(), CoverArtSize::Original))
+    }
+}
+
+impl CoverArtIndex {
+    /// The first image matching the earliest type in `types`.
+    pub fn select(&self, types: &[String]) -> Option<&IndexImage> {
+        types.iter().find_map(|wanted| {
+            self.images
+                .iter()
+                .find(|image| image.has_type(wanted) && !image.image.is_empty())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::api::fixtures::serve_once;
+    use crate::config::{ConfigLayer, CoverArtLayer};
+
+    fn image(value: serde_json::Value) -> IndexImage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn types(types: &[&str]) -> Vec<String> {
+        types.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn thumbnails_prefer_the_next_larger_size() {
+        let image = image(json!({
+            "image": "original.jpg",
+            "thumbnails": { "250": "250.jpg", "500": "500.jpg", "1200": "1200.jpg" }
+        }));
+
+        assert_eq!(
+            image.url_for(CoverArtSize::Px250),
+            ("250.jpg", CoverArtSize::Px250)
+        );
+        assert_eq!(
+            image.url_for(CoverArtSize::Px500),
+            ("500.jpg", CoverArtSize::Px500)
+        );
+        assert_eq!(
+            image.url_for(CoverArtSize::Px1200),
+            ("1200.jpg", CoverArtSize::Px1200)
+        );
+        assert_eq!(
+            image.url_for(CoverArtSize::Original),
+            ("original.jpg", CoverArtSize::Original)
+        );
+    }
+
+    #[test]
+    fn missing_thumbnails_fall_back_to_legacy_names_then_larger_sizes() {
+        let legacy = image(json!({
+            "image": "original.jpg",
+            "thumbnails": { "small": "small.jpg", "large": "large.jpg" }
+        }));
+        assert_eq!(
+            legacy.url_for(CoverArtSize::Px250),
+            ("small.jpg", CoverArtSize::Px250)
+        );
+        assert_eq!(
+            legacy.url_for(CoverArtSize::Px500),
+            ("large.jpg", CoverArtSize::Px500)
+        );
+        assert_eq!(
+            legacy.url_for(CoverArtSize::Px1200),
+            ("original.jpg", CoverArtSize::Original)
+        );
+
+        let sparse = image(json!({
+            "image": "original.jpg",
+            "thumbnails": { "250": "", "1200": "1200.jpg" }
+        }));
+        assert_eq!(
+            sparse.url_for(CoverArtSize::Px250),
+            ("1200.jpg", CoverArtSize::Px1200)
+        );
+    }
+
+    #[test]
+    fn image_types_match_case_insensitively_and_by_the_front_flag() {
+        let front = image(json!({ "types": ["Front", "Booklet"], "front": true }));
+        assert!(front.has_type("front"));
+        assert!(front.has_type("BOOKLET"));
+        assert!(!front.has_type("back"));
+
+        let flagged = image(json!({ "types": [], "front": true }));
+        assert!(flagged.has_type("Front"));
+        assert!(!flagged.has_type("Back"));
+    }
+
+    #[test]
+    fn select_follows_the_preferred_type_order() {
+        let index: CoverArtIndex = serde_json::from_value(json!({
+            "images": [
+                { "id": 1, "types": ["Back"], "image": "back.jpg" },
+                { "id": "2", "types": ["Front"], "front": true, "image": "" },
+                { "id": 3, "types": ["Front"], "front": true, "image": "front.jpg" },
+                { "id": 4, "types": ["Medium"], "image": "medium.jpg" }
+            ]
+        }))
+        .unwrap();
+
+        let pick = |wanted: &[&str]| index.select(&types(wanted)).map(IndexImage::id);
+        assert_eq!(pick(&["front", "back"]).as_deref(), Some("3"));
+        assert_eq!(pick(&["back", "front"]).as_deref(), Some("1"));
+        assert_eq!(pick(&["booklet", "medium"]).as_deref(), Some("4"));
+        assert_eq!(pick(&["tray"]), None);
+        assert_eq!(pick(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn listings_come_from_the_configured_archive() {
+        let (server, request) = serve_once(r#"{"images": []}"#).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig::in_dir_with(
+            dir.path(),
+            ConfigLayer {
+                cover_art: CoverArtLayer {
+                    base_url: Some(format!("{server}/caa/")),
+                    ..CoverArtLayer::default()
+                },
+                ..ConfigLayer::default()
+            },
+        );
+        let client = CoverArtArchiveClient::new(&config).unwrap();
+
+        let index = client.release_index("3a3aca1e").await.unwrap();
+
+        assert!(index.unwrap().images.is_empty());
+        assert_eq!(request.await.unwrap(), "GET /caa/release/3a3aca1e HTTP/1.1");
+    }
+}
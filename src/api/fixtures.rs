@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use super::MetadataProvider;
+use super::cover_art_archive::{ArchiveSource, CoverArtIndex, DownloadedImage};
+use super::musicbrainz::{MusicBrainzError, ResponseSource};
+use super::search::ReleaseQuery;
+use crate::models::{Album, Artist, DiscographyFilter, Release};
+
+/// Serves synthetic MusicBrainz responses from a directory. The bodies are
+/// hand-written to the shape of the web service's JSON, not captured from it.
+///
+/// The directory holds a `manifest.json` mapping request keys to the files
+/// with their bodies. A key is the path followed by the query parameters
+/// sorted by name, e.g. `artist?limit=25&query=Portishead`. Requests without
+/// a fixture fail with [`MusicBrainzError::NotFound`].
+pub struct FixtureSource {
+    dir: PathBuf,
+    responses: HashMap<String, String>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl FixtureSource {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let manifest = dir.join("manifest.json");
+        let contents = fs::read_to_string(&manifest)
+            .with_context(|| format!("unable to read {}", manifest.display()))?;
+        let responses = serde_json::from_str(&contents)
+            .with_context(|| format!("unable to parse {}", manifest.display()))?;
+
+        Ok(Self {
+            dir,
+            responses,
+            requests: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// The fixtures shipped with the crate.
+    pub fn musicbrainz() -> Result<Self> {
+        Self::load(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/musicbrainz"))
+    }
+
+    /// Keys of every request made so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn key(path: &str, query: &[(&str, &str)]) -> String {
+        let mut params = query.to_vec();
+        params.sort();
+        let params = params
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        if params.is_empty() {
+            path.to_string()
+        } else {
+            format!("{path}?{params}")
+        }
+    }
+}
+
+#[async_trait]
+impl ResponseSource for FixtureSource {
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<String, MusicBrainzError> {
+        let key = Self::key(path, query);
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(key.clone());
+        }
+
+        let file = self.responses.get(&key).ok_or(MusicBrainzError::NotFound)?;
+        let body = fs::read_to_string(self.dir.join(file))
+            .unwrap_or_else(|err| panic!("unable to read fixture {file}: {err}"));
+        Ok(body)
+    }
+}
+
+/// A [`MetadataProvider`] answering from in-memory artists, albums and
+/// releases, for exercising code above the MusicBrainz client.
+///
+/// Release searches match on barcode or catalog number when the query has
+/// one. Lookups of unknown IDs fail with [`MusicBrainzError::NotFound`] and
+/// searches without matches with [`MusicBrainzError::Empty`].
+#[derive(Default)]
+pub struct StubProvider {
+    pub artists: Vec<Artist>,
+    pub albums: Vec<Album>,
+    pub releases: Vec<Release>,
+}
+
+fn found<T>(items: Vec<T>) -> Result<Vec<T>, MusicBrainzError> {
+    if items.is_empty() {
+        Err(MusicBrainzError::Empty)
+    } else {
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for StubProvider {
+    async fn search_artists(&self, _query: &str) -> Result<Vec<Artist>, MusicBrainzError> {
+        found(self.artists.clone())
+    }
+
+    async fn search_release_groups(
+        &self,
+        _query: &ReleaseQuery,
+    ) -> Result<Vec<Album>, MusicBrainzError> {
+        found(self.albums.clone())
+    }
+
+    async fn search_releases(
+        &self,
+        query: &ReleaseQuery,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        found(
+            self.releases
+                .iter()
+                .filter(|release| {
+                    (query.barcode.is_empty() || release.barcode == query.barcode)
+                        && (query.catalog_number.is_empty()
+                            || release.catalog_number == query.catalog_number)
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    async fn albums_for_artist(
+        &self,
+        artist_id: &str,
+        filter: &DiscographyFilter,
+    ) -> Result<Vec<Album>, MusicBrainzError> {
+        found(
+            self.albums
+                .iter()
+                .filter(|album| album.artist_id == artist_id && filter.matches(album))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    async fn fetch_album_details(&self, release_group_id: &str) -> Result<Album, MusicBrainzError> {
+        self.albums
+            .iter()
+            .find(|album| album.id == release_group_id)
+            .cloned()
+            .ok_or(MusicBrainzError::NotFound)
+    }
+
+    async fn releases_for_group(
+        &self,
+        release_group_id: &str,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        found(
+            self.releases
+                .iter()
+                .filter(|release| release.release_group_id == release_group_id)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    async fn fetch_album_by_release_id(&self, release_id: &str) -> Result<Album, MusicBrainzError> {
+        let release = self
+            .releases
+            .iter()
+            .find(|release| release.id == release_id)
+            .ok_or(MusicBrainzError::NotFound)?;
+        let album = self.fetch_album_details(&release.release_group_id).await?;
+        Ok(Album {
+            release_id: release.id.clone(),
+            barcode: release.barcode.clone(),
+            ..album
+        })
+    }
+}
+
+/// An [`ArchiveSource`] answering from in-memory listings and images, keyed
+/// by URL. Listings not given are missing (`None`), listing URLs in `broken`
+/// fail, and images not given fail as a `404` would.
+#[derive(Default)]
+pub struct StubArchive {
+    pub listings: HashMap<String, CoverArtIndex>,
+    pub broken: Vec<String>,
+    /// Image bytes and their reported content type.
+    pub images: HashMap<String, (Vec<u8>, Option<String>)>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl StubArchive {
+    /// URLs requested so far, listings and images alike, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, url: &str) {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(url.to_string());
+        }
+    }
+}
+
+#[async_trait]
+impl ArchiveSource for StubArchive {
+    async fn index(&self, url: &str) -> Result<Option<CoverArtIndex>> {
+        self.record(url);
+        if self.broken.iter().any(|broken| broken == url) {
+            return Err(anyhow!("listing returned status 503 Service Unavailable"));
+        }
+        Ok(self.listings.get(url).cloned())
+    }
+
+    async fn download(&self, url: &str) -> Result<DownloadedImage> {
+        self.record(url);
+        let (bytes, content_type) = self
+            .images
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow!("returned status 404 Not Found"))?;
+        Ok(DownloadedImage {
+            bytes,
+            content_type,
+        })
+    }
+}
+
+/// Starts an HTTP server on localhost that answers one request with the JSON
+/// `body`. Returns the server's base URL and a task resolving to the request
+/// line it received, e.g. `GET /ws/2/artist?query=x HTTP/1.1`.
+pub async fn serve_once(body: &'static str) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let request = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buf).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..read]);
+        }
+
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        String::from_utf8_lossy(&head)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    (base_url, request)
+}
@@ -1 +1,10 @@
+pub mod cache;
+pub mod cover_art_archive;
+#[cfg(test)]
+pub mod fixtures;
 pub mod musicbrainz;
+mod provider;
+pub mod retry;
+pub mod search;
+
+pub use provider::MetadataProvider;
@@ -2,87 +2,213 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use chrono::NaiveDate;
-use reqwest::{Client, Url, header};
+use reqwest::{Client, StatusCode, Url, header};
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
 use thiserror::Error;
 use tokio::sync::Mutex;
 use tokio::time::sleep;
 
-use crate::config::AppConfig;
+use super::MetadataProvider;
+use super::cache::{CachedResponse, ResponseCache};
+use super::retry::{self, RetryPolicy};
+use super::search::ReleaseQuery;
+use crate::config::{AppConfig, ResponseCacheSettings};
 use crate::models::album::{Album, TrackInfo};
-use crate::models::artist::Artist;
+use crate::models::artist::{Artist, ArtistCredit};
+use crate::models::date::parse_partial_date;
+use crate::models::discography::DiscographyFilter;
+use crate::models::release::Release;
+
+/// Longest stretch of an unparseable response body kept for error messages.
+const BODY_SNIPPET_LEN: usize = 200;
 
 #[derive(Debug, Error)]
 pub enum MusicBrainzError {
+    #[error("not found on MusicBrainz")]
+    NotFound,
+    /// MusicBrainz answers `503` (and occasionally `429`) when clients exceed
+    /// its rate limit.
+    #[error("rate limited by MusicBrainz")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("MusicBrainz server error ({0})")]
+    ServerError(StatusCode),
+    /// Any other rejected request, such as `400 Bad Request` for a malformed query.
+    #[error("MusicBrainz rejected the request ({0})")]
+    Rejected(StatusCode),
+    #[error("request to MusicBrainz timed out")]
+    Timeout,
     #[error("network error: {0}")]
-    Network(#[from] reqwest::Error),
-    #[error("failed to parse response: {0}")]
-    Parse(String),
+    Network(reqwest::Error),
+    #[error("failed to parse response: {message}")]
+    Parse {
+        message: String,
+        /// The start of the body that failed to parse, or empty.
+        body_snippet: String,
+    },
     #[error("no results returned")]
     Empty,
+    /// Offline mode and the response has not been cached.
+    #[error("not available offline")]
+    Offline,
 }
 
+impl MusicBrainzError {
+    fn parse(message: impl ToString) -> Self {
+        MusicBrainzError::Parse {
+            message: message.to_string(),
+            body_snippet: String::new(),
+        }
+    }
+
+    /// The error followed by what the user can do about it.
+    pub fn user_message(&self) -> String {
+        match self {
+            MusicBrainzError::NotFound => {
+                format!("{self}; check that the MBID is correct")
+            }
+            MusicBrainzError::RateLimited {
+                retry_after: Some(wait),
+            } => format!("{self}; wait {}s and try again", wait.as_secs().max(1)),
+            MusicBrainzError::RateLimited { retry_after: None } => {
+                format!("{self}; wait a moment and try again")
+            }
+            MusicBrainzError::ServerError(_) => {
+                format!("{self}; MusicBrainz may be down, try again later")
+            }
+            MusicBrainzError::Rejected(_) => {
+                format!("{self}; check the search text or ID for unusual characters")
+            }
+            MusicBrainzError::Timeout | MusicBrainzError::Network(_) => {
+                format!("{self}; check your internet connection and try again")
+            }
+            MusicBrainzError::Parse { body_snippet, .. } if !body_snippet.is_empty() => {
+                format!("{self}; MusicBrainz sent an unexpected answer: {body_snippet}")
+            }
+            MusicBrainzError::Offline => {
+                format!("{self}; run without --offline once to fetch and cache it")
+            }
+            MusicBrainzError::Parse { .. } | MusicBrainzError::Empty => self.to_string(),
+        }
+    }
+}
+
+impl From<reqwest::Error> for MusicBrainzError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            MusicBrainzError::Timeout
+        } else {
+            MusicBrainzError::Network(err)
+        }
+    }
+}
+
+/// Reads MusicBrainz metadata and turns it into albums, artists and releases.
 #[derive(Clone)]
 pub struct MusicBrainzClient {
+    source: Arc<dyn ResponseSource>,
+}
+
+/// Where the client gets MusicBrainz JSON from.
+#[async_trait]
+pub trait ResponseSource: Send + Sync {
+    /// The body of the web service response for `path` (relative to the
+    /// service root, e.g. `release-group`) with the given query parameters.
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<String, MusicBrainzError>;
+}
+
+/// Queries the MusicBrainz web service over HTTP, throttled to its rate limit.
+///
+/// Responses are kept in the response cache when one is configured and
+/// reused until their time to live runs out. Stale responses are revalidated
+/// with `If-None-Match`/`If-Modified-Since` so unchanged bodies are not sent
+/// again.
+struct HttpSource {
     http: Client,
     base_headers: header::HeaderMap,
-    throttle: Arc<Mutex<Option<Instant>>>,
+    base_url: String,
+    /// Minimum gap between requests.
+    min_interval: Duration,
+    throttle: Mutex<Option<Instant>>,
+    retry: RetryPolicy,
+    cache: Option<(ResponseCache, ResponseCacheSettings)>,
+    /// Serve only from the cache.
+    offline: bool,
 }
 
 impl MusicBrainzClient {
     pub fn new(config: &AppConfig) -> Result<Self> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_str(config.user_agent())
-                .context("invalid user agent header value")?,
-        );
-        headers.insert(
-            header::ACCEPT,
-            header::HeaderValue::from_static("application/json"),
-        );
-        headers.insert(
-            "X-Client-Id",
-            header::HeaderValue::from_str(config.client_id())
-                .context("invalid client identifier header value")?,
-        );
+        Ok(Self::with_source(HttpSource::new(config)?))
+    }
 
-        let http = Client::builder()
-            .default_headers(headers.clone())
-            .user_agent(config.user_agent())
-            .timeout(Duration::from_secs(30))
-            .connect_timeout(Duration::from_secs(10))
-            .build()
-            .context("unable to construct http client")?;
+    /// A client reading responses from `source` instead of the web service.
+    pub fn with_source(source: impl ResponseSource + 'static) -> Self {
+        Self {
+            source: Arc::new(source),
+        }
+    }
 
-        Ok(Self {
-            http,
-            base_headers: headers,
-            throttle: Arc::new(Mutex::new(None)),
+    /// Fetches and parses one response, keeping the start of the body when it
+    /// does not parse.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, MusicBrainzError> {
+        let body = self.source.get(path, query).await?;
+        serde_json::from_str(&body).map_err(|err| MusicBrainzError::Parse {
+            message: err.to_string(),
+            body_snippet: body.chars().take(BODY_SNIPPET_LEN).collect(),
         })
     }
 
-    pub async fn search_artists(&self, query: &str) -> Result<Vec<Artist>, MusicBrainzError> {
-        let url = Url::parse_with_params(
-            "https://musicbrainz.org/ws/2/artist",
-            [("query", query), ("fmt", "json"), ("limit", "25")],
-        )
-        .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
+    fn build_album_from_release(&self, release: ReleaseItem) -> Result<Album, MusicBrainzError> {
+        let credits = release
+            .artist_credit
+            .into_iter()
+            .map(ArtistCredit::from)
+            .collect::<Vec<_>>();
+        let album = Album {
+            id: release.release_group.id.clone(), // Use release-group ID as primary ID
+            release_id: release.id.clone(),       // Store actual release ID for cover art
+            title: release.title,
+            artist: ArtistCredit::credit_string(&credits),
+            artist_id: credits
+                .first()
+                .map(|credit| credit.artist_id.clone())
+                .unwrap_or_default(),
+            credits,
+            primary_type: release.release_group.primary_type.unwrap_or_default(),
+            secondary_types: release.release_group.secondary_types.unwrap_or_default(),
+            status: release.status.unwrap_or_default(),
+            first_release_date: release.release_group.first_release_date.unwrap_or_default(),
+            disambiguation: release.disambiguation,
+            label: release
+                .label_info
+                .iter()
+                .find_map(|info| info.label.as_ref().and_then(|label| label.name.clone()))
+                .unwrap_or_default(),
+            country: release.country.unwrap_or_default(),
+            barcode: release.barcode.unwrap_or_default(),
+            tracklist: release
+                .media
+                .into_iter()
+                .flat_map(|medium| medium.tracks.into_iter().map(TrackInfo::from))
+                .collect::<Vec<_>>(),
+        };
 
-        self.await_throttle().await;
-        let response = self
-            .http
-            .get(url)
-            .headers(self.base_headers.clone())
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let body: ArtistSearchResponse = response
-            .json()
-            .await
-            .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
+        Ok(album)
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for MusicBrainzClient {
+    async fn search_artists(&self, query: &str) -> Result<Vec<Artist>, MusicBrainzError> {
+        let body: ArtistSearchResponse = self
+            .get("artist", &[("query", query), ("limit", "25")])
+            .await?;
 
         let artists = body
             .artists
@@ -102,47 +228,112 @@ impl MusicBrainzClient {
         Ok(artists)
     }
 
-    pub async fn albums_for_artist(&self, artist_id: &str) -> Result<Vec<Album>, MusicBrainzError> {
+    async fn search_release_groups(
+        &self,
+        query: &ReleaseQuery,
+    ) -> Result<Vec<Album>, MusicBrainzError> {
+        let body: ReleaseGroupSearchResponse = self
+            .get(
+                "release-group",
+                &[("query", &query.release_group_lucene()), ("limit", "25")],
+            )
+            .await?;
+
+        let albums = body
+            .release_groups
+            .into_iter()
+            .map(|group| {
+                let credits = group
+                    .artist_credit
+                    .into_iter()
+                    .map(ArtistCredit::from)
+                    .collect::<Vec<_>>();
+                Album {
+                    id: group.id,
+                    release_id: String::new(), // Filled in when metadata is fetched
+                    title: group.title,
+                    artist: ArtistCredit::credit_string(&credits),
+                    artist_id: credits
+                        .first()
+                        .map(|credit| credit.artist_id.clone())
+                        .unwrap_or_default(),
+                    credits,
+                    primary_type: group.primary_type.unwrap_or_default(),
+                    secondary_types: group.secondary_types.unwrap_or_default(),
+                    status: String::new(),
+                    first_release_date: group.first_release_date.unwrap_or_default(),
+                    disambiguation: group.disambiguation,
+                    label: String::new(),
+                    country: String::new(),
+                    barcode: String::new(),
+                    tracklist: Vec::new(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if albums.is_empty() {
+            return Err(MusicBrainzError::Empty);
+        }
+
+        Ok(albums)
+    }
+
+    async fn search_releases(
+        &self,
+        query: &ReleaseQuery,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        let body: ReleaseSearchResponse = self
+            .get(
+                "release",
+                &[("query", &query.release_lucene()), ("limit", "25")],
+            )
+            .await?;
+
+        let releases = body
+            .releases
+            .into_iter()
+            .map(Release::from)
+            .collect::<Vec<_>>();
+
+        if releases.is_empty() {
+            return Err(MusicBrainzError::Empty);
+        }
+
+        Ok(releases)
+    }
+
+    async fn albums_for_artist(
+        &self,
+        artist_id: &str,
+        filter: &DiscographyFilter,
+    ) -> Result<Vec<Album>, MusicBrainzError> {
         const PAGE_SIZE: usize = 100;
         let mut albums: Vec<Album> = Vec::new();
         let mut offset: usize = 0;
+        let types = filter.type_param();
 
-        // Fetch all album/EP release groups (fast, minimal data)
+        // Fetch the release groups of the wanted types (fast, minimal data)
         loop {
             let limit = PAGE_SIZE.to_string();
             let offset_str = offset.to_string();
-            let url = Url::parse_with_params(
-                "https://musicbrainz.org/ws/2/release-group",
-                [
-                    ("artist", artist_id),
-                    ("fmt", "json"),
-                    ("limit", limit.as_str()),
-                    ("offset", offset_str.as_str()),
-                    ("type", "album|ep"),
-                ],
-            )
-            .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
-
-            self.await_throttle().await;
-            let response = self
-                .http
-                .get(url)
-                .headers(self.base_headers.clone())
-                .send()
-                .await?
-                .error_for_status()?;
-
-            let body: ReleaseGroupResponse = response
-                .json()
-                .await
-                .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
+            let mut query = vec![
+                ("artist", artist_id),
+                ("limit", limit.as_str()),
+                ("offset", offset_str.as_str()),
+                ("type", types.as_str()),
+            ];
+            if filter.official_only {
+                // Leaves out groups with only bootleg, promotion or pseudo-releases.
+                query.push(("release-group-status", "website-default"));
+            }
+            let body: ReleaseGroupResponse = self.get("release-group", &query).await?;
 
             if body.release_groups.is_empty() {
                 break;
             }
 
             let batch_len = body.release_groups.len();
-            
+
             // Convert release groups to minimal Album structs
             for group in body.release_groups {
                 let album = Album {
@@ -150,6 +341,8 @@ impl MusicBrainzClient {
                     release_id: String::new(), // Will be filled in when metadata is fetched
                     title: group.title,
                     artist: String::new(), // Will be filled in when added to library
+                    artist_id: artist_id.to_string(),
+                    credits: Vec::new(),
                     primary_type: group.primary_type.unwrap_or_default(),
                     secondary_types: group.secondary_types.unwrap_or_default(),
                     status: String::new(),
@@ -157,9 +350,12 @@ impl MusicBrainzClient {
                     disambiguation: group.disambiguation,
                     label: String::new(),
                     country: String::new(),
+                    barcode: String::new(),
                     tracklist: Vec::new(),
                 };
-                albums.push(album);
+                if filter.matches(&album) {
+                    albums.push(album);
+                }
             }
 
             offset += batch_len;
@@ -173,8 +369,8 @@ impl MusicBrainzClient {
         }
 
         albums.sort_by(|a, b| {
-            parse_date(&a.first_release_date)
-                .cmp(&parse_date(&b.first_release_date))
+            parse_partial_date(&a.first_release_date)
+                .cmp(&parse_partial_date(&b.first_release_date))
                 .then_with(|| a.first_release_date.cmp(&b.first_release_date))
                 .then_with(|| a.title.cmp(&b.title))
         });
@@ -183,54 +379,27 @@ impl MusicBrainzClient {
     }
 
     /// Fetch full metadata for a release group (used when adding to library)
-    pub async fn fetch_album_details(&self, release_group_id: &str) -> Result<Album, MusicBrainzError> {
+    async fn fetch_album_details(&self, release_group_id: &str) -> Result<Album, MusicBrainzError> {
         // First, get the release group info
-        let url = Url::parse_with_params(
-            &format!("https://musicbrainz.org/ws/2/release-group/{release_group_id}"),
-            [("fmt", "json"), ("inc", "artist-credits")],
-        )
-        .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
-
-        self.await_throttle().await;
-        let response = self
-            .http
-            .get(url)
-            .headers(self.base_headers.clone())
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let group: ReleaseGroupDetail = response
-            .json()
-            .await
-            .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
+        let group: ReleaseGroupDetail = self
+            .get(
+                &format!("release-group/{release_group_id}"),
+                &[("inc", "artist-credits")],
+            )
+            .await?;
 
         // Now fetch releases for this group
-        let url = Url::parse_with_params(
-            "https://musicbrainz.org/ws/2/release",
-            [
-                ("release-group", release_group_id),
-                ("fmt", "json"),
-                ("limit", "100"),
-                ("status", "official"),
-                ("inc", "recordings+labels"),
-            ],
-        )
-        .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
-
-        self.await_throttle().await;
-        let response = self
-            .http
-            .get(url)
-            .headers(self.base_headers.clone())
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let body: ReleaseSearchResponse = response
-            .json()
-            .await
-            .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
+        let body: ReleaseSearchResponse = self
+            .get(
+                "release",
+                &[
+                    ("release-group", release_group_id),
+                    ("limit", "100"),
+                    ("status", "official"),
+                    ("inc", "recordings+labels"),
+                ],
+            )
+            .await?;
 
         if body.releases.is_empty() {
             return Err(MusicBrainzError::Empty);
@@ -247,13 +416,17 @@ impl MusicBrainzClient {
             } else {
                 release_date_raw
             };
-            let sort_date = parse_date(&effective_date).or_else(|| parse_date(&group_first_date));
+            let sort_date = parse_partial_date(&effective_date)
+                .or_else(|| parse_partial_date(&group_first_date));
 
             let should_use = match &best_release {
                 None => true,
-                Some((_, existing_sort, existing_value)) => {
-                    should_replace_release(sort_date, &effective_date, *existing_sort, existing_value)
-                }
+                Some((_, existing_sort, existing_value)) => should_replace_release(
+                    sort_date,
+                    &effective_date,
+                    *existing_sort,
+                    existing_value,
+                ),
             };
 
             if should_use {
@@ -262,16 +435,22 @@ impl MusicBrainzClient {
         }
 
         let (release, _, _) = best_release.ok_or(MusicBrainzError::Empty)?;
+        let credits = group
+            .artist_credit
+            .into_iter()
+            .map(ArtistCredit::from)
+            .collect::<Vec<_>>();
 
         let album = Album {
-            id: group.id.clone(), // Use release-group ID as primary ID
+            id: group.id.clone(),           // Use release-group ID as primary ID
             release_id: release.id.clone(), // Store actual release ID for cover art
             title: group.title,
-            artist: group
-                .artist_credit
+            artist: ArtistCredit::credit_string(&credits),
+            artist_id: credits
                 .first()
-                .map(|credit| credit.artist.name.clone())
+                .map(|credit| credit.artist_id.clone())
                 .unwrap_or_default(),
+            credits,
             primary_type: group.primary_type.unwrap_or_default(),
             secondary_types: group.secondary_types.unwrap_or_default(),
             status: release.status.unwrap_or_default(),
@@ -283,6 +462,7 @@ impl MusicBrainzClient {
                 .find_map(|info| info.label.as_ref().and_then(|label| label.name.clone()))
                 .unwrap_or_default(),
             country: release.country.unwrap_or_default(),
+            barcode: release.barcode.unwrap_or_default(),
             tracklist: release
                 .media
                 .into_iter()
@@ -293,97 +473,232 @@ impl MusicBrainzClient {
         Ok(album)
     }
 
-    /// Fetch album details by release ID (for manual add)
-    pub async fn fetch_album_by_release_id(&self, release_id: &str) -> Result<Album, MusicBrainzError> {
-        // Fetch the release with full details - retry up to 3 times on network errors
-        let url = Url::parse_with_params(
-            &format!("https://musicbrainz.org/ws/2/release/{release_id}"),
-            [("fmt", "json"), ("inc", "recordings+labels+release-groups+artist-credits")],
-        )
-        .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
-
-        let mut last_error = None;
-        for attempt in 1..=3 {
-            self.await_throttle().await;
-            
-            match self
-                .http
-                .get(url.clone())
-                .headers(self.base_headers.clone())
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    match response.error_for_status() {
-                        Ok(resp) => {
-                            let release: ReleaseItem = resp
-                                .json()
-                                .await
-                                .map_err(|err| MusicBrainzError::Parse(err.to_string()))?;
-                            
-                            return self.build_album_from_release(release);
-                        }
-                        Err(e) => {
-                            return Err(e.into());
-                        }
-                    }
-                }
-                Err(e) => {
-                    last_error = Some(e);
-                    if attempt < 3 {
-                        // Wait before retry (exponential backoff)
-                        sleep(Duration::from_millis(1000 * attempt)).await;
-                    }
-                }
+    /// Lists every release (edition) of a release group, oldest first, so the
+    /// user can choose which one to store.
+    async fn releases_for_group(
+        &self,
+        release_group_id: &str,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        const PAGE_SIZE: usize = 100;
+        let mut releases: Vec<Release> = Vec::new();
+        let mut offset: usize = 0;
+
+        loop {
+            let limit = PAGE_SIZE.to_string();
+            let offset_str = offset.to_string();
+            let body: ReleaseSearchResponse = self
+                .get(
+                    "release",
+                    &[
+                        ("release-group", release_group_id),
+                        ("limit", limit.as_str()),
+                        ("offset", offset_str.as_str()),
+                        ("inc", "labels+media"),
+                    ],
+                )
+                .await?;
+
+            let batch_len = body.releases.len();
+            releases.extend(body.releases.into_iter().map(Release::from));
+
+            offset += batch_len;
+            if batch_len < PAGE_SIZE {
+                break;
             }
         }
 
-        Err(last_error.unwrap().into())
+        if releases.is_empty() {
+            return Err(MusicBrainzError::Empty);
+        }
+
+        releases.sort_by(|a, b| {
+            let (a_date, b_date) = (parse_partial_date(&a.date), parse_partial_date(&b.date));
+            a_date
+                .is_none()
+                .cmp(&b_date.is_none())
+                .then(a_date.cmp(&b_date))
+                .then_with(|| a.country.cmp(&b.country))
+        });
+
+        Ok(releases)
     }
 
-    fn build_album_from_release(&self, release: ReleaseItem) -> Result<Album, MusicBrainzError> {
-        let album = Album {
-            id: release.release_group.id.clone(), // Use release-group ID as primary ID
-            release_id: release.id.clone(), // Store actual release ID for cover art
-            title: release.title,
-            artist: release
-                .artist_credit
-                .first()
-                .map(|credit| credit.artist.name.clone())
-                .unwrap_or_default(),
-            primary_type: release.release_group.primary_type.unwrap_or_default(),
-            secondary_types: release.release_group.secondary_types.unwrap_or_default(),
-            status: release.status.unwrap_or_default(),
-            first_release_date: release.release_group.first_release_date.unwrap_or_default(),
-            disambiguation: release.disambiguation,
-            label: release
-                .label_info
-                .iter()
-                .find_map(|info| info.label.as_ref().and_then(|label| label.name.clone()))
-                .unwrap_or_default(),
-            country: release.country.unwrap_or_default(),
-            tracklist: release
-                .media
-                .into_iter()
-                .flat_map(|medium| medium.tracks.into_iter().map(TrackInfo::from))
-                .collect::<Vec<_>>(),
+    /// Fetch album details by release ID (for manual add)
+    async fn fetch_album_by_release_id(&self, release_id: &str) -> Result<Album, MusicBrainzError> {
+        // Fetch the release with full details
+        let release: ReleaseItem = self
+            .get(
+                &format!("release/{release_id}"),
+                &[("inc", "recordings+labels+release-groups+artist-credits")],
+            )
+            .await?;
+
+        self.build_album_from_release(release)
+    }
+}
+
+impl HttpSource {
+    fn new(config: &AppConfig) -> Result<Self> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_str(config.user_agent())
+                .context("invalid user agent header value")?,
+        );
+        headers.insert(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            "X-Client-Id",
+            header::HeaderValue::from_str(config.client_id())
+                .context("invalid client identifier header value")?,
+        );
+
+        let http = Client::builder()
+            .default_headers(headers.clone())
+            .user_agent(config.user_agent())
+            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .context("unable to construct http client")?;
+
+        let cache = match config.response_cache() {
+            Some(settings) => Some((ResponseCache::open(&settings.path)?, settings.clone())),
+            None => None,
         };
 
-        Ok(album)
+        Ok(Self {
+            http,
+            base_headers: headers,
+            base_url: config.musicbrainz_url().to_string(),
+            min_interval: config.musicbrainz_throttle(),
+            throttle: Mutex::new(None),
+            retry: RetryPolicy::default(),
+            cache,
+            offline: config.offline(),
+        })
+    }
+
+    /// How long a response to this request may be reused: searches carry a
+    /// `query` parameter, lookups name an entity in the path and everything
+    /// else browses a list.
+    fn ttl(settings: &ResponseCacheSettings, path: &str, query: &[(&str, &str)]) -> Duration {
+        if query.iter().any(|(name, _)| *name == "query") {
+            settings.search_ttl
+        } else if path.contains('/') {
+            settings.lookup_ttl
+        } else {
+            settings.browse_ttl
+        }
     }
 
     async fn await_throttle(&self) {
+        if self.min_interval.is_zero() {
+            return;
+        }
+
         let mut guard = self.throttle.lock().await;
         if let Some(last) = *guard {
             let elapsed = last.elapsed();
-            if elapsed < Duration::from_millis(1100) {
-                sleep(Duration::from_millis(1100) - elapsed).await;
+            if elapsed < self.min_interval {
+                sleep(self.min_interval - elapsed).await;
             }
         }
         *guard = Some(Instant::now());
     }
 }
 
+#[async_trait]
+impl ResponseSource for HttpSource {
+    /// Sends a throttled GET request, retrying transient failures.
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<String, MusicBrainzError> {
+        let url = Url::parse_with_params(
+            &format!("{}/{path}", self.base_url),
+            query.iter().copied().chain([("fmt", "json")]),
+        )
+        .map_err(MusicBrainzError::parse)?;
+
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|(cache, _)| cache.get(url.as_str()));
+        if self.offline {
+            return cached
+                .map(|entry| entry.body)
+                .ok_or(MusicBrainzError::Offline);
+        }
+        if let (Some(entry), Some((_, settings))) = (&cached, &self.cache)
+            && entry.is_fresh(Self::ttl(settings, path, query))
+        {
+            return Ok(entry.body.clone());
+        }
+
+        let mut headers = self.base_headers.clone();
+        if let Some(entry) = &cached {
+            for (name, value) in [
+                (header::IF_NONE_MATCH, &entry.etag),
+                (header::IF_MODIFIED_SINCE, &entry.last_modified),
+            ] {
+                if let Some(value) = value
+                    .as_deref()
+                    .and_then(|value| header::HeaderValue::from_str(value).ok())
+                {
+                    headers.insert(name, value);
+                }
+            }
+        }
+
+        let response = self
+            .retry
+            .send(|| async {
+                self.await_throttle().await;
+                self.http
+                    .get(url.clone())
+                    .headers(headers.clone())
+                    .send()
+                    .await
+            })
+            .await?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED
+            && let (Some(entry), Some((cache, _))) = (cached, &self.cache)
+        {
+            // A cache that cannot be written only costs a refetch next time.
+            let entry = cache.touch(url.as_str(), entry.clone()).unwrap_or(entry);
+            return Ok(entry.body);
+        }
+        if status.is_success() {
+            let validator = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let etag = validator(header::ETAG);
+            let last_modified = validator(header::LAST_MODIFIED);
+            let body = response.text().await?;
+            if let Some((cache, _)) = &self.cache {
+                let entry = CachedResponse::new(body.clone(), etag, last_modified);
+                let _ = cache.put(url.as_str(), &entry);
+            }
+            return Ok(body);
+        }
+        Err(match status {
+            StatusCode::NOT_FOUND => MusicBrainzError::NotFound,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                MusicBrainzError::RateLimited {
+                    retry_after: retry::retry_after(&response),
+                }
+            }
+            status if status.is_server_error() => MusicBrainzError::ServerError(status),
+            status => MusicBrainzError::Rejected(status),
+        })
+    }
+}
+
 fn should_replace_release(
     new_sort: Option<NaiveDate>,
     new_value: &str,
@@ -411,72 +726,32 @@ fn should_replace_release(
     }
 }
 
-fn parse_date(value: &str) -> Option<NaiveDate> {
-    if value.is_empty() {
-        return None;
-    }
-
-    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
-        return Some(date);
-    }
-
-    if value.len() == 7 {
-        let padded = format!("{value}-01");
-        return NaiveDate::parse_from_str(&padded, "%Y-%m-%d").ok();
-    }
-
-    if value.len() == 4 {
-        if let Ok(year) = value.parse::<i32>() {
-            return NaiveDate::from_ymd_opt(year, 1, 1);
-        }
-    }
-
-    None
-}
-
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct ArtistSearchResponse {
     artists: Vec<ArtistItem>,
 }
 
-impl Default for ArtistSearchResponse {
-    fn default() -> Self {
-        Self {
-            artists: Vec::new(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct ReleaseSearchResponse {
     releases: Vec<ReleaseItem>,
 }
 
-impl Default for ReleaseSearchResponse {
-    fn default() -> Self {
-        Self {
-            releases: Vec::new(),
-        }
-    }
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+struct ReleaseGroupSearchResponse {
+    #[serde(rename = "release-groups")]
+    release_groups: Vec<ReleaseGroupDetail>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct ReleaseGroupResponse {
     #[serde(rename = "release-groups")]
     release_groups: Vec<ReleaseGroupItem>,
 }
 
-impl Default for ReleaseGroupResponse {
-    fn default() -> Self {
-        Self {
-            release_groups: Vec::new(),
-        }
-    }
-}
-
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 struct ReleaseGroupItem {
@@ -517,7 +792,7 @@ struct ReleaseGroupDetail {
     #[serde(rename = "first-release-date")]
     first_release_date: Option<String>,
     #[serde(rename = "artist-credit")]
-    artist_credit: Vec<ArtistCredit>,
+    artist_credit: Vec<ArtistCreditItem>,
 }
 
 impl Default for ReleaseGroupDetail {
@@ -534,7 +809,7 @@ impl Default for ReleaseGroupDetail {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct ArtistItem {
     id: String,
@@ -543,18 +818,7 @@ struct ArtistItem {
     score: i32,
 }
 
-impl Default for ArtistItem {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            name: String::new(),
-            disambiguation: String::new(),
-            score: 0,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct ReleaseItem {
     id: String,
@@ -566,31 +830,23 @@ struct ReleaseItem {
     #[serde(default)]
     date: Option<String>,
     disambiguation: String,
+    barcode: Option<String>,
     #[serde(rename = "release-group")]
     release_group: ReleaseGroup,
     #[serde(rename = "artist-credit")]
-    artist_credit: Vec<ArtistCredit>,
+    artist_credit: Vec<ArtistCreditItem>,
     #[serde(rename = "label-info", default)]
     label_info: Vec<LabelInfo>,
     #[serde(default)]
     media: Vec<Medium>,
+    #[serde(rename = "cover-art-archive")]
+    cover_art_archive: CoverArtArchiveInfo,
 }
 
-impl Default for ReleaseItem {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            title: String::new(),
-            status: None,
-            country: None,
-            date: None,
-            disambiguation: String::new(),
-            release_group: ReleaseGroup::default(),
-            artist_credit: Vec::new(),
-            label_info: Vec::new(),
-            media: Vec::new(),
-        }
-    }
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+struct CoverArtArchiveInfo {
+    front: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -616,47 +872,31 @@ impl Default for ReleaseGroup {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
-struct ArtistCredit {
+struct ArtistCreditItem {
+    /// The name as credited, which may differ from the artist's own name.
+    name: String,
+    joinphrase: String,
     artist: ArtistSubRecord,
 }
 
-impl Default for ArtistCredit {
-    fn default() -> Self {
-        Self {
-            artist: ArtistSubRecord::default(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct ArtistSubRecord {
+    id: String,
     name: String,
 }
 
-impl Default for ArtistSubRecord {
-    fn default() -> Self {
-        Self {
-            name: String::new(),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct LabelInfo {
+    #[serde(rename = "catalog-number")]
+    catalog_number: Option<String>,
     #[serde(default)]
     label: Option<LabelRecord>,
 }
 
-impl Default for LabelInfo {
-    fn default() -> Self {
-        Self { label: None }
-    }
-}
-
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 struct LabelRecord {
@@ -671,23 +911,15 @@ impl Default for LabelRecord {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 #[serde(default)]
 struct Medium {
+    format: Option<String>,
     #[serde(rename = "track-count")]
     track_count: i32,
     tracks: Vec<TrackItem>,
 }
 
-impl Default for Medium {
-    fn default() -> Self {
-        Self {
-            track_count: 0,
-            tracks: Vec::new(),
-        }
-    }
-}
-
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 struct TrackItem {
@@ -729,6 +961,76 @@ impl TextOrNumber {
     }
 }
 
+impl From<ArtistCreditItem> for ArtistCredit {
+    fn from(credit: ArtistCreditItem) -> Self {
+        Self {
+            artist_id: credit.artist.id,
+            name: credit.artist.name,
+            credited_as: credit.name,
+            join_phrase: credit.joinphrase,
+        }
+    }
+}
+
+impl From<ReleaseItem> for Release {
+    fn from(release: ReleaseItem) -> Self {
+        let label = release
+            .label_info
+            .iter()
+            .find_map(|info| info.label.as_ref().and_then(|label| label.name.clone()))
+            .unwrap_or_default();
+        let catalog_number = release
+            .label_info
+            .iter()
+            .find_map(|info| info.catalog_number.clone())
+            .unwrap_or_default();
+
+        let credits = release
+            .artist_credit
+            .into_iter()
+            .map(ArtistCredit::from)
+            .collect::<Vec<_>>();
+
+        Self {
+            id: release.id,
+            title: release.title,
+            artist: ArtistCredit::credit_string(&credits),
+            release_group_id: release.release_group.id,
+            status: release.status.unwrap_or_default(),
+            date: release.date.unwrap_or_default(),
+            country: release.country.unwrap_or_default(),
+            format: format_label(&release.media),
+            label,
+            catalog_number,
+            barcode: release.barcode.unwrap_or_default(),
+            track_count: release.media.iter().map(|medium| medium.track_count).sum(),
+            disambiguation: release.disambiguation,
+            has_front_cover: release.cover_art_archive.front,
+        }
+    }
+}
+
+/// Describes a release's media, grouping repeats: `2×CD + DVD`.
+fn format_label(media: &[Medium]) -> String {
+    let mut groups: Vec<(&str, usize)> = Vec::new();
+    for medium in media {
+        let format = medium.format.as_deref().unwrap_or("Unknown");
+        match groups.last_mut() {
+            Some((last, count)) if *last == format => *count += 1,
+            _ => groups.push((format, 1)),
+        }
+    }
+
+    groups
+        .iter()
+        .map(|(format, count)| match count {
+            1 => format.to_string(),
+            count => format!("{count}×{format}"),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
 impl From<TrackItem> for TrackInfo {
     fn from(track: TrackItem) -> Self {
         let position = track
@@ -745,3 +1047,364 @@ impl From<TrackItem> for TrackInfo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::api::fixtures::{FixtureSource, serve_once};
+    use crate::config::{ConfigLayer, MusicBrainzLayer};
+
+    const PORTISHEAD: &str = "8f6bd1e4-fbe1-4f50-aa9b-94c450ec0f11";
+    const DUMMY: &str = "76df3287-6cda-33eb-8e9a-044b5e15ffdd";
+
+    fn client() -> (MusicBrainzClient, Arc<FixtureSource>) {
+        let source = Arc::new(FixtureSource::musicbrainz().expect("fixtures load"));
+        let client = MusicBrainzClient {
+            source: source.clone(),
+        };
+        (client, source)
+    }
+
+    #[tokio::test]
+    async fn search_artists_keeps_service_order() {
+        let (client, _) = client();
+        let artists = client.search_artists("Portishead").await.unwrap();
+
+        assert_eq!(artists.len(), 2);
+        assert_eq!(artists[0].id, PORTISHEAD);
+        assert_eq!(artists[0].name, "Portishead");
+        assert_eq!(artists[0].score, 100);
+        assert_eq!(artists[1].disambiguation, "DJ");
+    }
+
+    #[tokio::test]
+    async fn search_artists_without_results_is_empty() {
+        let (client, _) = client();
+        let err = client.search_artists("zzzzzz").await.unwrap_err();
+        assert!(matches!(err, MusicBrainzError::Empty));
+    }
+
+    #[tokio::test]
+    async fn search_release_groups_reads_artist_credits() {
+        let (client, _) = client();
+        let albums = client
+            .search_release_groups(&ReleaseQuery::parse("dummy"))
+            .await
+            .unwrap();
+
+        assert_eq!(albums.len(), 2);
+        assert_eq!(albums[0].id, DUMMY);
+        assert_eq!(albums[0].artist, "Portishead");
+        assert_eq!(albums[0].artist_id, PORTISHEAD);
+        assert_eq!(albums[1].primary_type, "Single");
+        assert!(albums[1].secondary_types.is_empty());
+
+        let err = client
+            .search_release_groups(&ReleaseQuery::parse("zzzzzz year:1994"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MusicBrainzError::Empty));
+    }
+
+    #[tokio::test]
+    async fn search_releases_spans_release_groups() {
+        let (client, _) = client();
+        let releases = client
+            .search_releases(&ReleaseQuery::parse("dummy artist:portishead"))
+            .await
+            .unwrap();
+
+        assert_eq!(releases.len(), 4);
+        let vinyl = &releases[0];
+        assert_eq!(vinyl.title, "Dummy");
+        assert_eq!(vinyl.artist, "Portishead");
+        assert_eq!(vinyl.release_group_id, DUMMY);
+        assert_eq!(vinyl.barcode, "042282855329");
+        assert_eq!(vinyl.catalog_number, "828 522-1");
+    }
+
+    #[tokio::test]
+    async fn search_releases_by_printed_code() {
+        let (client, _) = client();
+        let [barcode, _] = ReleaseQuery::for_code("042282855329").try_into().unwrap();
+        let releases = client.search_releases(&barcode).await.unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].id, "a1b2c3d4-0000-4000-8000-000000000004");
+
+        let [catno] = ReleaseQuery::for_code("828 522-1").try_into().unwrap();
+        let releases = client.search_releases(&catno).await.unwrap();
+        assert_eq!(releases.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn albums_for_artist_follows_pages() {
+        let (client, source) = client();
+        let albums = client
+            .albums_for_artist(PORTISHEAD, &DiscographyFilter::default())
+            .await
+            .unwrap();
+
+        assert_eq!(albums.len(), 103);
+        assert_eq!(
+            source.requests(),
+            [
+                format!("release-group?artist={PORTISHEAD}&limit=100&offset=0&type=album|ep"),
+                format!("release-group?artist={PORTISHEAD}&limit=100&offset=100&type=album|ep"),
+            ]
+        );
+        assert!(albums.iter().all(|album| album.artist_id == PORTISHEAD));
+    }
+
+    #[tokio::test]
+    async fn albums_for_artist_sorts_by_first_release_date() {
+        let (client, _) = client();
+        let albums = client
+            .albums_for_artist(PORTISHEAD, &DiscographyFilter::default())
+            .await
+            .unwrap();
+
+        // Undated groups come first, then oldest to newest.
+        assert_eq!(albums[0].first_release_date, "");
+        let dates = albums
+            .iter()
+            .map(|album| parse_partial_date(&album.first_release_date))
+            .collect::<Vec<_>>();
+        assert!(dates.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let dummy = albums.iter().find(|album| album.id == DUMMY).unwrap();
+        assert_eq!(dummy.title, "Dummy");
+        assert_eq!(dummy.primary_type, "Album");
+        assert!(dummy.tracklist.is_empty());
+    }
+
+    #[tokio::test]
+    async fn albums_for_artist_applies_secondary_types_and_years() {
+        let (client, source) = client();
+        let mut filter = DiscographyFilter {
+            exclude_secondary_types: vec![String::from("live")],
+            ..DiscographyFilter::default()
+        };
+        let studio = client.albums_for_artist(PORTISHEAD, &filter).await.unwrap();
+        assert_eq!(studio.len(), 93);
+        assert!(studio.iter().all(|album| album.secondary_types.is_empty()));
+
+        filter.cycle_secondary_type("Live");
+        filter.set_years("1990-1999").unwrap();
+        let live = client.albums_for_artist(PORTISHEAD, &filter).await.unwrap();
+        assert_eq!(live.len(), 3);
+        assert!(
+            live.iter()
+                .all(|album| album.first_release_date.starts_with("199"))
+        );
+
+        // Secondary types and years do not change what is requested.
+        let requests = source.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[..2], requests[2..]);
+    }
+
+    #[tokio::test]
+    async fn albums_for_artist_official_only_asks_musicbrainz() {
+        let (client, source) = client();
+        let filter = DiscographyFilter {
+            official_only: true,
+            ..DiscographyFilter::default()
+        };
+        client.albums_for_artist(PORTISHEAD, &filter).await.unwrap();
+
+        assert!(
+            source
+                .requests()
+                .iter()
+                .all(|key| key.contains("release-group-status=website-default"))
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_album_details_uses_earliest_release() {
+        let (client, _) = client();
+        let album = client.fetch_album_details(DUMMY).await.unwrap();
+
+        assert_eq!(album.id, DUMMY);
+        assert_eq!(album.release_id, "a1b2c3d4-0000-4000-8000-000000000001");
+        assert_eq!(album.country, "GB");
+        assert_eq!(album.label, "Go! Beat");
+        assert_eq!(album.artist, "Portishead");
+        assert_eq!(album.artist_id, PORTISHEAD);
+        assert_eq!(album.first_release_date, "1994-08-22");
+        assert_eq!(album.tracklist.len(), 11);
+    }
+
+    #[tokio::test]
+    async fn fetch_album_details_of_unknown_group_is_not_found() {
+        let (client, _) = client();
+        let err = client
+            .fetch_album_details("00000000-0000-0000-0000-000000000000")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MusicBrainzError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn releases_for_group_lists_dated_releases_first() {
+        let (client, _) = client();
+        let releases = client.releases_for_group(DUMMY).await.unwrap();
+
+        let order = releases
+            .iter()
+            .map(|release| release.country.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["GB", "US", "GB", "XE"]);
+
+        let vinyl = &releases[2];
+        assert_eq!(vinyl.date, "2008");
+        assert_eq!(vinyl.format, "2×12\" Vinyl");
+        assert_eq!(vinyl.catalog_number, "828 522-1");
+        assert_eq!(vinyl.track_count, 11);
+        assert!(!releases[3].has_front_cover);
+    }
+
+    #[tokio::test]
+    async fn fetch_album_by_release_id_parses_tracks() {
+        let (client, _) = client();
+        let album = client
+            .fetch_album_by_release_id("a1b2c3d4-0000-4000-8000-000000000004")
+            .await
+            .unwrap();
+
+        assert_eq!(album.id, DUMMY);
+        assert_eq!(album.release_id, "a1b2c3d4-0000-4000-8000-000000000004");
+        assert_eq!(album.barcode, "042282855329");
+        assert_eq!(album.tracklist.len(), 11);
+
+        // An empty position falls back to the vinyl side number.
+        let first = &album.tracklist[0];
+        assert_eq!(first.position, "A1");
+        assert_eq!(first.title, "Mysterons");
+        assert_eq!(first.length_ms, 201_000);
+
+        // Numeric positions are kept as text, and a missing length is zero.
+        let biscuit = album
+            .tracklist
+            .iter()
+            .find(|track| track.title == "Biscuit")
+            .unwrap();
+        assert_eq!(biscuit.position, "4");
+        assert_eq!(biscuit.length_ms, 0);
+    }
+
+    #[tokio::test]
+    async fn truncated_response_is_a_parse_error() {
+        let (client, _) = client();
+        let err = client
+            .fetch_album_by_release_id("a1b2c3d4-0000-4000-8000-00000000dead")
+            .await
+            .unwrap_err();
+
+        let MusicBrainzError::Parse { body_snippet, .. } = err else {
+            panic!("expected a parse error, got {err:?}");
+        };
+        assert!(body_snippet.starts_with('{'));
+    }
+
+    #[tokio::test]
+    async fn requests_go_to_the_configured_server() {
+        let (server, request) = serve_once(include_str!(
+            "../../tests/fixtures/musicbrainz/artist-search-portishead.json"
+        ))
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig::in_dir_with(
+            dir.path(),
+            ConfigLayer {
+                musicbrainz: MusicBrainzLayer {
+                    base_url: Some(format!("{server}/ws/2/")),
+                    ..MusicBrainzLayer::default()
+                },
+                ..ConfigLayer::default()
+            },
+        );
+        let client = MusicBrainzClient::new(&config).unwrap();
+
+        let artists = client.search_artists("Portishead").await.unwrap();
+
+        assert_eq!(artists[0].id, PORTISHEAD);
+        let request = request.await.unwrap();
+        assert!(request.starts_with("GET /ws/2/artist?"), "{request}");
+        assert!(request.contains("query=Portishead"), "{request}");
+    }
+
+    #[test]
+    fn cache_ttl_depends_on_the_kind_of_request() {
+        let settings = ResponseCacheSettings {
+            path: Default::default(),
+            search_ttl: Duration::from_secs(1),
+            browse_ttl: Duration::from_secs(2),
+            lookup_ttl: Duration::from_secs(3),
+        };
+        let ttl = |path, query: &[(&str, &str)]| HttpSource::ttl(&settings, path, query).as_secs();
+
+        assert_eq!(
+            ttl("artist", &[("query", "Portishead"), ("limit", "25")]),
+            1
+        );
+        assert_eq!(ttl("release-group", &[("artist", PORTISHEAD)]), 2);
+        assert_eq!(ttl("release", &[("release-group", DUMMY)]), 2);
+        assert_eq!(
+            ttl(
+                &format!("release-group/{DUMMY}"),
+                &[("inc", "artist-credits")]
+            ),
+            3
+        );
+    }
+
+    #[test]
+    fn track_position_accepts_text_or_number() {
+        let tracks: Vec<TrackItem> = serde_json::from_str(
+            r#"[
+                {"id": "a", "position": 3, "number": "3", "title": "Numb", "length": 238000},
+                {"id": "b", "position": "", "number": "B2", "title": "Roads", "length": null},
+                {"id": "c", "title": "Untitled"}
+            ]"#,
+        )
+        .unwrap();
+        let tracks = tracks.into_iter().map(TrackInfo::from).collect::<Vec<_>>();
+
+        assert_eq!(tracks[0].position, "3");
+        assert_eq!(tracks[0].length_ms, 238_000);
+        assert_eq!(tracks[1].position, "B2");
+        assert_eq!(tracks[1].length_ms, 0);
+        assert_eq!(tracks[2].position, "");
+    }
+
+    #[test]
+    fn release_replacement_prefers_earlier_dates() {
+        let date = |value| parse_partial_date(value);
+
+        assert!(should_replace_release(
+            date("1994-08-22"),
+            "1994-08-22",
+            date("1994-10-18"),
+            "1994-10-18",
+        ));
+        assert!(!should_replace_release(
+            date("1995"),
+            "1995",
+            date("1994-10"),
+            "1994-10",
+        ));
+        // Equal dates fall back to comparing the raw values.
+        assert!(!should_replace_release(
+            date("1994-01-01"),
+            "1994-01-01",
+            date("1994"),
+            "1994",
+        ));
+        assert!(should_replace_release(date("2001"), "2001", None, ""));
+        assert!(!should_replace_release(None, "", date("2001"), "2001"));
+        assert!(!should_replace_release(None, "", None, ""));
+    }
+}
@@ -0,0 +1,43 @@
+use async_trait::async_trait;
+
+use super::musicbrainz::MusicBrainzError;
+use super::search::ReleaseQuery;
+use crate::models::{Album, Artist, DiscographyFilter, Release};
+
+/// Looks up artists, albums and releases. The app talks to MusicBrainz
+/// through this so the source can be swapped, e.g. for test fixtures.
+#[async_trait]
+pub trait MetadataProvider: Send + Sync {
+    /// Artists matching `query`, best match first.
+    async fn search_artists(&self, query: &str) -> Result<Vec<Artist>, MusicBrainzError>;
+
+    /// Release groups matching `query`, best match first, with their artist
+    /// credits but no release details.
+    async fn search_release_groups(
+        &self,
+        query: &ReleaseQuery,
+    ) -> Result<Vec<Album>, MusicBrainzError>;
+
+    /// Releases matching `query`, best match first.
+    async fn search_releases(&self, query: &ReleaseQuery)
+    -> Result<Vec<Release>, MusicBrainzError>;
+
+    /// The artist's release groups that pass `filter`, oldest first.
+    async fn albums_for_artist(
+        &self,
+        artist_id: &str,
+        filter: &DiscographyFilter,
+    ) -> Result<Vec<Album>, MusicBrainzError>;
+
+    /// Full metadata for a release group, taken from its earliest official release.
+    async fn fetch_album_details(&self, release_group_id: &str) -> Result<Album, MusicBrainzError>;
+
+    /// Every release (edition) of a release group, oldest first.
+    async fn releases_for_group(
+        &self,
+        release_group_id: &str,
+    ) -> Result<Vec<Release>, MusicBrainzError>;
+
+    /// Full metadata taken from one specific release.
+    async fn fetch_album_by_release_id(&self, release_id: &str) -> Result<Album, MusicBrainzError>;
+}
@@ -0,0 +1,257 @@
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use reqwest::{Response, StatusCode, header};
+use tokio::time::sleep;
+
+/// How failed HTTP requests are retried.
+///
+/// Connection failures, timeouts, `429 Too Many Requests` and `5xx` responses
+/// are retried with exponential backoff and jitter, waiting for the server's
+/// `Retry-After` instead when it sends one. Other `4xx` responses are final.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first.
+    max_attempts: u32,
+    /// Delay before the first retry; doubled for each one after.
+    base_delay: Duration,
+    /// Longest delay between attempts. A `Retry-After` beyond this is not waited for.
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Runs `attempt` until it succeeds, fails permanently or runs out of
+    /// attempts. Returns the last response, which may still be an error
+    /// status, or the last transport error.
+    pub async fn send<F, Fut>(&self, mut attempt: F) -> reqwest::Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = reqwest::Result<Response>>,
+    {
+        let mut tries = 1;
+        loop {
+            let result = attempt().await;
+            if tries >= self.max_attempts {
+                return result;
+            }
+
+            let delay = match &result {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    match retry_after(response) {
+                        Some(wait) if wait > self.max_delay => return result,
+                        Some(wait) => wait,
+                        None => self.backoff(tries),
+                    }
+                }
+                Ok(_) => return result,
+                Err(err) if is_retryable_error(err) => self.backoff(tries),
+                Err(_) => return result,
+            };
+
+            sleep(delay).await;
+            tries += 1;
+        }
+    }
+
+    /// Exponential delay before retry number `retry`, with up to half of it
+    /// randomised so concurrent clients do not retry in lockstep.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(1 << (retry - 1).min(16))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Connection failures and timeouts are worth another try; malformed requests,
+/// redirect loops and undecodable bodies are not.
+fn is_retryable_error(err: &reqwest::Error) -> bool {
+    err.is_connect() || err.is_timeout() || (err.is_request() && !err.is_builder())
+}
+
+/// The wait requested by a `Retry-After` header, given either in seconds or
+/// as an HTTP date.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let when = httpdate::parse_http_date(value).ok()?;
+    Some(
+        when.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn response(status: u16, retry_after: Option<&str>) -> Response {
+        let mut builder = http::Response::builder().status(status);
+        if let Some(value) = retry_after {
+            builder = builder.header(header::RETRY_AFTER, value);
+        }
+        Response::from(builder.body(String::new()).unwrap())
+    }
+
+    fn quick_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_within_jitter_bounds() {
+        let policy = RetryPolicy::default();
+        for (retry, full) in [(1, 1), (2, 2), (3, 4), (4, 8), (5, 16)] {
+            let full = Duration::from_secs(full);
+            for _ in 0..100 {
+                let delay = policy.backoff(retry);
+                assert!(
+                    delay >= full / 2 && delay <= full,
+                    "retry {retry} waited {delay:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped_at_thirty_seconds() {
+        let policy = RetryPolicy::default();
+        for retry in [6, 10, 17, 40, u32::MAX] {
+            let delay = policy.backoff(retry);
+            assert!(delay >= Duration::from_secs(15) && delay <= Duration::from_secs(30));
+        }
+    }
+
+    #[test]
+    fn retry_after_reads_seconds_and_http_dates() {
+        assert_eq!(
+            retry_after(&response(429, Some("120"))),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            retry_after(&response(503, Some(" 0 "))),
+            Some(Duration::ZERO)
+        );
+
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(90));
+        let wait = retry_after(&response(503, Some(&later))).unwrap();
+        assert!(wait > Duration::from_secs(85) && wait <= Duration::from_secs(90));
+
+        let earlier = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(90));
+        assert_eq!(
+            retry_after(&response(503, Some(&earlier))),
+            Some(Duration::ZERO)
+        );
+
+        assert_eq!(retry_after(&response(503, None)), None);
+        assert_eq!(retry_after(&response(503, Some("soon"))), None);
+        assert_eq!(retry_after(&response(503, Some("-5"))), None);
+    }
+
+    #[test]
+    fn only_rate_limits_and_server_errors_are_retried() {
+        for status in [429, 500, 502, 503, 504] {
+            assert!(is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 301, 400, 401, 403, 404, 422] {
+            assert!(!is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn connection_failures_and_timeouts_are_retried() {
+        let client = reqwest::Client::new();
+
+        // Nothing listens on a port once its listener is dropped.
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let refused = client.get(&url).send().await.unwrap_err();
+        assert!(is_retryable_error(&refused));
+
+        // A server that accepts but never answers.
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", silent.local_addr().unwrap());
+        let timed_out = client
+            .get(&url)
+            .timeout(Duration::from_millis(50))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(is_retryable_error(&timed_out));
+
+        let malformed = client.get("not a url").send().await.unwrap_err();
+        assert!(!is_retryable_error(&malformed));
+    }
+
+    /// Runs `policy` over `responses`, returning the final status and the
+    /// number of attempts made.
+    async fn attempts(policy: &RetryPolicy, responses: &[(u16, Option<&str>)]) -> (u16, u32) {
+        let count = AtomicU32::new(0);
+        let result = policy
+            .send(|| {
+                let n = count.fetch_add(1, Ordering::SeqCst) as usize;
+                let (status, retry_after) = responses[n.min(responses.len() - 1)];
+                async move { Ok(response(status, retry_after)) }
+            })
+            .await
+            .unwrap();
+        (result.status().as_u16(), count.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn send_retries_until_success_or_out_of_attempts() {
+        let policy = quick_policy();
+        assert_eq!(
+            attempts(&policy, &[(503, None), (200, None)]).await,
+            (200, 2)
+        );
+        assert_eq!(
+            attempts(&policy, &[(429, Some("0")), (200, None)]).await,
+            (200, 2)
+        );
+        assert_eq!(attempts(&policy, &[(500, None)]).await, (500, 3));
+        assert_eq!(attempts(&policy, &[(404, None)]).await, (404, 1));
+    }
+
+    #[tokio::test]
+    async fn send_gives_up_when_retry_after_exceeds_the_cap() {
+        let policy = quick_policy();
+        assert_eq!(
+            attempts(&policy, &[(503, Some("60")), (200, None)]).await,
+            (503, 1)
+        );
+    }
+}
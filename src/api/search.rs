@@ -0,0 +1,197 @@
+/// What to look for when searching releases or release groups. Every field
+/// that is set must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReleaseQuery {
+    pub title: String,
+    pub artist: String,
+    /// Year of release; for release groups, of first release.
+    pub year: Option<i32>,
+    /// UPC/EAN barcode. Releases only.
+    pub barcode: String,
+    /// Label catalog number. Releases only.
+    pub catalog_number: String,
+}
+
+impl ReleaseQuery {
+    /// Reads search box text such as `dummy artist:portishead year:1994`.
+    /// `artist:`, `year:`, `barcode:` and `catno:` take the following word or
+    /// quoted phrase; everything else is the title.
+    pub fn parse(text: &str) -> Self {
+        let mut query = Self::default();
+        let mut title = Vec::new();
+        let mut rest = text.trim_start();
+
+        while !rest.is_empty() {
+            let (field, value_start) = match rest.split_once(':') {
+                Some((field, value))
+                    if !field.contains(char::is_whitespace)
+                        && matches!(
+                            field.to_ascii_lowercase().as_str(),
+                            "artist" | "year" | "barcode" | "catno"
+                        ) =>
+                {
+                    (Some(field.to_ascii_lowercase()), value)
+                }
+                _ => (None, rest),
+            };
+
+            let (word, remainder) = match value_start.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => value_start
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((value_start, "")),
+            };
+            rest = remainder.trim_start();
+
+            match field.as_deref() {
+                Some("artist") => query.artist = word.to_string(),
+                Some("year") => query.year = word.parse().ok(),
+                Some("barcode") => query.barcode = word.to_string(),
+                Some("catno") => query.catalog_number = word.to_string(),
+                _ => title.push(word),
+            }
+        }
+
+        query.title = title.join(" ");
+        query
+    }
+
+    /// Searches for a code printed on a CD or record: a UPC/EAN barcode when
+    /// it is 8 to 14 digits, then the same text as a catalog number, since
+    /// some labels number their catalog with digits only.
+    pub fn for_code(code: &str) -> Vec<Self> {
+        let code = code.trim();
+        let digits = code
+            .chars()
+            .filter(|ch| !matches!(ch, ' ' | '-'))
+            .collect::<String>();
+        let catalog_number = Self {
+            catalog_number: code.to_string(),
+            ..Self::default()
+        };
+
+        if (8..=14).contains(&digits.len()) && digits.chars().all(|ch| ch.is_ascii_digit()) {
+            let barcode = Self {
+                barcode: digits,
+                ..Self::default()
+            };
+            vec![barcode, catalog_number]
+        } else if code.is_empty() {
+            Vec::new()
+        } else {
+            vec![catalog_number]
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_empty()
+            && self.artist.is_empty()
+            && self.year.is_none()
+            && self.barcode.is_empty()
+            && self.catalog_number.is_empty()
+    }
+
+    /// Lucene query for the release-group search. Barcode and catalog number
+    /// belong to releases and are not part of it.
+    pub fn release_group_lucene(&self) -> String {
+        lucene(&[
+            ("releasegroup", phrase(&self.title)),
+            ("artist", phrase(&self.artist)),
+            ("firstreleasedate", year_prefix(self.year)),
+        ])
+    }
+
+    /// Lucene query for the release search.
+    pub fn release_lucene(&self) -> String {
+        lucene(&[
+            ("release", phrase(&self.title)),
+            ("artist", phrase(&self.artist)),
+            ("date", year_prefix(self.year)),
+            ("barcode", phrase(&self.barcode)),
+            ("catno", phrase(&self.catalog_number)),
+        ])
+    }
+}
+
+/// Joins the terms that have a value with `AND`.
+fn lucene(terms: &[(&str, Option<String>)]) -> String {
+    terms
+        .iter()
+        .filter_map(|(field, value)| value.as_ref().map(|value| format!("{field}:{value}")))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// A quoted Lucene phrase, or `None` for empty text.
+fn phrase(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"");
+    Some(format!("\"{escaped}\""))
+}
+
+/// Matches any date in `year`, such as `1994-08-22`.
+fn year_prefix(year: Option<i32>) -> Option<String> {
+    year.map(|year| format!("{year}*"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_fields_and_title() {
+        let query = ReleaseQuery::parse(r#"dummy Artist:portishead year:1994 catno:"828 522-1""#);
+        assert_eq!(
+            query,
+            ReleaseQuery {
+                title: String::from("dummy"),
+                artist: String::from("portishead"),
+                year: Some(1994),
+                barcode: String::new(),
+                catalog_number: String::from("828 522-1"),
+            }
+        );
+
+        let query = ReleaseQuery::parse("  the  good son  ");
+        assert_eq!(query.title, "the good son");
+        assert!(ReleaseQuery::parse("   ").is_empty());
+    }
+
+    #[test]
+    fn codes_try_barcode_before_catalog_number() {
+        let queries = ReleaseQuery::for_code(" 0 42282-85532 9 ");
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].barcode, "042282855329");
+        assert_eq!(queries[0].release_lucene(), r#"barcode:"042282855329""#);
+        assert_eq!(queries[1].catalog_number, "0 42282-85532 9");
+
+        let queries = ReleaseQuery::for_code("828 522-2");
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].release_lucene(), r#"catno:"828 522-2""#);
+
+        assert!(ReleaseQuery::for_code("  ").is_empty());
+    }
+
+    #[test]
+    fn lucene_quotes_values_and_skips_unset_fields() {
+        let query = ReleaseQuery {
+            title: String::from(r#"Say "Hi""#),
+            artist: String::from("AC/DC"),
+            year: Some(1979),
+            barcode: String::from("075678"),
+            ..ReleaseQuery::default()
+        };
+
+        assert_eq!(
+            query.release_group_lucene(),
+            r#"releasegroup:"Say \"Hi\"" AND artist:"AC/DC" AND firstreleasedate:1979*"#
+        );
+        assert_eq!(
+            query.release_lucene(),
+            r#"release:"Say \"Hi\"" AND artist:"AC/DC" AND date:1979* AND barcode:"075678""#
+        );
+    }
+}
@@ -1,16 +1,36 @@
-use crate::models::{Album, AlbumRecord, Artist, CoverArtStatus};
+use crate::models::{Album, AlbumRecord, Artist, CoverArtSource, CoverArtStatus, Release};
+use crate::notes::NotePlan;
 
 #[derive(Debug, Clone)]
 pub enum AppMessage {
     ArtistResults(Vec<Artist>),
     AlbumsLoaded(Vec<Album>),
+    /// Release groups found by an album search.
+    AlbumSearchResults {
+        query: String,
+        albums: Vec<Album>,
+    },
+    /// Releases found by a release search, to pick one to add.
+    ReleaseSearchResults {
+        query: String,
+        releases: Vec<Release>,
+    },
+    /// Editions of a release group to choose from.
+    ReleasesLoaded {
+        release_group_id: String,
+        title: String,
+        releases: Vec<Release>,
+    },
     SearchFailed(String),
     CoverArtStatus {
         mbid: String,
         status: CoverArtStatus,
         path: Option<String>,
+        source: Option<CoverArtSource>,
     },
     DownloadLog(String),
     LibraryRefreshed(Vec<AlbumRecord>),
+    /// Rendered notes awaiting confirmation before they are written.
+    NotePreview(Vec<NotePlan>),
     NotesGenerated(Vec<String>),
 }
@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::api::search::ReleaseQuery;
+use crate::config::{ConfigLayer, CoverArtLayer, DiscographyLayer, MusicBrainzLayer, VaultLayer};
+use crate::models::discography::PRIMARY_TYPES;
+
+/// Search MusicBrainz, build an album library and generate Obsidian notes.
+#[derive(Debug, Parser)]
+#[command(name = "obsidian-album-propogator", version, about)]
+pub struct Cli {
+    /// Run a single command instead of the interactive interface.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Print machine-readable JSON instead of text.
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Config file to load instead of the default XDG location.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Base directory for the library database, notes and cover art.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Directory where downloaded cover art is stored.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub album_art_dir: Option<PathBuf>,
+
+    /// Directory where generated notes are written.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub notes_dir: Option<PathBuf>,
+
+    /// Location of the sled library database.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub db_path: Option<PathBuf>,
+
+    /// Directory containing named note templates (`<name>.md`).
+    #[arg(long, global = true, value_name = "DIR")]
+    pub templates_dir: Option<PathBuf>,
+
+    /// Note location relative to the notes folder, e.g. `{artist}/{year} - {title}.md`.
+    #[arg(long, global = true, value_name = "PATTERN")]
+    pub note_path_pattern: Option<String>,
+
+    /// MusicBrainz web service to query, e.g. a mirror at `http://localhost:5000/ws/2`.
+    #[arg(long, global = true, value_name = "URL")]
+    pub musicbrainz_url: Option<String>,
+
+    /// Answer MusicBrainz lookups from the response cache only, without network access.
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Cover Art Archive to download from.
+    #[arg(long, global = true, value_name = "URL")]
+    pub cover_art_url: Option<String>,
+
+    /// Obsidian vault to write notes and cover art into.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub vault: Option<PathBuf>,
+
+    /// Folder for album notes, relative to the vault root.
+    #[arg(long, global = true, value_name = "FOLDER")]
+    pub vault_notes_folder: Option<PathBuf>,
+
+    /// Folder for cover art, relative to the vault root.
+    #[arg(long, global = true, value_name = "FOLDER")]
+    pub vault_attachments_folder: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Command-line overrides, applied on top of the config file and environment.
+    pub fn config_overrides(&self) -> ConfigLayer {
+        ConfigLayer {
+            data_dir: self.data_dir.clone(),
+            album_art_dir: self.album_art_dir.clone(),
+            notes_dir: self.notes_dir.clone(),
+            db_path: self.db_path.clone(),
+            templates_dir: self.templates_dir.clone(),
+            default_template: None,
+            template_rules: None,
+            note_path_pattern: self.note_path_pattern.clone(),
+            artist_template: None,
+            artist_note_path_pattern: None,
+            cover_art: CoverArtLayer {
+                base_url: self.cover_art_url.clone(),
+                ..CoverArtLayer::default()
+            },
+            musicbrainz: MusicBrainzLayer {
+                base_url: self.musicbrainz_url.clone(),
+                offline: self.offline.then_some(true),
+                ..MusicBrainzLayer::default()
+            },
+            discography: DiscographyLayer::default(),
+            vault: VaultLayer {
+                root: self.vault.clone(),
+                notes_folder: self.vault_notes_folder.clone(),
+                attachments_folder: self.vault_attachments_folder.clone(),
+            },
+        }
+    }
+}
+
+/// Headless commands for scripting library population.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Search MusicBrainz for artists by name.
+    SearchArtist {
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+    },
+    /// Search MusicBrainz for release groups (albums); add one with `add <id>`.
+    SearchAlbum {
+        #[command(flatten)]
+        query: ReleaseQueryArgs,
+    },
+    /// Search MusicBrainz for releases (editions); add one with `add <release-id>`.
+    SearchRelease {
+        #[command(flatten)]
+        query: ReleaseQueryArgs,
+        /// UPC/EAN barcode printed on the release.
+        #[arg(long)]
+        barcode: Option<String>,
+        /// Label catalog number, e.g. `828 522-2`.
+        #[arg(long, value_name = "CATNO")]
+        catno: Option<String>,
+    },
+    /// List an artist's release groups, filtered by the configured discography defaults.
+    ListAlbums {
+        artist_mbid: String,
+        #[command(flatten)]
+        filter: DiscographyArgs,
+    },
+    /// List the releases (editions) of a release group; add one with `add <release-id>`.
+    ListReleases { release_group_mbid: String },
+    /// Add albums by release or release-group MBID and fetch their cover art.
+    Add {
+        #[arg(required = true, value_name = "ID")]
+        ids: Vec<String>,
+    },
+    /// Add albums by the UPC/EAN barcode or catalog number printed on a CD or record.
+    AddCode {
+        #[arg(required = true, value_name = "CODE")]
+        codes: Vec<String>,
+    },
+    /// Inspect the album library.
+    #[command(subcommand)]
+    Library(LibraryCommand),
+    /// Work with generated notes.
+    #[command(subcommand)]
+    Notes(NotesCommand),
+    /// Work with cover art.
+    #[command(subcommand)]
+    Covers(CoversCommand),
+}
+
+/// Title, artist and year of a release or release-group search.
+#[derive(Debug, Args)]
+pub struct ReleaseQueryArgs {
+    /// Title words.
+    pub title: Vec<String>,
+    /// Artist name as credited.
+    #[arg(long)]
+    pub artist: Option<String>,
+    /// Year of release.
+    #[arg(long)]
+    pub year: Option<i32>,
+}
+
+impl ReleaseQueryArgs {
+    pub fn into_query(self) -> ReleaseQuery {
+        ReleaseQuery {
+            title: self.title.join(" "),
+            artist: self.artist.unwrap_or_default(),
+            year: self.year,
+            ..ReleaseQuery::default()
+        }
+    }
+}
+
+/// Overrides of the configured discography filter.
+#[derive(Debug, Args)]
+pub struct DiscographyArgs {
+    /// Primary types to list, e.g. `album,single`.
+    #[arg(long, value_name = "TYPES", value_delimiter = ',', value_parser = PRIMARY_TYPES)]
+    pub types: Option<Vec<String>>,
+    /// Hide release groups with any of these secondary types, e.g. `live,compilation`.
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    pub hide: Option<Vec<String>>,
+    /// List only release groups with one of these secondary types, e.g. `soundtrack`.
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    pub only: Option<Vec<String>>,
+    /// Leave out release groups with only bootleg, promotion or pseudo-releases.
+    #[arg(long)]
+    pub official: bool,
+    /// First release year or range, e.g. `1994`, `1990-1999` or `2000-`.
+    #[arg(long, value_name = "RANGE")]
+    pub years: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LibraryCommand {
+    /// List every album in the library.
+    List,
+    /// Pick the note template for an album, or clear it to follow the template rules.
+    SetTemplate {
+        /// Release-group MBID of an album in the library.
+        mbid: String,
+        /// Template name (a file stem in the templates directory). Omit to clear.
+        template: Option<String>,
+    },
+    /// Download every Cover Art Archive image of an album into its gallery folder.
+    SetGallery {
+        /// Release-group MBID of an album in the library.
+        mbid: String,
+        /// Turn the gallery off instead. Files already downloaded are kept.
+        #[arg(long)]
+        off: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NotesCommand {
+    /// Generate notes for albums that do not have one yet.
+    Generate {
+        /// Also update the managed properties and sections of existing notes.
+        #[arg(long)]
+        sync: bool,
+        /// Render every album with this template instead of its own.
+        #[arg(long, value_name = "NAME")]
+        template: Option<String>,
+        /// Show what would be written, with a diff against existing notes, without writing.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the available note templates.
+    Templates,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CoversCommand {
+    /// Download cover art for albums that do not have it yet.
+    Fetch,
+}
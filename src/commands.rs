@@ -0,0 +1,625 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::timeout;
+
+use crate::api::musicbrainz::MusicBrainzError;
+use crate::api::search::ReleaseQuery;
+use crate::app::AppMessage;
+use crate::cli::{Command, CoversCommand, DiscographyArgs, LibraryCommand, NotesCommand};
+use crate::models::{AlbumRecord, Artist, CoverArtStatus};
+use crate::notes::{NoteAction, NoteMode};
+use crate::tui::AppController;
+
+/// How long to wait for any word from the cover art downloader before giving
+/// up on the albums still queued.
+const COVER_ART_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Runs one CLI command to completion. Results go to stdout (as JSON with
+/// `--json`), progress messages from background tasks go to stderr.
+pub async fn run(
+    command: Command,
+    json: bool,
+    controller: AppController,
+    msg_rx: UnboundedReceiver<AppMessage>,
+) -> Result<()> {
+    let mut session = Session {
+        controller,
+        msg_rx,
+        json,
+        cover_art_timeout: COVER_ART_IDLE_TIMEOUT,
+    };
+
+    match command {
+        Command::SearchArtist { query } => session.search_artist(&query.join(" ")).await,
+        Command::SearchAlbum { query } => session.search_album(query.into_query()).await,
+        Command::SearchRelease {
+            query,
+            barcode,
+            catno,
+        } => {
+            let query = ReleaseQuery {
+                barcode: barcode.unwrap_or_default(),
+                catalog_number: catno.unwrap_or_default(),
+                ..query.into_query()
+            };
+            session.search_release(query).await
+        }
+        Command::ListAlbums {
+            artist_mbid,
+            filter,
+        } => session.list_albums(artist_mbid, filter).await,
+        Command::ListReleases { release_group_mbid } => {
+            session.list_releases(&release_group_mbid).await
+        }
+        Command::Add { ids } => session.add(&ids).await,
+        Command::AddCode { codes } => session.add_codes(&codes).await,
+        Command::Library(LibraryCommand::List) => session.list_library(),
+        Command::Library(LibraryCommand::SetTemplate { mbid, template }) => {
+            session.set_template(&mbid, template)
+        }
+        Command::Library(LibraryCommand::SetGallery { mbid, off }) => {
+            session.set_gallery(&mbid, !off).await
+        }
+        Command::Notes(NotesCommand::Generate {
+            sync,
+            template,
+            dry_run,
+        }) => {
+            let mode = if sync {
+                NoteMode::Sync
+            } else {
+                NoteMode::Create
+            };
+            session.generate_notes(mode, template, dry_run).await
+        }
+        Command::Notes(NotesCommand::Templates) => session.list_templates(),
+        Command::Covers(CoversCommand::Fetch) => session.fetch_covers().await,
+    }
+}
+
+struct Session {
+    controller: AppController,
+    msg_rx: UnboundedReceiver<AppMessage>,
+    json: bool,
+    cover_art_timeout: Duration,
+}
+
+impl Session {
+    async fn search_artist(&mut self, query: &str) -> Result<()> {
+        let artists = self
+            .controller
+            .find_artists(query)
+            .await
+            .map_err(|err| anyhow!(err.user_message()))?;
+
+        self.output(&artists, |out| {
+            for artist in &artists {
+                writeln!(
+                    out,
+                    "{}  {} (score {})",
+                    artist.id,
+                    artist.display_name(),
+                    artist.score
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn search_album(&mut self, query: ReleaseQuery) -> Result<()> {
+        if query.is_empty() {
+            bail!("Give a title, --artist or --year to search for");
+        }
+        let albums = self
+            .controller
+            .find_release_groups(&query)
+            .await
+            .map_err(|err| anyhow!(err.user_message()))?;
+
+        self.output(&albums, |out| {
+            for album in &albums {
+                writeln!(
+                    out,
+                    "{}  {:<10}  {} — {} [{}]",
+                    album.id,
+                    album.first_release_date,
+                    album.artist,
+                    album.title,
+                    album.primary_type
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn search_release(&mut self, query: ReleaseQuery) -> Result<()> {
+        if query.is_empty() {
+            bail!("Give a title, --artist, --year, --barcode or --catno to search for");
+        }
+        let releases = self
+            .controller
+            .find_releases_matching(&query)
+            .await
+            .map_err(|err| anyhow!(err.user_message()))?;
+
+        self.output(&releases, |out| {
+            for release in &releases {
+                writeln!(
+                    out,
+                    "{}  {:<10}  {:<3}  {} — {}  {}  {}  {}",
+                    release.id,
+                    release.date,
+                    release.country,
+                    release.artist,
+                    release.title,
+                    release.format,
+                    release.label,
+                    release.catalog_number
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn list_albums(&mut self, artist_mbid: String, args: DiscographyArgs) -> Result<()> {
+        let artist = Artist {
+            id: artist_mbid,
+            ..Artist::default()
+        };
+        let mut filter = self.controller.default_discography().clone();
+        if let Some(types) = args.types {
+            filter.set_primary_types(types);
+        }
+        if let Some(hide) = args.hide {
+            filter.exclude_secondary_types = hide;
+        }
+        if let Some(only) = args.only {
+            filter.include_secondary_types = only;
+        }
+        filter.official_only |= args.official;
+        if let Some(years) = &args.years {
+            filter.set_years(years)?;
+        }
+
+        let albums = self
+            .controller
+            .find_albums(&artist, &filter)
+            .await
+            .map_err(|err| anyhow!(err.user_message()))?;
+
+        self.output(&albums, |out| {
+            for album in &albums {
+                writeln!(
+                    out,
+                    "{}  {:<10}  {} [{}]",
+                    album.id, album.first_release_date, album.title, album.primary_type
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn list_releases(&mut self, release_group_mbid: &str) -> Result<()> {
+        let releases = self
+            .controller
+            .find_releases(release_group_mbid)
+            .await
+            .map_err(|err| anyhow!(err.user_message()))?;
+
+        self.output(&releases, |out| {
+            for release in &releases {
+                writeln!(
+                    out,
+                    "{}  {:<10}  {:<3}  {}  {} tracks  {}  art: {}",
+                    release.id,
+                    release.date,
+                    release.country,
+                    release.format,
+                    release.track_count,
+                    release.label,
+                    if release.has_front_cover { "yes" } else { "no" }
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn add(&mut self, ids: &[String]) -> Result<()> {
+        self.add_ids(ids, 0, ids.len()).await
+    }
+
+    /// Adds the release with each barcode or catalog number. Codes matching
+    /// several releases are not added; their matches are listed instead.
+    async fn add_codes(&mut self, codes: &[String]) -> Result<()> {
+        let mut ids = Vec::new();
+        let mut failures = 0;
+
+        for code in codes {
+            match self.controller.find_releases_by_code(code).await {
+                Ok(releases) if releases.len() == 1 => ids.push(releases[0].id.clone()),
+                Ok(releases) => {
+                    failures += 1;
+                    eprintln!(
+                        "{} releases match '{code}'; add one with `add <release-id>`:",
+                        releases.len()
+                    );
+                    for release in &releases {
+                        eprintln!(
+                            "  {}  {:<10}  {:<3}  {} — {}  {}  {}",
+                            release.id,
+                            release.date,
+                            release.country,
+                            release.artist,
+                            release.title,
+                            release.format,
+                            release.catalog_number
+                        );
+                    }
+                }
+                Err(MusicBrainzError::Empty) => {
+                    failures += 1;
+                    eprintln!("No release found with barcode or catalog number '{code}'");
+                }
+                Err(err) => {
+                    failures += 1;
+                    eprintln!("Lookup of '{code}' failed: {}", err.user_message());
+                }
+            }
+        }
+
+        self.add_ids(&ids, failures, codes.len()).await
+    }
+
+    /// Adds `ids`, waits for their cover art and prints them. `failures` of
+    /// the `requested` albums have already failed.
+    async fn add_ids(
+        &mut self,
+        ids: &[String],
+        mut failures: usize,
+        requested: usize,
+    ) -> Result<()> {
+        let mut added = Vec::new();
+
+        for id in ids {
+            match self.controller.add_by_id(id).await {
+                Ok(record) => added.push(record),
+                Err(err) => {
+                    failures += 1;
+                    eprintln!("{err}");
+                }
+            }
+            self.print_logs();
+        }
+
+        let pending = added
+            .iter()
+            .filter(|record| record.cover_art_status == CoverArtStatus::Queued)
+            .map(|record| record.mbid.clone())
+            .collect();
+        self.wait_for_cover_art(pending).await;
+
+        let mbids: HashSet<_> = added.iter().map(|record| record.mbid.as_str()).collect();
+        let records = self.library_records(|record| mbids.contains(record.mbid.as_str()))?;
+        self.output_records(&records)?;
+
+        if failures > 0 {
+            bail!("{failures} of {requested} albums could not be added");
+        }
+        Ok(())
+    }
+
+    fn list_library(&mut self) -> Result<()> {
+        let records = self.controller.load_library()?;
+        self.output_records(&records)
+    }
+
+    fn set_template(&mut self, mbid: &str, template: Option<String>) -> Result<()> {
+        let record = self.controller.set_template(mbid, template)?;
+        self.output_records(&[record])
+    }
+
+    async fn set_gallery(&mut self, mbid: &str, enabled: bool) -> Result<()> {
+        let record = self.controller.set_gallery(mbid, enabled)?;
+        if enabled {
+            self.wait_for_cover_art(HashSet::from([record.mbid.clone()]))
+                .await;
+        }
+
+        let records = self.library_records(|candidate| candidate.mbid == record.mbid)?;
+        self.output_records(&records)
+    }
+
+    async fn generate_notes(
+        &mut self,
+        mode: NoteMode,
+        template: Option<String>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let records = self.controller.note_candidates(mode)?;
+        let plans = self.controller.plan_notes(records, mode, template).await?;
+        let failures = plans
+            .iter()
+            .filter(|plan| plan.action == NoteAction::Failed)
+            .count();
+
+        if dry_run {
+            self.output(&plans, |out| {
+                for plan in &plans {
+                    writeln!(out, "{}", plan.summary())?;
+                    if matches!(plan.action, NoteAction::Create | NoteAction::Update) {
+                        write!(out, "{}", plan.diff)?;
+                    }
+                }
+                Ok(())
+            })?;
+        } else {
+            let logs = self.controller.write_notes(plans).await?;
+            self.output(&logs, |out| {
+                for line in &logs {
+                    writeln!(out, "{line}")?;
+                }
+                Ok(())
+            })?;
+        }
+
+        if failures > 0 {
+            bail!("{failures} notes could not be rendered");
+        }
+        Ok(())
+    }
+
+    fn list_templates(&mut self) -> Result<()> {
+        let templates = self.controller.available_templates()?;
+
+        self.output(&templates, |out| {
+            for name in &templates {
+                writeln!(out, "{name}")?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn fetch_covers(&mut self) -> Result<()> {
+        let records = self.library_records(|record| {
+            record.cover_art_status != CoverArtStatus::Completed && !record.cover_art_url.is_empty()
+        })?;
+
+        let pending = records.iter().map(|record| record.mbid.clone()).collect();
+        self.controller.queue_cover_art(records.clone())?;
+        self.wait_for_cover_art(pending).await;
+
+        let mbids: HashSet<_> = records.iter().map(|record| record.mbid.as_str()).collect();
+        let records = self.library_records(|record| mbids.contains(record.mbid.as_str()))?;
+        self.output_records(&records)
+    }
+
+    /// Blocks until every album in `pending` has finished downloading or been
+    /// marked unavailable, echoing downloader logs along the way.
+    async fn wait_for_cover_art(&mut self, mut pending: HashSet<String>) {
+        while !pending.is_empty() {
+            let Ok(message) = timeout(self.cover_art_timeout, self.msg_rx.recv()).await else {
+                eprintln!(
+                    "Gave up waiting for cover art of {} albums; run `covers fetch` to retry",
+                    pending.len()
+                );
+                break;
+            };
+            match message {
+                Some(AppMessage::CoverArtStatus {
+                    mbid,
+                    status: CoverArtStatus::Completed | CoverArtStatus::Unavailable,
+                    ..
+                }) => {
+                    pending.remove(&mbid);
+                }
+                Some(AppMessage::DownloadLog(entry)) => eprintln!("{entry}"),
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+
+    fn print_logs(&mut self) {
+        while let Ok(message) = self.msg_rx.try_recv() {
+            if let AppMessage::DownloadLog(entry) = message {
+                eprintln!("{entry}");
+            }
+        }
+    }
+
+    fn library_records(&self, filter: impl Fn(&AlbumRecord) -> bool) -> Result<Vec<AlbumRecord>> {
+        Ok(self
+            .controller
+            .load_library()?
+            .into_iter()
+            .filter(|record| filter(record))
+            .collect())
+    }
+
+    fn output_records(&self, records: &[AlbumRecord]) -> Result<()> {
+        self.output(&records, |out| {
+            for record in records {
+                writeln!(
+                    out,
+                    "{}  {} - {}  art: {:?}{}  template: {}  note: {}",
+                    record.mbid,
+                    record.artist,
+                    record.title,
+                    record.cover_art_status,
+                    if record.gallery {
+                        format!("  gallery: {}", record.gallery_images.len())
+                    } else {
+                        String::new()
+                    },
+                    self.controller.template_for(record),
+                    record.note_path.as_deref().unwrap_or("-")
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Writes `value` as JSON in `--json` mode, otherwise runs `text`.
+    fn output<T: Serialize + ?Sized>(
+        &self,
+        value: &T,
+        text: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        if self.json {
+            serde_json::to_writer_pretty(&mut out, value).context("Failed to write JSON output")?;
+            writeln!(out)?;
+        } else {
+            text(&mut out)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::api::fixtures::StubProvider;
+    use crate::models::{Album, Release};
+    use crate::tasks::cover_art::CoverArtDownloaderHandle;
+
+    const RELEASE_GROUP: &str = "76df3287-6cda-33eb-8e9a-044b5e15ffdd";
+    const RELEASE: &str = "3a3aca1e-ae9b-4b35-9b7b-c2d6c5f2b4f3";
+
+    fn provider() -> StubProvider {
+        StubProvider {
+            albums: vec![Album {
+                id: RELEASE_GROUP.to_string(),
+                title: String::from("Dummy"),
+                artist: String::from("Portishead"),
+                ..Album::default()
+            }],
+            releases: vec![Release {
+                id: RELEASE.to_string(),
+                release_group_id: RELEASE_GROUP.to_string(),
+                title: String::from("Dummy"),
+                barcode: String::from("042282855329"),
+                catalog_number: String::from("828 522-1"),
+                ..Release::default()
+            }],
+            ..StubProvider::default()
+        }
+    }
+
+    fn session(
+        dir: &Path,
+        provider: StubProvider,
+        downloader: CoverArtDownloaderHandle,
+    ) -> Session {
+        let (controller, msg_rx) = AppController::in_dir(dir, Arc::new(provider), downloader);
+        Session {
+            controller,
+            msg_rx,
+            json: true,
+            cover_art_timeout: Duration::from_millis(50),
+        }
+    }
+
+    fn status(session: &Session, mbid: &str) -> CoverArtStatus {
+        let records = session
+            .library_records(|record| record.mbid == mbid)
+            .unwrap();
+        records[0].cover_art_status
+    }
+
+    #[tokio::test]
+    async fn add_fails_when_cover_art_cannot_be_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = CoverArtDownloaderHandle::stopped("https://coverartarchive.org");
+        let mut session = session(dir.path(), provider(), downloader);
+
+        let added = timeout(Duration::from_secs(5), session.add(&[RELEASE.to_string()]))
+            .await
+            .expect("add should not wait for a job that was never queued");
+
+        assert!(added.is_err());
+        assert_eq!(status(&session, RELEASE_GROUP), CoverArtStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn add_stops_waiting_when_the_downloader_goes_quiet() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = CoverArtDownloaderHandle::idle("https://coverartarchive.org");
+        let mut session = session(dir.path(), provider(), downloader);
+
+        timeout(Duration::from_secs(5), session.add(&[RELEASE.to_string()]))
+            .await
+            .expect("add should give up on cover art after the idle timeout")
+            .unwrap();
+
+        assert_eq!(status(&session, RELEASE_GROUP), CoverArtStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn add_codes_fails_when_cover_art_cannot_be_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = CoverArtDownloaderHandle::stopped("https://coverartarchive.org");
+        let mut session = session(dir.path(), provider(), downloader);
+
+        let added = timeout(
+            Duration::from_secs(5),
+            session.add_codes(&[String::from("0 42282 85532 9")]),
+        )
+        .await
+        .expect("add-code should not wait for a job that was never queued");
+
+        assert!(added.is_err());
+        assert_eq!(status(&session, RELEASE_GROUP), CoverArtStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn add_codes_adds_by_catalog_number() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloader = CoverArtDownloaderHandle::idle("https://coverartarchive.org");
+        let mut session = session(dir.path(), provider(), downloader);
+
+        timeout(
+            Duration::from_secs(5),
+            session.add_codes(&[String::from("828 522-1")]),
+        )
+        .await
+        .expect("add-code should give up on cover art after the idle timeout")
+        .unwrap();
+
+        let records = session.library_records(|_| true).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].barcode, "042282855329");
+    }
+
+    #[tokio::test]
+    async fn add_codes_skips_codes_matching_several_releases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut provider = provider();
+        let mut reissue = provider.releases[0].clone();
+        reissue.id = String::from("0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b");
+        provider.releases.push(reissue);
+        let downloader = CoverArtDownloaderHandle::idle("https://coverartarchive.org");
+        let mut session = session(dir.path(), provider, downloader);
+
+        let added = timeout(
+            Duration::from_secs(5),
+            session.add_codes(&[String::from("042282855329")]),
+        )
+        .await
+        .unwrap();
+
+        assert!(added.is_err());
+        assert!(session.library_records(|_| true).unwrap().is_empty());
+    }
+}
@@ -1,9 +1,55 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::Url;
+use serde::Deserialize;
 use uuid::Uuid;
 
+use crate::models::discography::PRIMARY_TYPES;
+use crate::models::{AlbumRecord, ArtistRecord, DiscographyFilter};
+use crate::notes::NotePathPattern;
+use crate::vault::{LinkStyle, Vault};
+
+/// Directory name used under the XDG config and data directories.
+pub const APP_NAME: &str = "obsidian-album-propogator";
+
+/// Prefix for environment variable overrides, e.g. `ALBUM_PROPOGATOR_NOTES_DIR`.
+const ENV_PREFIX: &str = "ALBUM_PROPOGATOR";
+
+/// Notes land flat in `notes_dir` unless `note_path_pattern` is set.
+const DEFAULT_NOTE_PATH_PATTERN: &str = "{artist} - {title}.md";
+
+/// The public MusicBrainz web service.
+const DEFAULT_MUSICBRAINZ_URL: &str = "https://musicbrainz.org/ws/2";
+
+/// MusicBrainz allows one request per second per client; a little slack keeps
+/// bursts from tripping the limit.
+const DEFAULT_MUSICBRAINZ_THROTTLE_MS: u64 = 1100;
+
+/// Default lifetimes of cached MusicBrainz responses, by kind of request.
+/// Searches go stale fastest; fetched entities rarely change.
+const DEFAULT_SEARCH_TTL_HOURS: u64 = 24;
+const DEFAULT_BROWSE_TTL_HOURS: u64 = 24 * 7;
+const DEFAULT_LOOKUP_TTL_HOURS: u64 = 24 * 30;
+
+/// The public Cover Art Archive.
+const DEFAULT_COVER_ART_URL: &str = "https://coverartarchive.org";
+
+/// Artist notes sit next to album notes unless `artist_note_path_pattern` is set.
+const DEFAULT_ARTIST_NOTE_PATH_PATTERN: &str = "{artist}.md";
+
+/// Written to `<templates_dir>/<default_template>.md` on first run.
+const DEFAULT_TEMPLATE: &str = include_str!("templates/note_template.md");
+
+/// Written to `<templates_dir>/<artist_template>.md` on first run.
+const DEFAULT_ARTIST_TEMPLATE: &str = include_str!("templates/artist_template.md");
+
 /// Static configuration and filesystem paths used throughout the application.
 #[derive(Clone, Debug)]
 pub struct AppConfig {
@@ -11,36 +57,725 @@ pub struct AppConfig {
     album_art_dir: PathBuf,
     notes_dir: PathBuf,
     db_path: PathBuf,
-    template_path: PathBuf,
+    templates_dir: PathBuf,
+    default_template: String,
+    template_rules: Vec<TemplateRule>,
+    note_path_pattern: String,
+    artist_template: String,
+    artist_note_path_pattern: String,
+    cover_art_size: CoverArtSize,
+    cover_art_types: Vec<String>,
+    square_cover: Option<SquareCover>,
+    cover_art_url: String,
+    musicbrainz_url: String,
+    musicbrainz_throttle: Duration,
+    response_cache: Option<ResponseCacheSettings>,
+    offline: bool,
+    discography: DiscographyFilter,
+    vault: Option<Vault>,
     user_agent: String,
     client_id: String,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        let base = PathBuf::from("data");
-        let album_art = base.join("album_art");
-        let notes = base.join("notes");
-        let db_path = base.join("library.db");
-        let templates = PathBuf::from("templates");
+/// One layer of user-supplied settings. Layers are read from the config file,
+/// the environment and the command line, then merged in that order so later
+/// layers win. Unset fields fall back to defaults derived from `data_dir`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub data_dir: Option<PathBuf>,
+    pub album_art_dir: Option<PathBuf>,
+    pub notes_dir: Option<PathBuf>,
+    pub db_path: Option<PathBuf>,
+    pub templates_dir: Option<PathBuf>,
+    pub default_template: Option<String>,
+    pub template_rules: Option<Vec<TemplateRule>>,
+    /// Note location relative to `notes_dir`, e.g. `{artist}/{year} - {title}.md`.
+    pub note_path_pattern: Option<String>,
+    /// Template for artist notes, by name.
+    pub artist_template: Option<String>,
+    /// Artist note location relative to `notes_dir`, e.g. `Artists/{artist}.md`.
+    pub artist_note_path_pattern: Option<String>,
+    pub cover_art: CoverArtLayer,
+    pub musicbrainz: MusicBrainzLayer,
+    pub discography: DiscographyLayer,
+    pub vault: VaultLayer,
+}
+
+/// Picks a named template for albums of a given release type. Rules are
+/// checked in order and the first whose conditions all match wins.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateRule {
+    /// Matches the release group's primary type, e.g. `EP` (case-insensitive).
+    pub primary_type: Option<String>,
+    /// Matches when any secondary type equals this, e.g. `Live` (case-insensitive).
+    pub secondary_type: Option<String>,
+    /// Template name: a file stem in `templates_dir`.
+    pub template: String,
+}
+
+impl TemplateRule {
+    pub fn matches(&self, primary_type: &str, secondary_types: &[String]) -> bool {
+        let primary_ok = self
+            .primary_type
+            .as_ref()
+            .is_none_or(|wanted| wanted.eq_ignore_ascii_case(primary_type));
+        let secondary_ok = self.secondary_type.as_ref().is_none_or(|wanted| {
+            secondary_types
+                .iter()
+                .any(|secondary| wanted.eq_ignore_ascii_case(secondary))
+        });
+        primary_ok && secondary_ok
+    }
+}
+
+/// Which cover image is downloaded for each album.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoverArtLayer {
+    /// Thumbnail size: `250`, `500`, `1200` or `original`.
+    pub size: Option<CoverArtSize>,
+    /// Cover Art Archive image types in order of preference, e.g. `["front", "back"]`.
+    pub image_types: Option<Vec<String>>,
+    /// Cover Art Archive to download from, e.g. a local mirror.
+    pub base_url: Option<String>,
+    pub square: SquareCoverLayer,
+}
+
+/// Post-processing of the downloaded cover into a square image, so Bases
+/// card views line up. Off unless `enabled` is set.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SquareCoverLayer {
+    pub enabled: Option<bool>,
+    /// Edge length in pixels.
+    pub size: Option<u32>,
+    /// `crop` to fill the square, or `pad` to fit the whole image inside it.
+    pub fit: Option<SquareFit>,
+    /// `jpeg` or `webp`.
+    pub format: Option<SquareFormat>,
+    /// JPEG quality from 1 to 100. WebP output is always lossless.
+    pub quality: Option<u8>,
+    /// Colour of the padding, as `#rrggbb`.
+    pub background: Option<Rgb>,
+    /// Also keep the image as downloaded, next to the processed one.
+    pub keep_original: Option<bool>,
+}
+
+/// Resolved square cover settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SquareCover {
+    pub size: u32,
+    pub fit: SquareFit,
+    pub format: SquareFormat,
+    pub quality: u8,
+    pub background: Rgb,
+    pub keep_original: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SquareFit {
+    /// Scale to cover the square and cut off what overhangs.
+    #[default]
+    Crop,
+    /// Scale to fit inside the square and fill the rest with the background.
+    Pad,
+}
+
+impl FromStr for SquareFit {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "crop" => Ok(SquareFit::Crop),
+            "pad" => Ok(SquareFit::Pad),
+            other => Err(anyhow!("unknown fit `{other}` (expected crop or pad)")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SquareFormat {
+    #[default]
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Webp,
+}
+
+impl SquareFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SquareFormat::Jpeg => "jpg",
+            SquareFormat::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            SquareFormat::Jpeg => "image/jpeg",
+            SquareFormat::Webp => "image/webp",
+        }
+    }
+}
+
+impl FromStr for SquareFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Ok(SquareFormat::Jpeg),
+            "webp" => Ok(SquareFormat::Webp),
+            other => Err(anyhow!("unknown format `{other}` (expected jpeg or webp)")),
+        }
+    }
+}
+
+/// An RGB colour, written as `#rrggbb`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub [u8; 3]);
+
+impl TryFrom<String> for Rgb {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let hex = value.trim().trim_start_matches('#');
+        let channel = |range: std::ops::Range<usize>| {
+            hex.get(range)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        };
+        match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Rgb([r, g, b])),
+            _ => Err(anyhow!("invalid colour `{value}` (expected #rrggbb)")),
+        }
+    }
+}
+
+/// A Cover Art Archive thumbnail size, smallest first.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "SizeSetting")]
+pub enum CoverArtSize {
+    Px250,
+    Px500,
+    #[default]
+    Px1200,
+    /// The full-size upload, which can be several megabytes.
+    Original,
+}
+
+/// `size` may be written as a number (`500`) or a string (`"original"`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeSetting {
+    Number(u32),
+    Text(String),
+}
+
+impl TryFrom<SizeSetting> for CoverArtSize {
+    type Error = anyhow::Error;
+
+    fn try_from(setting: SizeSetting) -> Result<Self> {
+        match setting {
+            SizeSetting::Number(number) => number.to_string().parse(),
+            SizeSetting::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl FromStr for CoverArtSize {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "250" => Ok(CoverArtSize::Px250),
+            "500" => Ok(CoverArtSize::Px500),
+            "1200" => Ok(CoverArtSize::Px1200),
+            "original" => Ok(CoverArtSize::Original),
+            other => Err(anyhow!(
+                "unknown cover art size `{other}` (expected 250, 500, 1200 or original)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CoverArtSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CoverArtSize::Px250 => "250",
+            CoverArtSize::Px500 => "500",
+            CoverArtSize::Px1200 => "1200",
+            CoverArtSize::Original => "original",
+        })
+    }
+}
+
+impl SquareCoverLayer {
+    fn resolve(&self) -> Option<SquareCover> {
+        self.enabled.unwrap_or(false).then(|| SquareCover {
+            size: self.size.unwrap_or(600),
+            fit: self.fit.unwrap_or_default(),
+            format: self.format.unwrap_or_default(),
+            quality: self.quality.unwrap_or(85),
+            background: self.background.unwrap_or_default(),
+            keep_original: self.keep_original.unwrap_or(false),
+        })
+    }
+}
+
+/// Where MusicBrainz requests go and how fast they may be sent.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MusicBrainzLayer {
+    /// Web service root including the `/ws/2` path, e.g. `http://localhost:5000/ws/2`.
+    pub base_url: Option<String>,
+    /// Minimum gap between requests in milliseconds; `0` turns throttling off.
+    /// Defaults to 1100 for musicbrainz.org and 0 for any other server.
+    pub throttle_ms: Option<u64>,
+    /// Answer lookups from the response cache only, never from the network.
+    pub offline: Option<bool>,
+    pub cache: ResponseCacheLayer,
+}
+
+/// On-disk cache of MusicBrainz responses. On unless `enabled` is `false`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseCacheLayer {
+    pub enabled: Option<bool>,
+    /// Cache database location. Defaults to `http_cache.db` next to `db_path`.
+    pub path: Option<PathBuf>,
+    /// How long search results (`artist?query=...`) are used without asking again.
+    pub search_ttl_hours: Option<u64>,
+    /// How long browse pages (an artist's release groups, a group's releases) are used.
+    pub browse_ttl_hours: Option<u64>,
+    /// How long single release and release-group lookups are used.
+    pub lookup_ttl_hours: Option<u64>,
+}
+
+/// Resolved response cache settings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseCacheSettings {
+    pub path: PathBuf,
+    pub search_ttl: Duration,
+    pub browse_ttl: Duration,
+    pub lookup_ttl: Duration,
+}
+
+impl ResponseCacheLayer {
+    fn resolve(&self, db_path: &Path) -> Option<ResponseCacheSettings> {
+        let hours =
+            |value: Option<u64>, default| Duration::from_secs(value.unwrap_or(default) * 3600);
+        self.enabled.unwrap_or(true).then(|| ResponseCacheSettings {
+            path: self.path.clone().unwrap_or_else(|| {
+                db_path
+                    .parent()
+                    .unwrap_or(Path::new("."))
+                    .join("http_cache.db")
+            }),
+            search_ttl: hours(self.search_ttl_hours, DEFAULT_SEARCH_TTL_HOURS),
+            browse_ttl: hours(self.browse_ttl_hours, DEFAULT_BROWSE_TTL_HOURS),
+            lookup_ttl: hours(self.lookup_ttl_hours, DEFAULT_LOOKUP_TTL_HOURS),
+        })
+    }
+}
+
+/// Which of an artist's release groups are listed by default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscographyLayer {
+    /// Any of `album`, `ep`, `single`, `broadcast` and `other`. Defaults to album and EP.
+    pub primary_types: Option<Vec<String>>,
+    /// Secondary types to hide, e.g. `["Live", "Compilation"]`.
+    pub exclude_secondary_types: Option<Vec<String>>,
+    /// Secondary types to list exclusively, e.g. `["Soundtrack"]`.
+    pub include_secondary_types: Option<Vec<String>>,
+    /// Hide release groups that only have bootleg, promotion or pseudo-releases.
+    pub official_only: Option<bool>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+}
+
+impl DiscographyLayer {
+    fn resolve(&self) -> DiscographyFilter {
+        let mut filter = DiscographyFilter {
+            exclude_secondary_types: self.exclude_secondary_types.clone().unwrap_or_default(),
+            include_secondary_types: self.include_secondary_types.clone().unwrap_or_default(),
+            official_only: self.official_only.unwrap_or(false),
+            year_from: self.year_from,
+            year_to: self.year_to,
+            ..DiscographyFilter::default()
+        };
+        if let Some(types) = &self.primary_types {
+            filter.set_primary_types(types.clone());
+        }
+        filter
+    }
+}
+
+/// Vault mode settings. When `root` is set, notes and cover art are written
+/// into the vault and links follow its `.obsidian/app.json` preferences.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VaultLayer {
+    pub root: Option<PathBuf>,
+    /// Folder for album notes, relative to the vault root.
+    pub notes_folder: Option<PathBuf>,
+    /// Folder for cover art, relative to the vault root. Defaults to the
+    /// vault's configured attachment folder.
+    pub attachments_folder: Option<PathBuf>,
+}
+
+impl ConfigLayer {
+    /// Reads overrides from `ALBUM_PROPOGATOR_*` environment variables.
+    fn from_env() -> Result<Self> {
+        Self::from_vars(|key| env::var_os(key))
+    }
+
+    /// Reads overrides from `ALBUM_PROPOGATOR_*` variables looked up by `lookup`.
+    fn from_vars(lookup: impl Fn(&str) -> Option<OsString>) -> Result<Self> {
+        let var = |name: &str| {
+            lookup(&format!("{ENV_PREFIX}_{name}"))
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+
+        let text = |name: &str| var(name).map(|value| value.to_string_lossy().into_owned());
+        let list = |name: &str| {
+            text(name).map(|values| {
+                values
+                    .split(',')
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+                    .collect()
+            })
+        };
+        Ok(Self {
+            data_dir: var("DATA_DIR"),
+            album_art_dir: var("ALBUM_ART_DIR"),
+            notes_dir: var("NOTES_DIR"),
+            db_path: var("DB_PATH"),
+            templates_dir: var("TEMPLATES_DIR"),
+            default_template: text("DEFAULT_TEMPLATE"),
+            template_rules: None,
+            note_path_pattern: text("NOTE_PATH_PATTERN"),
+            artist_template: text("ARTIST_TEMPLATE"),
+            artist_note_path_pattern: text("ARTIST_NOTE_PATH_PATTERN"),
+            cover_art: CoverArtLayer {
+                size: parse_var(&lookup, "COVER_ART_SIZE")?,
+                image_types: list("COVER_ART_IMAGE_TYPES"),
+                base_url: text("COVER_ART_URL"),
+                square: SquareCoverLayer {
+                    enabled: parse_var(&lookup, "COVER_ART_SQUARE")?,
+                    size: parse_var(&lookup, "COVER_ART_SQUARE_SIZE")?,
+                    fit: parse_var(&lookup, "COVER_ART_SQUARE_FIT")?,
+                    format: parse_var(&lookup, "COVER_ART_SQUARE_FORMAT")?,
+                    quality: parse_var(&lookup, "COVER_ART_SQUARE_QUALITY")?,
+                    background: parse_var(&lookup, "COVER_ART_SQUARE_BACKGROUND")?,
+                    keep_original: parse_var(&lookup, "COVER_ART_SQUARE_KEEP_ORIGINAL")?,
+                },
+            },
+            musicbrainz: MusicBrainzLayer {
+                base_url: text("MUSICBRAINZ_URL"),
+                throttle_ms: parse_var(&lookup, "MUSICBRAINZ_THROTTLE_MS")?,
+                offline: parse_var(&lookup, "OFFLINE")?,
+                cache: ResponseCacheLayer {
+                    enabled: parse_var(&lookup, "MUSICBRAINZ_CACHE")?,
+                    path: var("MUSICBRAINZ_CACHE_PATH"),
+                    search_ttl_hours: parse_var(&lookup, "MUSICBRAINZ_CACHE_SEARCH_TTL_HOURS")?,
+                    browse_ttl_hours: parse_var(&lookup, "MUSICBRAINZ_CACHE_BROWSE_TTL_HOURS")?,
+                    lookup_ttl_hours: parse_var(&lookup, "MUSICBRAINZ_CACHE_LOOKUP_TTL_HOURS")?,
+                },
+            },
+            discography: DiscographyLayer {
+                primary_types: list("DISCOGRAPHY_PRIMARY_TYPES"),
+                exclude_secondary_types: list("DISCOGRAPHY_EXCLUDE_SECONDARY_TYPES"),
+                include_secondary_types: list("DISCOGRAPHY_INCLUDE_SECONDARY_TYPES"),
+                official_only: parse_var(&lookup, "DISCOGRAPHY_OFFICIAL_ONLY")?,
+                year_from: parse_var(&lookup, "DISCOGRAPHY_YEAR_FROM")?,
+                year_to: parse_var(&lookup, "DISCOGRAPHY_YEAR_TO")?,
+            },
+            vault: VaultLayer {
+                root: var("VAULT_ROOT"),
+                notes_folder: var("VAULT_NOTES_FOLDER"),
+                attachments_folder: var("VAULT_ATTACHMENTS_FOLDER"),
+            },
+        })
+    }
+
+    /// Overlays every field set in `other` on top of `self`.
+    fn merge(&mut self, other: ConfigLayer) {
+        let ConfigLayer {
+            data_dir,
+            album_art_dir,
+            notes_dir,
+            db_path,
+            templates_dir,
+            default_template,
+            template_rules,
+            note_path_pattern,
+            artist_template,
+            artist_note_path_pattern,
+            cover_art,
+            musicbrainz,
+            discography,
+            vault,
+        } = other;
+
+        self.data_dir = data_dir.or(self.data_dir.take());
+        self.album_art_dir = album_art_dir.or(self.album_art_dir.take());
+        self.notes_dir = notes_dir.or(self.notes_dir.take());
+        self.db_path = db_path.or(self.db_path.take());
+        self.templates_dir = templates_dir.or(self.templates_dir.take());
+        self.default_template = default_template.or(self.default_template.take());
+        self.template_rules = template_rules.or(self.template_rules.take());
+        self.note_path_pattern = note_path_pattern.or(self.note_path_pattern.take());
+        self.artist_template = artist_template.or(self.artist_template.take());
+        self.artist_note_path_pattern =
+            artist_note_path_pattern.or(self.artist_note_path_pattern.take());
+        self.cover_art.size = cover_art.size.or(self.cover_art.size.take());
+        self.cover_art.image_types = cover_art.image_types.or(self.cover_art.image_types.take());
+        self.cover_art.base_url = cover_art.base_url.or(self.cover_art.base_url.take());
+        self.musicbrainz.base_url = musicbrainz.base_url.or(self.musicbrainz.base_url.take());
+        self.musicbrainz.throttle_ms = musicbrainz
+            .throttle_ms
+            .or(self.musicbrainz.throttle_ms.take());
+        self.musicbrainz.offline = musicbrainz.offline.or(self.musicbrainz.offline.take());
+        let cache = &mut self.musicbrainz.cache;
+        cache.enabled = musicbrainz.cache.enabled.or(cache.enabled.take());
+        cache.path = musicbrainz.cache.path.or(cache.path.take());
+        cache.search_ttl_hours = musicbrainz
+            .cache
+            .search_ttl_hours
+            .or(cache.search_ttl_hours.take());
+        cache.browse_ttl_hours = musicbrainz
+            .cache
+            .browse_ttl_hours
+            .or(cache.browse_ttl_hours.take());
+        cache.lookup_ttl_hours = musicbrainz
+            .cache
+            .lookup_ttl_hours
+            .or(cache.lookup_ttl_hours.take());
+        let square = &mut self.cover_art.square;
+        square.enabled = cover_art.square.enabled.or(square.enabled.take());
+        square.size = cover_art.square.size.or(square.size.take());
+        square.fit = cover_art.square.fit.or(square.fit.take());
+        square.format = cover_art.square.format.or(square.format.take());
+        square.quality = cover_art.square.quality.or(square.quality.take());
+        square.background = cover_art.square.background.or(square.background.take());
+        square.keep_original = cover_art
+            .square
+            .keep_original
+            .or(square.keep_original.take());
+        self.discography.primary_types = discography
+            .primary_types
+            .or(self.discography.primary_types.take());
+        self.discography.exclude_secondary_types = discography
+            .exclude_secondary_types
+            .or(self.discography.exclude_secondary_types.take());
+        self.discography.include_secondary_types = discography
+            .include_secondary_types
+            .or(self.discography.include_secondary_types.take());
+        self.discography.official_only = discography
+            .official_only
+            .or(self.discography.official_only.take());
+        self.discography.year_from = discography.year_from.or(self.discography.year_from.take());
+        self.discography.year_to = discography.year_to.or(self.discography.year_to.take());
+        self.vault.root = vault.root.or(self.vault.root.take());
+        self.vault.notes_folder = vault.notes_folder.or(self.vault.notes_folder.take());
+        self.vault.attachments_folder = vault
+            .attachments_folder
+            .or(self.vault.attachments_folder.take());
+    }
+
+    /// Expands `~` and anchors relative paths at `base`.
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [
+            &mut self.data_dir,
+            &mut self.album_art_dir,
+            &mut self.notes_dir,
+            &mut self.db_path,
+            &mut self.templates_dir,
+            &mut self.musicbrainz.cache.path,
+            &mut self.vault.root,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = resolve_path(path, base);
+        }
+    }
+}
+
+impl AppConfig {
+    /// Builds the configuration from defaults, the config file, environment
+    /// variables and finally `overrides` (usually the command line).
+    ///
+    /// The config file is `config_path` when given, otherwise
+    /// `$ALBUM_PROPOGATOR_CONFIG`, otherwise `<XDG config dir>/obsidian-album-propogator/config.toml`.
+    /// An explicitly named file must exist; the default location is optional.
+    pub fn load(config_path: Option<&Path>, overrides: ConfigLayer) -> Result<Self> {
+        let cwd = env::current_dir().context("Unable to determine current directory")?;
+
+        let explicit = config_path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var_os(format!("{ENV_PREFIX}_CONFIG")).map(PathBuf::from))
+            .map(|path| resolve_path(&path, &cwd));
+
+        let (config_file, required) = match explicit {
+            Some(path) => (path, true),
+            None => (default_config_dir().join("config.toml"), false),
+        };
+
+        let mut layer = if config_file.exists() {
+            let mut file_layer = read_config_file(&config_file)?;
+            file_layer.resolve_paths(config_file.parent().unwrap_or(Path::new(".")));
+            file_layer
+        } else if required {
+            bail!("Config file not found: {}", config_file.display());
+        } else {
+            ConfigLayer::default()
+        };
+
+        let mut env_layer = ConfigLayer::from_env()?;
+        env_layer.resolve_paths(&cwd);
+        layer.merge(env_layer);
+
+        let mut cli_layer = overrides;
+        cli_layer.resolve_paths(&cwd);
+        layer.merge(cli_layer);
+
+        if layer.data_dir.is_none()
+            && layer.db_path.is_none()
+            && let Some(legacy) = legacy_data_dir(&cwd, &default_data_dir())
+        {
+            eprintln!(
+                "Using the library in {} from an earlier version. Move it to {} or set data_dir to silence this notice.",
+                legacy.display(),
+                default_data_dir().display()
+            );
+            layer.data_dir = Some(legacy);
+        }
+
+        let config_dir = config_file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(default_config_dir);
+
+        if layer.vault.root.is_some()
+            && (layer.notes_dir.is_some() || layer.album_art_dir.is_some())
+        {
+            bail!(
+                "notes_dir and album_art_dir cannot be combined with vault.root; \
+                 use vault.notes_folder and vault.attachments_folder instead"
+            );
+        }
+
+        let config = Self::from_layer(layer, &config_dir)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_layer(layer: ConfigLayer, config_dir: &Path) -> Result<Self> {
+        let data_dir = layer.data_dir.unwrap_or_else(default_data_dir);
+
+        let vault = layer
+            .vault
+            .root
+            .as_deref()
+            .map(|root| {
+                Vault::open(
+                    root,
+                    layer.vault.notes_folder.as_deref(),
+                    layer.vault.attachments_folder.as_deref(),
+                )
+            })
+            .transpose()?;
+
+        let (album_art_dir, notes_dir) = match &vault {
+            Some(vault) => (vault.attachments_dir(), vault.notes_dir().to_path_buf()),
+            None => (
+                layer
+                    .album_art_dir
+                    .unwrap_or_else(|| data_dir.join("album_art")),
+                layer.notes_dir.unwrap_or_else(|| data_dir.join("notes")),
+            ),
+        };
+        let db_path = layer.db_path.unwrap_or_else(|| data_dir.join("library.db"));
+        let templates_dir = layer
+            .templates_dir
+            .unwrap_or_else(|| config_dir.join("templates"));
+        let default_template = layer
+            .default_template
+            .unwrap_or_else(|| String::from("note_template"));
+
+        let musicbrainz_url = layer
+            .musicbrainz
+            .base_url
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| String::from(DEFAULT_MUSICBRAINZ_URL));
+        let musicbrainz_throttle = Duration::from_millis(layer.musicbrainz.throttle_ms.unwrap_or(
+            if musicbrainz_url == DEFAULT_MUSICBRAINZ_URL {
+                DEFAULT_MUSICBRAINZ_THROTTLE_MS
+            } else {
+                0
+            },
+        ));
+
+        let response_cache = layer.musicbrainz.cache.resolve(&db_path);
 
         let client_id = format!("rust-mb-client-{}", Uuid::new_v4());
         let user_agent =
             format!("rust-mb-library/0.1.0 ( https://musicbrainz.org ; unique-id={client_id} )");
 
-        Self {
-            data_dir: base,
-            album_art_dir: album_art,
-            notes_dir: notes,
+        Ok(Self {
+            data_dir,
+            album_art_dir,
+            notes_dir,
             db_path,
-            template_path: templates.join("note_template.md"),
+            templates_dir,
+            default_template,
+            template_rules: layer.template_rules.unwrap_or_default(),
+            note_path_pattern: layer
+                .note_path_pattern
+                .unwrap_or_else(|| String::from(DEFAULT_NOTE_PATH_PATTERN)),
+            artist_template: layer
+                .artist_template
+                .unwrap_or_else(|| String::from("artist_template")),
+            artist_note_path_pattern: layer
+                .artist_note_path_pattern
+                .unwrap_or_else(|| String::from(DEFAULT_ARTIST_NOTE_PATH_PATTERN)),
+            cover_art_size: layer.cover_art.size.unwrap_or_default(),
+            cover_art_types: layer
+                .cover_art
+                .image_types
+                .unwrap_or_else(|| vec![String::from("front")]),
+            square_cover: layer.cover_art.square.resolve(),
+            cover_art_url: layer
+                .cover_art
+                .base_url
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| String::from(DEFAULT_COVER_ART_URL)),
+            musicbrainz_url,
+            musicbrainz_throttle,
+            response_cache,
+            offline: layer.musicbrainz.offline.unwrap_or(false),
+            discography: layer.discography.resolve(),
+            vault,
             user_agent,
             client_id,
-        }
+        })
     }
-}
 
-impl AppConfig {
     pub fn data_dir(&self) -> &Path {
         &self.data_dir
     }
@@ -49,6 +784,26 @@ impl AppConfig {
         &self.album_art_dir
     }
 
+    /// Where cover art for `album` is saved. In a vault that keeps
+    /// attachments next to notes (`./`), this follows the album's note folder.
+    pub fn album_art_dir_for(&self, album: &AlbumRecord) -> PathBuf {
+        let Some(vault) = &self.vault else {
+            return self.album_art_dir.clone();
+        };
+        // The pattern was checked by `validate`; only the file name can
+        // change when notes share a path, never the folder.
+        let note_path = match &album.note_path {
+            Some(path) => Some(PathBuf::from(path)),
+            None => {
+                NotePathPattern::parse::<AlbumRecord>("note_path_pattern", &self.note_path_pattern)
+                    .ok()
+                    .map(|pattern| self.notes_dir.join(pattern.render(album, "")))
+            }
+        };
+        let note_dir = note_path.as_deref().and_then(Path::parent);
+        vault.attachments_dir_for(note_dir.unwrap_or(&self.notes_dir))
+    }
+
     pub fn notes_dir(&self) -> &Path {
         &self.notes_dir
     }
@@ -57,8 +812,82 @@ impl AppConfig {
         &self.db_path
     }
 
-    pub fn template_path(&self) -> &Path {
-        &self.template_path
+    pub fn templates_dir(&self) -> &Path {
+        &self.templates_dir
+    }
+
+    pub fn default_template(&self) -> &str {
+        &self.default_template
+    }
+
+    pub fn template_rules(&self) -> &[TemplateRule] {
+        &self.template_rules
+    }
+
+    pub fn note_path_pattern(&self) -> &str {
+        &self.note_path_pattern
+    }
+
+    pub fn artist_template(&self) -> &str {
+        &self.artist_template
+    }
+
+    pub fn artist_note_path_pattern(&self) -> &str {
+        &self.artist_note_path_pattern
+    }
+
+    pub fn cover_art_size(&self) -> CoverArtSize {
+        self.cover_art_size
+    }
+
+    /// Image types to download, in order of preference.
+    pub fn cover_art_types(&self) -> &[String] {
+        &self.cover_art_types
+    }
+
+    /// Settings for squaring downloaded covers, or `None` to keep them as downloaded.
+    pub fn square_cover(&self) -> Option<SquareCover> {
+        self.square_cover
+    }
+
+    /// Root of the Cover Art Archive, without a trailing slash.
+    pub fn cover_art_url(&self) -> &str {
+        &self.cover_art_url
+    }
+
+    /// Root of the MusicBrainz web service, without a trailing slash.
+    pub fn musicbrainz_url(&self) -> &str {
+        &self.musicbrainz_url
+    }
+
+    /// Minimum gap between MusicBrainz requests; zero when throttling is off.
+    pub fn musicbrainz_throttle(&self) -> Duration {
+        self.musicbrainz_throttle
+    }
+
+    /// Where and for how long MusicBrainz responses are cached, or `None`
+    /// when caching is off.
+    pub fn response_cache(&self) -> Option<&ResponseCacheSettings> {
+        self.response_cache.as_ref()
+    }
+
+    /// Whether MusicBrainz lookups are answered from the response cache only.
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// The discography filter the Albums pane and `list-albums` start with.
+    pub fn discography(&self) -> &DiscographyFilter {
+        &self.discography
+    }
+
+    /// How notes should link to cover art: the vault's preferences in vault
+    /// mode, otherwise markdown links relative to the note.
+    pub fn link_style(&self) -> LinkStyle {
+        match &self.vault {
+            Some(vault) => vault.link_style().clone(),
+            None => LinkStyle::relative_markdown(&self.notes_dir),
+        }
     }
 
     pub fn user_agent(&self) -> &str {
@@ -69,59 +898,440 @@ impl AppConfig {
         &self.client_id
     }
 
+    /// Checks that every configured path can be used for its purpose, reporting
+    /// all problems at once rather than failing on the first.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        for (name, path) in [
+            ("data_dir", self.data_dir()),
+            ("album_art_dir", self.album_art_dir()),
+            ("notes_dir", self.notes_dir()),
+        ] {
+            if path.as_os_str().is_empty() {
+                problems.push(format!("{name} must not be empty"));
+            } else if path.exists() && !path.is_dir() {
+                problems.push(format!(
+                    "{name} `{}` exists but is not a directory",
+                    path.display()
+                ));
+            } else if let Some(blocker) = file_ancestor(path) {
+                problems.push(format!(
+                    "{name} `{}` cannot be created because `{}` is a file",
+                    path.display(),
+                    blocker.display()
+                ));
+            }
+        }
+
+        if self.db_path().as_os_str().is_empty() {
+            problems.push("db_path must not be empty".to_string());
+        } else if self.db_path().is_file() {
+            problems.push(format!(
+                "db_path `{}` is a file; the library database is stored as a directory",
+                self.db_path().display()
+            ));
+        }
+
+        match self.response_cache() {
+            Some(cache) if cache.path.is_file() => problems.push(format!(
+                "musicbrainz.cache.path `{}` is a file; the response cache is stored as a directory",
+                cache.path.display()
+            )),
+            Some(_) => {}
+            None if self.offline() => problems.push(
+                "offline mode needs the response cache; remove musicbrainz.cache.enabled = false"
+                    .to_string(),
+            ),
+            None => {}
+        }
+
+        if self.templates_dir().as_os_str().is_empty() {
+            problems.push("templates_dir must not be empty".to_string());
+        } else if self.templates_dir().exists() && !self.templates_dir().is_dir() {
+            problems.push(format!(
+                "templates_dir `{}` exists but is not a directory",
+                self.templates_dir().display()
+            ));
+        }
+
+        for name in [self.default_template(), self.artist_template()]
+            .into_iter()
+            .chain(
+                self.template_rules()
+                    .iter()
+                    .map(|rule| rule.template.as_str()),
+            )
+        {
+            if name.is_empty() || name.contains(['/', '\\']) {
+                problems.push(format!(
+                    "template name `{name}` must be a file name without extension"
+                ));
+            }
+        }
+
+        if self.cover_art_types().is_empty() {
+            problems.push("cover_art.image_types must name at least one image type".to_string());
+        }
+        for (name, url) in [
+            ("musicbrainz.base_url", self.musicbrainz_url()),
+            ("cover_art.base_url", self.cover_art_url()),
+        ] {
+            match Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                Ok(_) => problems.push(format!("{name} `{url}` must be an http or https URL")),
+                Err(err) => problems.push(format!("{name} `{url}` is not a valid URL: {err}")),
+            }
+        }
+
+        let discography = self.discography();
+        if discography.primary_types.is_empty() {
+            problems.push("discography.primary_types must name at least one type".to_string());
+        }
+        for primary_type in &discography.primary_types {
+            if !PRIMARY_TYPES.contains(&primary_type.as_str()) {
+                problems.push(format!(
+                    "unknown discography primary type `{primary_type}` (expected {})",
+                    PRIMARY_TYPES.join(", ")
+                ));
+            }
+        }
+        if let (Some(from), Some(to)) = (discography.year_from, discography.year_to)
+            && from > to
+        {
+            problems.push(format!(
+                "discography.year_from {from} is after discography.year_to {to}"
+            ));
+        }
+
+        if let Some(square) = self.square_cover() {
+            if !(16..=4096).contains(&square.size) {
+                problems.push(format!(
+                    "cover_art.square.size must be between 16 and 4096 pixels, not {}",
+                    square.size
+                ));
+            }
+            if !(1..=100).contains(&square.quality) {
+                problems.push(format!(
+                    "cover_art.square.quality must be between 1 and 100, not {}",
+                    square.quality
+                ));
+            }
+        }
+
+        if let Err(err) =
+            NotePathPattern::parse::<AlbumRecord>("note_path_pattern", self.note_path_pattern())
+        {
+            problems.push(err.to_string());
+        }
+        if let Err(err) = NotePathPattern::parse::<ArtistRecord>(
+            "artist_note_path_pattern",
+            self.artist_note_path_pattern(),
+        ) {
+            problems.push(err.to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("Invalid configuration:\n  - {}", problems.join("\n  - "))
+        }
+    }
+
+    /// Path of the named template inside `templates_dir`.
+    pub fn template_file(&self, name: &str) -> PathBuf {
+        self.templates_dir.join(format!("{name}.md"))
+    }
+
     /// Ensures that required directories exist and bootstraps default template content.
     pub fn ensure_filesystem(&self) -> Result<()> {
         for path in [
             self.data_dir(),
             self.album_art_dir(),
             self.notes_dir(),
-            self.template_path()
-                .parent()
-                .unwrap_or_else(|| Path::new("templates")),
+            self.templates_dir(),
         ] {
             fs::create_dir_all(path)
                 .with_context(|| format!("Failed to create directory: {}", path.display()))?;
         }
 
-        if !self.template_path().exists() {
-            let default_template = r#"---
-title: {title}
-artist: {artist}
-release_date: {release_date}
-musicbrainz_id: {musicbrainz_id}
-primary_type: {primary_type}
----
+        for (name, contents, kind) in [
+            (self.default_template(), DEFAULT_TEMPLATE, "note"),
+            (self.artist_template(), DEFAULT_ARTIST_TEMPLATE, "artist"),
+        ] {
+            let path = self.template_file(name);
+            if !path.exists() {
+                fs::write(&path, contents).with_context(|| {
+                    format!(
+                        "Failed to write default {kind} template to {}",
+                        path.display()
+                    )
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses `ALBUM_PROPOGATOR_<name>` when it is set and not empty.
+fn parse_var<T>(lookup: &impl Fn(&str) -> Option<OsString>, name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let key = format!("{ENV_PREFIX}_{name}");
+    match lookup(&key) {
+        Some(value) if !value.is_empty() => value
+            .to_string_lossy()
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|err| anyhow!("Invalid {key}: {err}")),
+        _ => Ok(None),
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<ConfigLayer> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read config file {}", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+fn default_config_dir() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_NAME))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_NAME))
+        .unwrap_or_else(|| PathBuf::from("data"))
+}
+
+/// The `./data` directory earlier versions defaulted to, when it holds a
+/// library and `default` does not, so upgrading keeps the existing library.
+fn legacy_data_dir(cwd: &Path, default: &Path) -> Option<PathBuf> {
+    let legacy = cwd.join("data");
+    (legacy.join("library.db").exists() && !default.join("library.db").exists()).then_some(legacy)
+}
 
-# {title}
+/// Expands a leading `~` to the home directory and joins relative paths onto `base`.
+fn resolve_path(path: &Path, base: &Path) -> PathBuf {
+    let expanded = match path.strip_prefix("~") {
+        Ok(rest) => match dirs::home_dir() {
+            Some(home) => home.join(rest),
+            None => path.to_path_buf(),
+        },
+        Err(_) => path.to_path_buf(),
+    };
 
-**Artist:** {artist}
+    if expanded.is_relative() && !expanded.as_os_str().is_empty() {
+        base.join(expanded)
+    } else {
+        expanded
+    }
+}
 
-**Release Date:** {release_date}
+/// Returns the nearest existing ancestor of `path` if it is a regular file.
+fn file_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.exists())
+        .filter(|ancestor| ancestor.is_file())
+}
 
-**Primary Type:** {primary_type}
+#[cfg(test)]
+impl AppConfig {
+    /// Default settings with the data directory and an Obsidian vault under
+    /// `dir`, both created. Neither a config file nor the environment is read.
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::in_dir_with(dir, ConfigLayer::default())
+    }
 
-**Secondary Types:** {secondary_types}
+    /// Like [`in_dir`](Self::in_dir), with `overrides` on top.
+    pub fn in_dir_with(dir: &Path, overrides: ConfigLayer) -> Self {
+        let vault = dir.join("vault");
+        fs::create_dir_all(vault.join(".obsidian")).unwrap();
+        let mut layer = ConfigLayer {
+            data_dir: Some(dir.join("data")),
+            vault: VaultLayer {
+                root: Some(vault),
+                ..VaultLayer::default()
+            },
+            ..ConfigLayer::default()
+        };
+        layer.merge(overrides);
 
-**Cover Art:** ![]({cover_art_relative_path})
+        let config = Self::from_layer(layer, dir).unwrap();
+        config.validate().unwrap();
+        config.ensure_filesystem().unwrap();
+        config
+    }
+}
 
-## Tracklist
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-{track_listing}
+    fn file_layer(toml: &str) -> ConfigLayer {
+        toml::from_str(toml).expect("valid config file")
+    }
 
-## Notes
+    fn env_layer(vars: &[(&str, &str)]) -> ConfigLayer {
+        ConfigLayer::from_vars(|key| {
+            vars.iter()
+                .find(|(name, _)| format!("{ENV_PREFIX}_{name}") == key)
+                .map(|(_, value)| OsString::from(value))
+        })
+        .expect("valid environment")
+    }
 
-- 
+    #[test]
+    fn later_layers_win() {
+        let mut layer = file_layer(
+            r#"
+            default_template = "file"
+            note_path_pattern = "{artist}/{title}.md"
+            artist_template = "file_artist"
 
-"#;
+            [musicbrainz]
+            throttle_ms = 5000
+            "#,
+        );
+        layer.merge(env_layer(&[
+            ("DEFAULT_TEMPLATE", "env"),
+            ("ARTIST_TEMPLATE", "env_artist"),
+            ("MUSICBRAINZ_THROTTLE_MS", "2000"),
+            ("COVER_ART_SIZE", "500"),
+        ]));
+        layer.merge(ConfigLayer {
+            default_template: Some(String::from("cli")),
+            musicbrainz: MusicBrainzLayer {
+                offline: Some(true),
+                ..MusicBrainzLayer::default()
+            },
+            ..ConfigLayer::default()
+        });
 
-            fs::write(self.template_path(), default_template).with_context(|| {
-                format!(
-                    "Failed to write default note template to {}",
-                    self.template_path().display()
-                )
-            })?;
+        let config = AppConfig::from_layer(layer, Path::new("/config")).unwrap();
+        // CLI over environment over file.
+        assert_eq!(config.default_template(), "cli");
+        assert_eq!(config.artist_template(), "env_artist");
+        assert_eq!(config.note_path_pattern(), "{artist}/{title}.md");
+        assert_eq!(config.musicbrainz_throttle(), Duration::from_millis(2000));
+        assert_eq!(config.cover_art_size(), CoverArtSize::Px500);
+        assert!(config.offline());
+        // Unset everywhere falls back to the default.
+        assert_eq!(config.cover_art_types(), ["front"]);
+        assert_eq!(config.templates_dir(), Path::new("/config/templates"));
+    }
+
+    #[test]
+    fn empty_and_invalid_environment_values() {
+        let layer = env_layer(&[
+            ("DEFAULT_TEMPLATE", ""),
+            ("DISCOGRAPHY_OFFICIAL_ONLY", "true"),
+        ]);
+        assert_eq!(layer.default_template, None);
+        assert_eq!(layer.discography.official_only, Some(true));
+
+        let err = ConfigLayer::from_vars(|key| {
+            (key == "ALBUM_PROPOGATOR_MUSICBRAINZ_THROTTLE_MS").then(|| OsString::from("soon"))
+        })
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid ALBUM_PROPOGATOR_MUSICBRAINZ_THROTTLE_MS")
+        );
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocker = dir.path().join("file");
+        fs::write(&blocker, "").unwrap();
+
+        let layer = ConfigLayer {
+            data_dir: Some(dir.path().to_path_buf()),
+            notes_dir: Some(blocker.join("notes")),
+            default_template: Some(String::from("nested/name")),
+            cover_art: CoverArtLayer {
+                image_types: Some(Vec::new()),
+                base_url: Some(String::from("ftp://example.org")),
+                ..CoverArtLayer::default()
+            },
+            discography: DiscographyLayer {
+                year_from: Some(2000),
+                year_to: Some(1990),
+                ..DiscographyLayer::default()
+            },
+            ..ConfigLayer::default()
+        };
+        let config = AppConfig::from_layer(layer, dir.path()).unwrap();
+
+        let message = config.validate().unwrap_err().to_string();
+        for problem in [
+            "notes_dir",
+            "template name `nested/name`",
+            "cover_art.image_types",
+            "cover_art.base_url `ftp://example.org` must be an http or https URL",
+            "discography.year_from 2000 is after discography.year_to 1990",
+        ] {
+            assert!(
+                message.contains(problem),
+                "missing `{problem}` in:\n{message}"
+            );
         }
+        assert_eq!(message.lines().count(), 6);
+    }
 
-        Ok(())
+    #[test]
+    fn an_earlier_data_dir_is_used_until_the_default_has_a_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let cwd = dir.path().join("project");
+        let default = dir.path().join("xdg");
+
+        assert_eq!(legacy_data_dir(&cwd, &default), None);
+
+        fs::create_dir_all(cwd.join("data/library.db")).unwrap();
+        assert_eq!(legacy_data_dir(&cwd, &default), Some(cwd.join("data")));
+
+        fs::create_dir_all(default.join("library.db")).unwrap();
+        assert_eq!(legacy_data_dir(&cwd, &default), None);
+    }
+
+    #[test]
+    fn note_relative_cover_art_follows_the_album_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("vault");
+        fs::create_dir_all(vault.join(".obsidian")).unwrap();
+        fs::write(
+            vault.join(".obsidian/app.json"),
+            r#"{"attachmentFolderPath": "./assets"}"#,
+        )
+        .unwrap();
+        let config = AppConfig::in_dir_with(
+            dir.path(),
+            ConfigLayer {
+                note_path_pattern: Some(String::from("{artist}/{title}.md")),
+                ..ConfigLayer::default()
+            },
+        );
+
+        let mut album = AlbumRecord {
+            artist: String::from("Portishead"),
+            title: String::from("Dummy"),
+            ..AlbumRecord::default()
+        };
+        assert_eq!(config.album_art_dir(), vault.join("assets"));
+        assert_eq!(
+            config.album_art_dir_for(&album),
+            vault.join("Portishead/assets")
+        );
+
+        album.note_path = Some(vault.join("Moved/Dummy.md").to_string_lossy().into_owned());
+        assert_eq!(config.album_art_dir_for(&album), vault.join("Moved/assets"));
     }
 }
@@ -2,11 +2,15 @@ use anyhow::{Context, Result};
 use sled::IVec;
 
 use crate::config::AppConfig;
-use crate::models::library::{AlbumRecord, CoverArtStatus, NoteStatus};
+use crate::models::ArtistRecord;
+use crate::models::library::{
+    AlbumRecord, CoverArtImage, CoverArtSource, CoverArtStatus, NoteStatus,
+};
 
 #[derive(Clone)]
 pub struct LibraryStore {
     tree: sled::Tree,
+    artists: sled::Tree,
 }
 
 impl LibraryStore {
@@ -20,7 +24,52 @@ impl LibraryStore {
         let tree = db
             .open_tree("albums")
             .context("Unable to open albums tree")?;
-        Ok(Self { tree })
+        let artists = db
+            .open_tree("artists")
+            .context("Unable to open artists tree")?;
+        let store = Self { tree, artists };
+        store
+            .migrate_release_ids()
+            .context("Unable to migrate library records")?;
+        Ok(store)
+    }
+
+    /// Fills in `release_id` on records saved before it was stored, recovering
+    /// it from the cover art URL, which was built from the release ID. Records
+    /// whose URL fell back to the release-group ID point at the release-group
+    /// cover instead. Only the `/release/<id>/front` path is matched, so
+    /// records from any cover art host are migrated.
+    fn migrate_release_ids(&self) -> Result<()> {
+        for result in self.tree.iter() {
+            let (key, value) = result?;
+            let Ok(mut record) = Self::deserialize_record(value) else {
+                continue;
+            };
+            if !record.release_id.is_empty() {
+                continue;
+            }
+            let Some((base_url, id)) = record
+                .cover_art_url
+                .strip_suffix("/front")
+                .and_then(|url| url.rsplit_once("/release/"))
+            else {
+                continue;
+            };
+
+            if id == record.mbid {
+                record.cover_art_url = format!("{base_url}/release-group/{id}/front");
+            } else {
+                record.release_id = id.to_string();
+            }
+
+            let value = serde_json::to_vec(&record).context("Failed to serialize album record")?;
+            self.tree
+                .insert(key, value)
+                .context("Failed to persist album record")?;
+        }
+
+        self.tree.flush()?;
+        Ok(())
     }
 
     pub fn upsert_album(&self, mut record: AlbumRecord) -> Result<bool> {
@@ -39,7 +88,7 @@ impl LibraryStore {
     pub fn get_album(&self, mbid: &str) -> Result<Option<AlbumRecord>> {
         self.tree
             .get(Self::album_key(mbid))?
-            .map(|bytes| Self::deserialize_record(bytes))
+            .map(Self::deserialize_record)
             .transpose()
     }
 
@@ -51,7 +100,7 @@ impl LibraryStore {
                 records.push(record);
             }
         }
-        records.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
+        records.sort_by_key(|a| a.title.to_lowercase());
         Ok(records)
     }
 
@@ -70,6 +119,43 @@ impl LibraryStore {
         Ok(())
     }
 
+    /// Records where the downloaded cover came from and what it is.
+    pub fn set_cover_art_details(
+        &self,
+        mbid: &str,
+        source: Option<CoverArtSource>,
+        image: Option<CoverArtImage>,
+    ) -> Result<()> {
+        if let Some(mut record) = self.get_album(mbid)? {
+            record.cover_art_source = source;
+            record.cover_art_image = image;
+            record.touch();
+            self.upsert_album(record)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_gallery(&self, mbid: &str, enabled: bool) -> Result<()> {
+        if let Some(mut record) = self.get_album(mbid)? {
+            record.gallery = enabled;
+            if !enabled {
+                record.gallery_images.clear();
+            }
+            record.touch();
+            self.upsert_album(record)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_gallery_images(&self, mbid: &str, images: Vec<CoverArtImage>) -> Result<()> {
+        if let Some(mut record) = self.get_album(mbid)? {
+            record.gallery_images = images;
+            record.touch();
+            self.upsert_album(record)?;
+        }
+        Ok(())
+    }
+
     pub fn mark_note_generated(&self, mbid: &str, note_path: String) -> Result<()> {
         if let Some(mut record) = self.get_album(mbid)? {
             record.note_status = NoteStatus::Generated;
@@ -80,6 +166,57 @@ impl LibraryStore {
         Ok(())
     }
 
+    pub fn set_template(&self, mbid: &str, template: Option<String>) -> Result<()> {
+        if let Some(mut record) = self.get_album(mbid)? {
+            record.template = template;
+            record.touch();
+            self.upsert_album(record)?;
+        }
+        Ok(())
+    }
+
+    /// Saves an artist, keeping the note path and creation time of an existing
+    /// record and any disambiguation the new one lacks.
+    pub fn upsert_artist(&self, mut record: ArtistRecord) -> Result<()> {
+        if record.id.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(existing) = self.get_artist(&record.id)? {
+            if record.disambiguation.is_empty() {
+                record.disambiguation = existing.disambiguation;
+            }
+            record.note_path = record.note_path.or(existing.note_path);
+            record.created_at_utc = existing.created_at_utc;
+        }
+        record.touch();
+
+        let value = serde_json::to_vec(&record).context("Failed to serialize artist record")?;
+        self.artists
+            .insert(Self::artist_key(&record.id), value)
+            .context("Failed to persist artist record")?;
+        self.artists.flush()?;
+        Ok(())
+    }
+
+    pub fn get_artist(&self, id: &str) -> Result<Option<ArtistRecord>> {
+        self.artists
+            .get(Self::artist_key(id))?
+            .map(|bytes| {
+                serde_json::from_slice::<ArtistRecord>(&bytes)
+                    .context("Unable to deserialize artist record")
+            })
+            .transpose()
+    }
+
+    pub fn mark_artist_note_generated(&self, id: &str, note_path: String) -> Result<()> {
+        if let Some(mut record) = self.get_artist(id)? {
+            record.note_path = Some(note_path);
+            self.upsert_artist(record)?;
+        }
+        Ok(())
+    }
+
     fn deserialize_record(bytes: IVec) -> Result<AlbumRecord> {
         serde_json::from_slice::<AlbumRecord>(&bytes).context("Unable to deserialize album record")
     }
@@ -87,4 +224,66 @@ impl LibraryStore {
     fn album_key(id: &str) -> Vec<u8> {
         format!("album::{id}").into_bytes()
     }
+
+    fn artist_key(id: &str) -> Vec<u8> {
+        format!("artist::{id}").into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELEASE_GROUP: &str = "76df3287-6cda-33eb-8e9a-044b5e15ffdd";
+    const RELEASE: &str = "3a3aca1e-ae9b-4b35-9b7b-c2d6c5f2b4f3";
+    /// A release group whose cover art URL fell back to its own ID.
+    const FALLBACK_GROUP: &str = "48140466-cff6-3222-bd55-63c27e43190d";
+
+    fn contents(store: &LibraryStore) -> Vec<(IVec, IVec)> {
+        store.tree.iter().map(|entry| entry.unwrap()).collect()
+    }
+
+    #[test]
+    fn legacy_records_are_migrated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig::in_dir(dir.path());
+        let legacy = |mbid: &str, url: String| AlbumRecord {
+            mbid: mbid.to_string(),
+            cover_art_url: url,
+            ..AlbumRecord::default()
+        };
+        let store = LibraryStore::open(&config).unwrap();
+        store
+            .upsert_album(legacy(
+                RELEASE_GROUP,
+                format!("http://localhost:5000/caa/release/{RELEASE}/front"),
+            ))
+            .unwrap();
+        store
+            .upsert_album(legacy(
+                FALLBACK_GROUP,
+                format!("https://coverartarchive.org/release/{FALLBACK_GROUP}/front"),
+            ))
+            .unwrap();
+        drop(store);
+
+        let store = LibraryStore::open(&config).unwrap();
+        let migrated = store.get_album(RELEASE_GROUP).unwrap().unwrap();
+        assert_eq!(migrated.release_id, RELEASE);
+        assert_eq!(
+            migrated.cover_art_url,
+            format!("http://localhost:5000/caa/release/{RELEASE}/front")
+        );
+        let fallback = store.get_album(FALLBACK_GROUP).unwrap().unwrap();
+        assert_eq!(fallback.release_id, "");
+        assert_eq!(
+            fallback.cover_art_url,
+            format!("https://coverartarchive.org/release-group/{FALLBACK_GROUP}/front")
+        );
+        let after_first = contents(&store);
+        drop(store);
+
+        let store = LibraryStore::open(&config).unwrap();
+        assert_eq!(contents(&store), after_first);
+    }
 }
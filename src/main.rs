@@ -1,28 +1,53 @@
 mod api;
 mod app;
+mod cli;
+mod commands;
 mod config;
 mod library;
 mod models;
 mod notes;
 mod tasks;
 mod tui;
+mod vault;
+
+use std::sync::Arc;
 
 use anyhow::Result;
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config = config::AppConfig::default();
+    let cli = cli::Cli::parse();
+    let config = config::AppConfig::load(cli.config.as_deref(), cli.config_overrides())?;
     config.ensure_filesystem()?;
 
     let (msg_tx, msg_rx) = tokio::sync::mpsc::unbounded_channel();
 
-    let client = api::musicbrainz::MusicBrainzClient::new(&config)?;
+    let client: Arc<dyn api::MetadataProvider> =
+        Arc::new(api::musicbrainz::MusicBrainzClient::new(&config)?);
     let library = library::LibraryStore::open(&config)?;
-    let downloader = tasks::cover_art::spawn(config.clone(), library.clone(), msg_tx.clone())?;
+    let downloader = tasks::cover_art::spawn(
+        config.clone(),
+        client.clone(),
+        library.clone(),
+        msg_tx.clone(),
+    )?;
     let note_service = notes::NoteService::new(config.clone(), library.clone());
 
-    let controller = tui::AppController::new(client, library, downloader, note_service, msg_tx);
+    let controller = tui::AppController::new(
+        client,
+        library,
+        downloader,
+        note_service,
+        config.discography().clone(),
+        msg_tx,
+    );
 
-    let app = tui::App::new(controller, msg_rx);
-    tui::run(app).await
+    match cli.command {
+        Some(command) => commands::run(command, cli.json, controller, msg_rx).await,
+        None => {
+            let app = tui::App::new(controller, msg_rx);
+            tui::run(app).await
+        }
+    }
 }
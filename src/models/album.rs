@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::artist::ArtistCredit;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct TrackInfo {
     pub position: String,
@@ -8,23 +10,17 @@ pub struct TrackInfo {
     pub length_ms: i64,
 }
 
-impl Default for TrackInfo {
-    fn default() -> Self {
-        Self {
-            position: String::new(),
-            title: String::new(),
-            length_ms: 0,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct Album {
     pub id: String,
     pub release_id: String, // Actual release ID for cover art
     pub title: String,
     pub artist: String,
+    /// MBID of the first credited artist.
+    pub artist_id: String,
+    /// Every credited artist, in credit order.
+    pub credits: Vec<ArtistCredit>,
     pub primary_type: String,
     pub secondary_types: Vec<String>,
     pub status: String,
@@ -32,36 +28,20 @@ pub struct Album {
     pub disambiguation: String,
     pub label: String,
     pub country: String,
+    /// UPC/EAN barcode of the release.
+    pub barcode: String,
     pub tracklist: Vec<TrackInfo>,
 }
 
-impl Default for Album {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            release_id: String::new(),
-            title: String::new(),
-            artist: String::new(),
-            primary_type: String::new(),
-            secondary_types: Vec::new(),
-            status: String::new(),
-            first_release_date: String::new(),
-            disambiguation: String::new(),
-            label: String::new(),
-            country: String::new(),
-            tracklist: Vec::new(),
-        }
-    }
-}
-
 impl Album {
-    pub fn cover_art_url(&self) -> String {
-        let id_for_cover = if self.release_id.is_empty() {
-            &self.id
+    /// Front cover of the chosen release, or of the release group until a
+    /// release has been picked, on the Cover Art Archive at `base_url`.
+    pub fn cover_art_url(&self, base_url: &str) -> String {
+        if self.release_id.is_empty() {
+            format!("{base_url}/release-group/{}/front", self.id)
         } else {
-            &self.release_id
-        };
-        format!("https://coverartarchive.org/release/{}/front", id_for_cover)
+            format!("{base_url}/release/{}/front", self.release_id)
+        }
     }
 
     pub fn secondary_types_label(&self) -> String {
@@ -1,6 +1,7 @@
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct Artist {
     pub id: String,
@@ -9,17 +10,6 @@ pub struct Artist {
     pub score: i32,
 }
 
-impl Default for Artist {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            name: String::new(),
-            disambiguation: String::new(),
-            score: 0,
-        }
-    }
-}
-
 impl Artist {
     pub fn display_name(&self) -> String {
         if self.disambiguation.is_empty() {
@@ -29,3 +19,110 @@ impl Artist {
         }
     }
 }
+
+/// One entry of a MusicBrainz artist credit, e.g. the `B` in `A & B feat. C`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ArtistCredit {
+    pub artist_id: String,
+    /// The artist's own name.
+    pub name: String,
+    /// The name printed on the release, which may differ from `name`.
+    pub credited_as: String,
+    /// Text joining this credit to the next one, such as ` & ` or ` feat. `.
+    pub join_phrase: String,
+}
+
+impl ArtistCredit {
+    /// Name to display for this entry: the credited name, falling back to the artist's own.
+    pub fn display_name(&self) -> &str {
+        if self.credited_as.is_empty() {
+            &self.name
+        } else {
+            &self.credited_as
+        }
+    }
+
+    /// The full credit as printed, e.g. `A & B feat. C`.
+    pub fn credit_string(credits: &[ArtistCredit]) -> String {
+        credits
+            .iter()
+            .map(|credit| format!("{}{}", credit.display_name(), credit.join_phrase))
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
+/// An artist credited on library albums, kept so artist notes can be generated.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ArtistRecord {
+    pub id: String,
+    pub name: String,
+    pub disambiguation: String,
+    pub note_path: Option<String>,
+    pub created_at_utc: String,
+    pub updated_at_utc: String,
+}
+
+impl ArtistRecord {
+    pub fn new(id: &str, name: &str, disambiguation: &str) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            disambiguation: disambiguation.to_string(),
+            note_path: None,
+            created_at_utc: now.clone(),
+            updated_at_utc: now,
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at_utc = Utc::now().to_rfc3339();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credit(name: &str, credited_as: &str, join_phrase: &str) -> ArtistCredit {
+        ArtistCredit {
+            artist_id: format!("{name}-id"),
+            name: name.to_string(),
+            credited_as: credited_as.to_string(),
+            join_phrase: join_phrase.to_string(),
+        }
+    }
+
+    #[test]
+    fn a_single_credit_is_the_artist_name() {
+        assert_eq!(
+            ArtistCredit::credit_string(&[credit("Portishead", "", "")]),
+            "Portishead"
+        );
+    }
+
+    #[test]
+    fn join_phrases_connect_multiple_artists() {
+        let credits = [
+            credit("Massive Attack", "", " & "),
+            credit("Mad Professor", "", " feat. "),
+            credit("Tracey Thorn", "", ""),
+        ];
+        assert_eq!(
+            ArtistCredit::credit_string(&credits),
+            "Massive Attack & Mad Professor feat. Tracey Thorn"
+        );
+    }
+
+    #[test]
+    fn credited_names_replace_the_artist_name() {
+        let credits = [credit("Prince", "The Artist", " & "), credit("NPG", "", "")];
+        assert_eq!(ArtistCredit::credit_string(&credits), "The Artist & NPG");
+        assert_eq!(credits[0].display_name(), "The Artist");
+        assert_eq!(credits[1].display_name(), "NPG");
+    }
+}
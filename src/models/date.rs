@@ -0,0 +1,59 @@
+use chrono::NaiveDate;
+
+/// Parses a MusicBrainz date of the form `YYYY`, `YYYY-MM` or `YYYY-MM-DD`,
+/// filling missing parts with `01`. Only for ordering: the filled-in parts
+/// are not known, so use [`parse_full_date`] for anything that is shown.
+pub fn parse_partial_date(value: &str) -> Option<NaiveDate> {
+    let padded = match value.len() {
+        4 => format!("{value}-01-01"),
+        7 => format!("{value}-01"),
+        10 => value.to_string(),
+        _ => return None,
+    };
+    NaiveDate::parse_from_str(&padded, "%Y-%m-%d").ok()
+}
+
+/// Parses a complete `YYYY-MM-DD` date; a bare year or month gives `None`.
+pub fn parse_full_date(value: &str) -> Option<NaiveDate> {
+    if value.len() != 10 {
+        return None;
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_dates_are_padded() {
+        assert_eq!(
+            parse_partial_date("1994-08-22"),
+            NaiveDate::from_ymd_opt(1994, 8, 22)
+        );
+        assert_eq!(
+            parse_partial_date("1994-08"),
+            NaiveDate::from_ymd_opt(1994, 8, 1)
+        );
+        assert_eq!(
+            parse_partial_date("1994"),
+            NaiveDate::from_ymd_opt(1994, 1, 1)
+        );
+        assert_eq!(parse_partial_date(""), None);
+        assert_eq!(parse_partial_date("soon"), None);
+        assert_eq!(parse_partial_date("1994-13"), None);
+        assert_eq!(parse_partial_date("1994-8-2"), None);
+    }
+
+    #[test]
+    fn full_dates_need_a_day() {
+        assert_eq!(
+            parse_full_date("1994-08-22"),
+            NaiveDate::from_ymd_opt(1994, 8, 22)
+        );
+        assert_eq!(parse_full_date("1994-08"), None);
+        assert_eq!(parse_full_date("1994"), None);
+        assert_eq!(parse_full_date(""), None);
+        assert_eq!(parse_full_date("1994-02-30"), None);
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::{Result, anyhow, bail};
+
+use super::album::Album;
+
+/// Release group primary types MusicBrainz can filter an artist's
+/// discography by, in the order they are sent.
+pub const PRIMARY_TYPES: [&str; 5] = ["album", "ep", "single", "broadcast", "other"];
+
+/// Which of an artist's release groups are listed.
+///
+/// Primary types and `official_only` are sent to MusicBrainz with the browse
+/// request; secondary types and the year range are applied to the result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscographyFilter {
+    /// Lowercase primary types, in [`PRIMARY_TYPES`] order.
+    pub primary_types: Vec<String>,
+    /// Groups with any of these secondary types are hidden, e.g. `Live`.
+    pub exclude_secondary_types: Vec<String>,
+    /// When not empty, only groups with at least one of these secondary types
+    /// are listed.
+    pub include_secondary_types: Vec<String>,
+    /// Hide groups whose releases are all bootlegs, promos or pseudo-releases.
+    pub official_only: bool,
+    /// First and last year of first release, inclusive. Undated groups are
+    /// hidden while either bound is set.
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+}
+
+/// How a secondary type is treated by a [`DiscographyFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecondaryTypeMode {
+    Shown,
+    Hidden,
+    Only,
+}
+
+impl Default for DiscographyFilter {
+    fn default() -> Self {
+        Self {
+            primary_types: vec![String::from("album"), String::from("ep")],
+            exclude_secondary_types: Vec::new(),
+            include_secondary_types: Vec::new(),
+            official_only: false,
+            year_from: None,
+            year_to: None,
+        }
+    }
+}
+
+impl DiscographyFilter {
+    /// The `type` parameter of the release-group browse request.
+    pub fn type_param(&self) -> String {
+        self.primary_types.join("|")
+    }
+
+    pub fn has_primary_type(&self, primary_type: &str) -> bool {
+        self.primary_types
+            .iter()
+            .any(|wanted| wanted.eq_ignore_ascii_case(primary_type))
+    }
+
+    /// Adds or removes a primary type. The last one cannot be removed, since
+    /// MusicBrainz would then list every type.
+    pub fn toggle_primary_type(&mut self, primary_type: &str) {
+        let primary_type = primary_type.to_ascii_lowercase();
+        if self.has_primary_type(&primary_type) {
+            if self.primary_types.len() > 1 {
+                self.primary_types.retain(|wanted| *wanted != primary_type);
+            }
+        } else {
+            let mut types = self.primary_types.clone();
+            types.push(primary_type);
+            self.set_primary_types(types);
+        }
+    }
+
+    /// Replaces the primary types, lowercased and in [`PRIMARY_TYPES`] order
+    /// so equal filters send equal requests. Unknown types sort last.
+    pub fn set_primary_types(&mut self, types: Vec<String>) {
+        let mut types = types
+            .into_iter()
+            .map(|primary_type| primary_type.trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        types.sort_by_key(|primary_type| {
+            PRIMARY_TYPES
+                .iter()
+                .position(|known| known == primary_type)
+                .unwrap_or(PRIMARY_TYPES.len())
+        });
+        types.dedup();
+        self.primary_types = types;
+    }
+
+    pub fn secondary_type_mode(&self, secondary_type: &str) -> SecondaryTypeMode {
+        let listed = |types: &[String]| {
+            types
+                .iter()
+                .any(|listed| listed.eq_ignore_ascii_case(secondary_type))
+        };
+        if listed(&self.include_secondary_types) {
+            SecondaryTypeMode::Only
+        } else if listed(&self.exclude_secondary_types) {
+            SecondaryTypeMode::Hidden
+        } else {
+            SecondaryTypeMode::Shown
+        }
+    }
+
+    /// Moves a secondary type from shown to hidden to only and back.
+    pub fn cycle_secondary_type(&mut self, secondary_type: &str) {
+        let next = match self.secondary_type_mode(secondary_type) {
+            SecondaryTypeMode::Shown => SecondaryTypeMode::Hidden,
+            SecondaryTypeMode::Hidden => SecondaryTypeMode::Only,
+            SecondaryTypeMode::Only => SecondaryTypeMode::Shown,
+        };
+        let other = |listed: &String| !listed.eq_ignore_ascii_case(secondary_type);
+        self.exclude_secondary_types.retain(other);
+        self.include_secondary_types.retain(other);
+        match next {
+            SecondaryTypeMode::Shown => {}
+            SecondaryTypeMode::Hidden => self
+                .exclude_secondary_types
+                .push(secondary_type.to_string()),
+            SecondaryTypeMode::Only => self
+                .include_secondary_types
+                .push(secondary_type.to_string()),
+        }
+    }
+
+    /// Whether a release group fetched with this filter's primary types
+    /// passes its secondary type and year conditions.
+    pub fn matches(&self, album: &Album) -> bool {
+        let has = |wanted: &String| {
+            album
+                .secondary_types
+                .iter()
+                .any(|secondary| secondary.eq_ignore_ascii_case(wanted))
+        };
+        if self.exclude_secondary_types.iter().any(has) {
+            return false;
+        }
+        if !self.include_secondary_types.is_empty() && !self.include_secondary_types.iter().any(has)
+        {
+            return false;
+        }
+
+        if self.year_from.is_none() && self.year_to.is_none() {
+            return true;
+        }
+        let Some(year) = album
+            .first_release_date
+            .get(..4)
+            .and_then(|year| year.parse::<i32>().ok())
+        else {
+            return false;
+        };
+        self.year_from.is_none_or(|from| year >= from) && self.year_to.is_none_or(|to| year <= to)
+    }
+
+    /// Sets the year range from `1990-1999`, `1990-`, `-1999` or `1995`.
+    /// Empty text clears it.
+    pub fn set_years(&mut self, text: &str) -> Result<()> {
+        let year = |value: &str| -> Result<Option<i32>> {
+            let value = value.trim();
+            if value.is_empty() {
+                return Ok(None);
+            }
+            value
+                .parse()
+                .map(Some)
+                .map_err(|_| anyhow!("invalid year `{value}`"))
+        };
+
+        let (from, to) = match text.trim().split_once(['-', '–']) {
+            Some((from, to)) => (year(from)?, year(to)?),
+            None => {
+                let single = year(text)?;
+                (single, single)
+            }
+        };
+        if let (Some(from), Some(to)) = (from, to)
+            && from > to
+        {
+            bail!("year range {from}-{to} ends before it starts");
+        }
+
+        self.year_from = from;
+        self.year_to = to;
+        Ok(())
+    }
+
+    /// A short description for pane titles, e.g.
+    /// `album, ep • hide Live • official • 1990–1999`.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.primary_types.join(", ")];
+        if !self.exclude_secondary_types.is_empty() {
+            parts.push(format!("hide {}", self.exclude_secondary_types.join(", ")));
+        }
+        if !self.include_secondary_types.is_empty() {
+            parts.push(format!("only {}", self.include_secondary_types.join(", ")));
+        }
+        if self.official_only {
+            parts.push(String::from("official"));
+        }
+        match (self.year_from, self.year_to) {
+            (None, None) => {}
+            (Some(from), None) => parts.push(format!("{from}–")),
+            (None, Some(to)) => parts.push(format!("–{to}")),
+            (Some(from), Some(to)) => parts.push(format!("{from}–{to}")),
+        }
+        parts.join(" • ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primary_types_keep_request_order() {
+        let mut filter = DiscographyFilter::default();
+        filter.toggle_primary_type("single");
+        filter.toggle_primary_type("Album");
+        assert_eq!(filter.type_param(), "ep|single");
+
+        filter.toggle_primary_type("album");
+        assert_eq!(filter.type_param(), "album|ep|single");
+
+        filter.set_primary_types(vec!["other".into(), "EP".into(), "ep".into()]);
+        filter.toggle_primary_type("ep");
+        filter.toggle_primary_type("other");
+        assert_eq!(filter.type_param(), "other");
+    }
+
+    #[test]
+    fn secondary_types_cycle_through_modes() {
+        let mut filter = DiscographyFilter::default();
+        let live = Album {
+            secondary_types: vec![String::from("Live")],
+            ..Album::default()
+        };
+        let studio = Album::default();
+
+        filter.cycle_secondary_type("Live");
+        assert_eq!(
+            filter.secondary_type_mode("live"),
+            SecondaryTypeMode::Hidden
+        );
+        assert!(!filter.matches(&live) && filter.matches(&studio));
+
+        filter.cycle_secondary_type("Live");
+        assert_eq!(filter.secondary_type_mode("Live"), SecondaryTypeMode::Only);
+        assert!(filter.matches(&live) && !filter.matches(&studio));
+
+        filter.cycle_secondary_type("Live");
+        assert_eq!(filter.secondary_type_mode("Live"), SecondaryTypeMode::Shown);
+        assert!(filter.matches(&live) && filter.matches(&studio));
+    }
+
+    #[test]
+    fn year_ranges_parse_open_ends() {
+        let mut filter = DiscographyFilter::default();
+        let years = |filter: &DiscographyFilter| (filter.year_from, filter.year_to);
+
+        filter.set_years("1990-1999").unwrap();
+        assert_eq!(years(&filter), (Some(1990), Some(1999)));
+        filter.set_years("2000-").unwrap();
+        assert_eq!(years(&filter), (Some(2000), None));
+        filter.set_years("-1994").unwrap();
+        assert_eq!(years(&filter), (None, Some(1994)));
+        filter.set_years("1994").unwrap();
+        assert_eq!(years(&filter), (Some(1994), Some(1994)));
+        assert!(filter.set_years("1999-1990").is_err());
+        assert!(filter.set_years("soon").is_err());
+        filter.set_years("").unwrap();
+        assert_eq!(years(&filter), (None, None));
+
+        filter.set_years("1994-").unwrap();
+        let undated = Album::default();
+        assert!(!filter.matches(&undated));
+        assert_eq!(filter.summary(), "album, ep • 1994–");
+    }
+}
@@ -1,11 +1,15 @@
+use std::fmt;
+
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
 use super::album::{Album, TrackInfo};
+use super::artist::ArtistCredit;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum CoverArtStatus {
+    #[default]
     Pending,
     Queued,
     Downloading,
@@ -13,41 +17,100 @@ pub enum CoverArtStatus {
     Unavailable,
 }
 
-impl Default for CoverArtStatus {
-    fn default() -> Self {
-        Self::Pending
+/// Where a downloaded cover came from, in the order they are tried.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverArtSource {
+    /// The front image of the album's own release.
+    Release,
+    /// The image the Cover Art Archive picks for the whole release group.
+    ReleaseGroup,
+    /// The front image of another release in the group, by release ID.
+    OtherRelease(String),
+}
+
+impl CoverArtSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoverArtSource::Release => "release",
+            CoverArtSource::ReleaseGroup => "release_group",
+            CoverArtSource::OtherRelease(_) => "other_release",
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+impl fmt::Display for CoverArtSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverArtSource::Release => write!(f, "release"),
+            CoverArtSource::ReleaseGroup => write!(f, "release group"),
+            CoverArtSource::OtherRelease(id) => write!(f, "release {id}"),
+        }
+    }
+}
+
+/// Details of the downloaded cover image, as listed by the Cover Art Archive.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct CoverArtImage {
+    /// The archive's image ID.
+    pub id: String,
+    /// Image types such as `Front` or `Booklet`.
+    pub types: Vec<String>,
+    pub comment: String,
+    /// Size that was downloaded: `250`, `500`, `1200` or `original`.
+    pub size: String,
+    pub url: String,
+    pub content_type: String,
+    pub bytes: u64,
+    /// Where the image was saved. For squared covers, the processed file.
+    pub path: String,
+    /// The cover as downloaded, when squaring kept it; otherwise empty.
+    pub original_path: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum NoteStatus {
+    #[default]
     NotGenerated,
     Generated,
 }
 
-impl Default for NoteStatus {
-    fn default() -> Self {
-        Self::NotGenerated
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AlbumRecord {
     pub mbid: String,
+    /// The release (edition) metadata and cover art were taken from.
+    pub release_id: String,
     pub title: String,
+    /// The full credit as printed, e.g. `A & B feat. C`.
     pub artist: String,
+    /// MBID of the first credited artist.
+    pub artist_id: String,
+    /// Every credited artist, in credit order.
+    pub credits: Vec<ArtistCredit>,
     pub primary_type: String,
     pub secondary_types: Vec<String>,
     pub status: String,
     pub release_date: String,
     pub label: String,
     pub country: String,
+    /// UPC/EAN barcode of the release, when it has one.
+    pub barcode: String,
     pub disambiguation: String,
     pub cover_art_url: String,
     pub cover_art_path: Option<String>,
+    /// Which source the downloaded cover came from.
+    pub cover_art_source: Option<CoverArtSource>,
+    pub cover_art_image: Option<CoverArtImage>,
+    /// Whether every image of the release is downloaded, not just the cover.
+    pub gallery: bool,
+    /// The downloaded gallery images, in the archive's order.
+    pub gallery_images: Vec<CoverArtImage>,
     pub note_path: Option<String>,
+    /// Note template chosen for this album; `None` follows the configured rules.
+    pub template: Option<String>,
     pub tracklist: Vec<TrackInfo>,
     pub cover_art_status: CoverArtStatus,
     pub note_status: NoteStatus,
@@ -60,18 +123,27 @@ impl Default for AlbumRecord {
         let now = Utc::now();
         Self {
             mbid: String::new(),
+            release_id: String::new(),
             title: String::new(),
             artist: String::new(),
+            artist_id: String::new(),
+            credits: Vec::new(),
             primary_type: String::new(),
             secondary_types: Vec::new(),
             status: String::new(),
             release_date: String::new(),
             label: String::new(),
             country: String::new(),
+            barcode: String::new(),
             disambiguation: String::new(),
             cover_art_url: String::new(),
             cover_art_path: None,
+            cover_art_source: None,
+            cover_art_image: None,
+            gallery: false,
+            gallery_images: Vec::new(),
             note_path: None,
+            template: None,
             tracklist: Vec::new(),
             cover_art_status: CoverArtStatus::Pending,
             note_status: NoteStatus::NotGenerated,
@@ -82,22 +154,33 @@ impl Default for AlbumRecord {
 }
 
 impl AlbumRecord {
-    pub fn from_album(album: &Album) -> Self {
+    /// A new record for `album`, pointing its cover at the Cover Art Archive
+    /// at `cover_art_base_url`.
+    pub fn from_album(album: &Album, cover_art_base_url: &str) -> Self {
         let now = Utc::now().to_rfc3339();
         Self {
             mbid: album.id.clone(),
+            release_id: album.release_id.clone(),
             title: album.title.clone(),
             artist: album.artist.clone(),
+            artist_id: album.artist_id.clone(),
+            credits: album.credits.clone(),
             primary_type: album.primary_type.clone(),
             secondary_types: album.secondary_types.clone(),
             status: album.status.clone(),
             release_date: album.first_release_date.clone(),
             label: album.label.clone(),
             country: album.country.clone(),
+            barcode: album.barcode.clone(),
             disambiguation: album.disambiguation.clone(),
-            cover_art_url: album.cover_art_url(),
+            cover_art_url: album.cover_art_url(cover_art_base_url),
             cover_art_path: None,
+            cover_art_source: None,
+            cover_art_image: None,
+            gallery: false,
+            gallery_images: Vec::new(),
             note_path: None,
+            template: None,
             tracklist: album.tracklist.clone(),
             cover_art_status: CoverArtStatus::Pending,
             note_status: NoteStatus::NotGenerated,
@@ -106,16 +189,44 @@ impl AlbumRecord {
         }
     }
 
+    /// The credited artists. Records saved before credits were kept fall back
+    /// to a single credit built from `artist` and `artist_id`.
+    pub fn artist_credits(&self) -> Vec<ArtistCredit> {
+        if !self.credits.is_empty() || self.artist_id.is_empty() {
+            return self.credits.clone();
+        }
+        vec![ArtistCredit {
+            artist_id: self.artist_id.clone(),
+            name: self.artist.clone(),
+            ..ArtistCredit::default()
+        }]
+    }
+
     pub fn touch(&mut self) {
         self.updated_at_utc = Utc::now().to_rfc3339();
     }
 
-    pub fn cover_art_filename(&self) -> String {
-        format!("{}.jpg", self.mbid)
+    /// File name for the album's cover, with the extension of its image format.
+    pub fn cover_art_filename(&self, extension: &str) -> String {
+        format!("{}.{extension}", self.mbid)
     }
 
-    pub fn note_filename(&self) -> String {
-        format!("{} - {}.md", self.artist, self.title)
+    /// Folder for the album's gallery images, named after its MBID.
+    pub fn gallery_dirname(&self) -> String {
+        self.mbid.clone()
+    }
+
+    /// File name of the gallery image at `position` (from 1), e.g.
+    /// `<mbid> 02 Back.jpg`. The MBID keeps names unique across the vault,
+    /// which shortest-path links rely on.
+    pub fn gallery_filename(&self, position: usize, types: &[String], extension: &str) -> String {
+        let types = if types.is_empty() {
+            String::from("Other")
+        } else {
+            types.join(", ")
+        };
+        let stem = sanitize_filename::sanitize(format!("{} {position:02} {types}", self.mbid));
+        format!("{stem}.{extension}")
     }
 
     pub fn as_track_listing_lines(&self) -> Vec<String> {
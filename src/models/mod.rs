@@ -1,7 +1,13 @@
 pub mod album;
 pub mod artist;
+pub mod date;
+pub mod discography;
 pub mod library;
+pub mod release;
 
 pub use album::Album;
-pub use artist::Artist;
-pub use library::{AlbumRecord, CoverArtStatus};
+pub use artist::{Artist, ArtistCredit, ArtistRecord};
+pub use date::{parse_full_date, parse_partial_date};
+pub use discography::DiscographyFilter;
+pub use library::{AlbumRecord, CoverArtImage, CoverArtSource, CoverArtStatus};
+pub use release::Release;
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// One release (edition) of a release group, as offered when picking which
+/// edition to store.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Release {
+    pub id: String,
+    pub title: String,
+    /// The full artist credit as printed.
+    pub artist: String,
+    pub release_group_id: String,
+    pub status: String,
+    pub date: String,
+    pub country: String,
+    /// Media formats, e.g. `2×CD` or `12" Vinyl + Digital Media`.
+    pub format: String,
+    pub label: String,
+    pub catalog_number: String,
+    /// UPC/EAN barcode, empty when unknown.
+    pub barcode: String,
+    pub track_count: i32,
+    pub disambiguation: String,
+    /// Whether the Cover Art Archive has a front image for this release.
+    pub has_front_cover: bool,
+}
@@ -0,0 +1,370 @@
+use chrono::NaiveDate;
+
+use crate::models::{AlbumRecord, ArtistRecord, parse_full_date};
+
+use super::template::{CreditContext, GalleryImageContext, yaml_quote};
+
+/// A typed frontmatter property. Each variant serializes to the YAML form
+/// Obsidian infers the matching property type from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Text(String),
+    Number(i64),
+    Date(NaiveDate),
+    List(Vec<String>),
+    /// A wikilink target, written as `"[[target]]"`.
+    Link(String),
+}
+
+impl PropertyValue {
+    fn write_yaml(&self, out: &mut String) {
+        match self {
+            PropertyValue::Text(text) => out.push_str(&format!(" {}\n", yaml_quote(text))),
+            PropertyValue::Number(number) => out.push_str(&format!(" {number}\n")),
+            PropertyValue::Date(date) => out.push_str(&format!(" {}\n", date.format("%Y-%m-%d"))),
+            PropertyValue::List(items) if items.is_empty() => out.push_str(" []\n"),
+            PropertyValue::List(items) => {
+                out.push('\n');
+                for item in items {
+                    out.push_str(&format!("  - {}\n", yaml_quote(item)));
+                }
+            }
+            PropertyValue::Link(target) => {
+                let link = if target.starts_with("[[") {
+                    target.clone()
+                } else {
+                    format!("[[{target}]]")
+                };
+                out.push_str(&format!(" {}\n", yaml_quote(&link)));
+            }
+        }
+    }
+}
+
+/// Every key the tool may write to an album note. When syncing an existing
+/// note these keys are replaced or removed; any other property belongs to the user.
+const MANAGED_KEYS: &[&str] = &[
+    "title",
+    "artist",
+    "artists",
+    "release_date",
+    "year",
+    "primary_type",
+    "secondary_types",
+    "status",
+    "label",
+    "country",
+    "barcode",
+    "disambiguation",
+    "musicbrainz_id",
+    "musicbrainz_release_id",
+    "cover_art",
+    "gallery",
+];
+
+/// Every key the tool may write to an artist note.
+const ARTIST_MANAGED_KEYS: &[&str] = &["name", "disambiguation", "musicbrainz_id", "albums"];
+
+/// The properties the tool owns, in the order they are written.
+#[derive(Debug, Clone, Default)]
+pub struct Frontmatter {
+    properties: Vec<(String, PropertyValue)>,
+    managed_keys: &'static [&'static str],
+}
+
+impl Frontmatter {
+    /// Builds the managed property map for an album. Empty text fields are
+    /// left out so Obsidian does not show blank properties. `artist` links to
+    /// the artist note for single-artist credits and holds the full credit
+    /// string otherwise; `artists` lists every credited artist, linked where
+    /// they have a note. `gallery` links every downloaded gallery image and is
+    /// left out when the album has none.
+    pub fn for_album(
+        album: &AlbumRecord,
+        cover_art_link: &str,
+        gallery: &[GalleryImageContext],
+        credits: &[CreditContext],
+    ) -> Self {
+        let mut frontmatter = Self {
+            managed_keys: MANAGED_KEYS,
+            ..Self::default()
+        };
+
+        frontmatter.text("title", &album.title);
+        match credits {
+            [credit] if !credit.link.is_empty() => {
+                frontmatter.insert("artist", PropertyValue::Link(credit.link.clone()));
+            }
+            _ => frontmatter.text("artist", &album.artist),
+        }
+        if !credits.is_empty() {
+            frontmatter.insert(
+                "artists",
+                PropertyValue::List(
+                    credits
+                        .iter()
+                        .map(|credit| {
+                            if credit.link.is_empty() {
+                                credit.name.clone()
+                            } else {
+                                credit.link.clone()
+                            }
+                        })
+                        .collect(),
+                ),
+            );
+        }
+        // Obsidian would read `1994` as 1994-01-01, so only a full date is typed.
+        match parse_full_date(&album.release_date) {
+            Some(date) => frontmatter.insert("release_date", PropertyValue::Date(date)),
+            None => frontmatter.text("release_date", &album.release_date),
+        }
+        if let Some(year) = album.release_date.get(..4).and_then(|y| y.parse().ok()) {
+            frontmatter.insert("year", PropertyValue::Number(year));
+        }
+        frontmatter.text("primary_type", &album.primary_type);
+        frontmatter.insert(
+            "secondary_types",
+            PropertyValue::List(album.secondary_types.clone()),
+        );
+        frontmatter.text("status", &album.status);
+        frontmatter.text("label", &album.label);
+        frontmatter.text("country", &album.country);
+        frontmatter.text("barcode", &album.barcode);
+        frontmatter.text("disambiguation", &album.disambiguation);
+        frontmatter.text("musicbrainz_id", &album.mbid);
+        frontmatter.text("musicbrainz_release_id", &album.release_id);
+        if !cover_art_link.is_empty() {
+            frontmatter.insert("cover_art", PropertyValue::Link(cover_art_link.to_string()));
+        }
+        if !gallery.is_empty() {
+            frontmatter.insert(
+                "gallery",
+                PropertyValue::List(gallery.iter().map(|image| image.link.clone()).collect()),
+            );
+        }
+
+        frontmatter
+    }
+
+    /// Builds the managed property map for an artist note. `albums` holds a
+    /// wikilink to each album note.
+    pub fn for_artist(artist: &ArtistRecord, albums: Vec<String>) -> Self {
+        let mut frontmatter = Self {
+            managed_keys: ARTIST_MANAGED_KEYS,
+            ..Self::default()
+        };
+
+        frontmatter.text("name", &artist.name);
+        frontmatter.text("disambiguation", &artist.disambiguation);
+        frontmatter.text("musicbrainz_id", &artist.id);
+        frontmatter.insert("albums", PropertyValue::List(albums));
+
+        frontmatter
+    }
+
+    pub fn insert(&mut self, key: &str, value: PropertyValue) {
+        match self
+            .properties
+            .iter_mut()
+            .find(|(existing, _)| existing == key)
+        {
+            Some((_, slot)) => *slot = value,
+            None => self.properties.push((key.to_string(), value)),
+        }
+    }
+
+    fn text(&mut self, key: &str, value: &str) {
+        if !value.trim().is_empty() {
+            self.insert(key, PropertyValue::Text(value.to_string()));
+        }
+    }
+
+    /// Keys this kind of note owns, whether or not they are set.
+    pub fn managed_keys(&self) -> &'static [&'static str] {
+        self.managed_keys
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.properties.iter().any(|(existing, _)| existing == key)
+    }
+
+    /// Serializes the properties, without the surrounding `---` fences.
+    pub fn to_yaml(&self) -> String {
+        self.entries().map(|(_, raw)| raw).collect()
+    }
+
+    /// Each property as a key and its serialized YAML lines.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, String)> {
+        self.properties.iter().map(|(key, value)| {
+            let mut raw = format!("{key}:");
+            value.write_yaml(&mut raw);
+            (key.as_str(), raw)
+        })
+    }
+}
+
+/// Splits a note into its frontmatter (without fences) and body. Notes without
+/// a leading `---` block return `None` and the whole text as the body.
+pub fn split_frontmatter(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (None, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let body = &rest[offset + line.len()..];
+            return (Some(&rest[..offset]), body);
+        }
+        offset += line.len();
+    }
+
+    (None, text)
+}
+
+/// Splits frontmatter YAML into top-level entries: each key with its raw text,
+/// including any indented continuation lines such as list items.
+pub fn top_level_entries(yaml: &str) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+
+    for line in yaml.split_inclusive('\n') {
+        let is_continuation = line.starts_with([' ', '\t', '-']) || line.trim().is_empty();
+        match (is_continuation, entries.last_mut()) {
+            (true, Some((_, raw))) => raw.push_str(line),
+            _ => {
+                let key = line
+                    .split_once(':')
+                    .map(|(key, _)| key.trim().trim_matches(['"', '\'']).to_string())
+                    .unwrap_or_else(|| line.trim().to_string());
+                entries.push((key, line.to_string()));
+            }
+        }
+    }
+
+    if let Some((_, raw)) = entries.last_mut()
+        && !raw.ends_with('\n')
+    {
+        raw.push('\n');
+    }
+
+    entries
+}
+
+/// Assembles a note from the managed properties, any extra properties declared
+/// in the rendered template's own frontmatter, and the rendered body.
+pub fn compose_note(managed: &Frontmatter, rendered: &str) -> String {
+    let (template_yaml, body) = split_frontmatter(rendered);
+
+    let mut yaml = managed.to_yaml();
+    if let Some(template_yaml) = template_yaml {
+        for (key, raw) in top_level_entries(template_yaml) {
+            if !managed.contains_key(&key) {
+                yaml.push_str(&raw);
+            }
+        }
+    }
+
+    format!(
+        "---\n{yaml}---\n\n{}",
+        body.trim_start_matches(['\n', '\r'])
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ArtistCredit;
+
+    fn release_date(value: &str) -> String {
+        let album = AlbumRecord {
+            release_date: value.to_string(),
+            ..AlbumRecord::default()
+        };
+        Frontmatter::for_album(&album, "", &[], &[])
+            .entries()
+            .find(|(key, _)| *key == "release_date")
+            .map(|(_, raw)| raw)
+            .unwrap_or_default()
+    }
+
+    /// The serialized `artist` and `artists` properties for `credits`, each
+    /// credit paired with its artist note link (empty for no note).
+    fn artist_properties(album_artist: &str, credits: &[(ArtistCredit, &str)]) -> String {
+        let album = AlbumRecord {
+            artist: album_artist.to_string(),
+            ..AlbumRecord::default()
+        };
+        let credits: Vec<CreditContext> = credits
+            .iter()
+            .map(|(credit, link)| CreditContext::new(credit.clone(), link.to_string()))
+            .collect();
+        Frontmatter::for_album(&album, "", &[], &credits)
+            .entries()
+            .filter(|(key, _)| matches!(*key, "artist" | "artists"))
+            .map(|(_, raw)| raw)
+            .collect()
+    }
+
+    fn credit(name: &str, credited_as: &str, join_phrase: &str) -> ArtistCredit {
+        ArtistCredit {
+            artist_id: format!("{name}-id"),
+            name: name.to_string(),
+            credited_as: credited_as.to_string(),
+            join_phrase: join_phrase.to_string(),
+        }
+    }
+
+    #[test]
+    fn a_single_linked_credit_links_the_artist() {
+        assert_eq!(
+            artist_properties(
+                "Portishead",
+                &[(credit("Portishead", "", ""), "[[Portishead]]")]
+            ),
+            "artist: \"[[Portishead]]\"\nartists:\n  - \"[[Portishead]]\"\n"
+        );
+    }
+
+    #[test]
+    fn multiple_credits_keep_the_credit_string_and_list_each_artist() {
+        let properties = artist_properties(
+            "Massive Attack & Mad Professor",
+            &[
+                (credit("Massive Attack", "", " & "), "[[Massive Attack]]"),
+                (credit("Mad Professor", "", ""), ""),
+            ],
+        );
+        assert_eq!(
+            properties,
+            "artist: \"Massive Attack & Mad Professor\"\n\
+             artists:\n  - \"[[Massive Attack]]\"\n  - \"Mad Professor\"\n"
+        );
+    }
+
+    #[test]
+    fn an_aliased_credit_links_to_the_artist_with_the_alias_shown() {
+        assert_eq!(
+            artist_properties(
+                "The Artist",
+                &[(credit("Prince", "The Artist", ""), "[[Prince|The Artist]]")]
+            ),
+            "artist: \"[[Prince|The Artist]]\"\nartists:\n  - \"[[Prince|The Artist]]\"\n"
+        );
+        assert_eq!(
+            artist_properties("The Artist", &[(credit("Prince", "The Artist", ""), "")]),
+            "artist: \"The Artist\"\nartists:\n  - \"Prince\"\n"
+        );
+    }
+
+    #[test]
+    fn only_full_release_dates_are_typed_as_dates() {
+        assert_eq!(release_date("1994-08-22"), "release_date: 1994-08-22\n");
+        assert_eq!(release_date("1994-08"), "release_date: \"1994-08\"\n");
+        assert_eq!(release_date("1994"), "release_date: \"1994\"\n");
+        assert_eq!(release_date(""), "");
+    }
+}
@@ -0,0 +1,1117 @@
+mod frontmatter;
+mod path;
+mod sync;
+mod template;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use similar::TextDiff;
+
+use crate::config::AppConfig;
+use crate::library::LibraryStore;
+use crate::models::{AlbumRecord, ArtistRecord, CoverArtSource, parse_partial_date};
+use crate::vault::LinkStyle;
+
+use frontmatter::Frontmatter;
+use template::{
+    ArtistAlbumContext, ArtistContext, CoverArtContext, CreditContext, GalleryImageContext,
+    NoteContext, TemplateSet,
+};
+
+pub use path::NotePathPattern;
+
+/// What to do with albums whose note file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteMode {
+    /// Only write notes that do not exist yet.
+    Create,
+    /// Also refresh the managed properties and regions of existing notes.
+    Sync,
+}
+
+/// What writing a planned note would do to its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteAction {
+    Create,
+    Update,
+    /// The note exists and already matches the rendered content.
+    Unchanged,
+    Skip,
+    /// The template could not be rendered; `reason` holds the error.
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    Album,
+    Artist,
+}
+
+/// A rendered note for one album or artist, before anything is written.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotePlan {
+    pub kind: NoteKind,
+    /// Release-group MBID for album notes, artist MBID for artist notes.
+    pub mbid: String,
+    pub artist: String,
+    pub title: String,
+    pub template: String,
+    /// Target note file, unknown when the album is skipped before it is resolved.
+    pub path: Option<PathBuf>,
+    pub action: NoteAction,
+    /// Why the note is skipped.
+    pub reason: Option<String>,
+    /// Unified diff from the existing file (or `/dev/null`) to the rendered note.
+    pub diff: String,
+    #[serde(skip)]
+    content: String,
+}
+
+impl NotePlan {
+    fn skip(mut self, reason: impl Into<String>) -> Self {
+        self.action = NoteAction::Skip;
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn fail(mut self, err: anyhow::Error) -> Self {
+        self.action = NoteAction::Failed;
+        self.reason = Some(err.to_string());
+        self
+    }
+
+    /// Fills in the rendered note: merged into `existing` when there is one,
+    /// with a diff against it.
+    fn with_content(
+        mut self,
+        existing: Option<String>,
+        properties: &Frontmatter,
+        rendered: &str,
+    ) -> Self {
+        let content = match &existing {
+            Some(existing) => sync::sync_note(existing, properties, rendered),
+            None => frontmatter::compose_note(properties, rendered),
+        };
+
+        let display_path = self
+            .path
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (old_header, old) = match &existing {
+            Some(existing) => (display_path.as_str(), existing.as_str()),
+            None => ("/dev/null", ""),
+        };
+        self.diff = TextDiff::from_lines(old, &content)
+            .unified_diff()
+            .header(old_header, &display_path)
+            .to_string();
+
+        self.action = match existing {
+            None => NoteAction::Create,
+            Some(existing) if existing == content => NoteAction::Unchanged,
+            Some(_) => NoteAction::Update,
+        };
+        self.content = content;
+        self
+    }
+
+    /// One line describing the planned change.
+    pub fn summary(&self) -> String {
+        let path = self
+            .path
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match self.action {
+            NoteAction::Create => format!("Create: {path}"),
+            NoteAction::Update => format!("Update: {path}"),
+            NoteAction::Unchanged => format!("Up to date: {path}"),
+            NoteAction::Skip | NoteAction::Failed => {
+                let name = match (self.kind, self.artist.is_empty()) {
+                    (_, true) => self.mbid.clone(),
+                    (NoteKind::Album, false) => format!("{} - {}", self.artist, self.title),
+                    (NoteKind::Artist, false) => format!("artist {}", self.artist),
+                };
+                let verb = if self.action == NoteAction::Failed {
+                    "Failed"
+                } else {
+                    "Skipped"
+                };
+                format!(
+                    "{verb} {name}: {}",
+                    self.reason.as_deref().unwrap_or_default()
+                )
+            }
+        }
+    }
+}
+
+/// What every note planned in one [`NoteService::plan_notes`] call shares.
+struct PlanRun<'a> {
+    /// Artists credited on the planned albums, with their note paths.
+    artists: &'a [(ArtistRecord, PathBuf)],
+    templates: &'a TemplateSet,
+    link_style: &'a LinkStyle,
+}
+
+#[derive(Clone)]
+pub struct NoteService {
+    config: AppConfig,
+    library: LibraryStore,
+}
+
+impl NoteService {
+    pub fn new(config: AppConfig, library: LibraryStore) -> Self {
+        Self { config, library }
+    }
+
+    /// The template an album is rendered with: its own override, else the
+    /// first matching `template_rules` entry, else the default template.
+    pub fn template_for<'a>(&'a self, album: &'a AlbumRecord) -> &'a str {
+        album
+            .template
+            .as_deref()
+            .or_else(|| {
+                self.config
+                    .template_rules()
+                    .iter()
+                    .find(|rule| rule.matches(&album.primary_type, &album.secondary_types))
+                    .map(|rule| rule.template.as_str())
+            })
+            .unwrap_or(self.config.default_template())
+    }
+
+    /// Names of the album templates in the templates directory.
+    pub fn available_templates(&self) -> Result<Vec<String>> {
+        Ok(TemplateSet::load(self.config.templates_dir())?
+            .names()
+            .iter()
+            .filter(|name| *name != self.config.artist_template())
+            .cloned()
+            .collect())
+    }
+
+    /// Renders notes for `albums` without writing anything, returning what
+    /// [`write_notes`](Self::write_notes) would do for each one. `template`
+    /// renders every album with that template instead of the one
+    /// [`template_for`](Self::template_for) picks.
+    ///
+    /// Every artist credited on `albums` also gets an artist note listing all
+    /// of the artist's album notes. Artist notes are always synced, so new
+    /// albums show up in them even in [`NoteMode::Create`].
+    pub fn plan_notes(
+        &self,
+        albums: &[AlbumRecord],
+        mode: NoteMode,
+        template: Option<&str>,
+    ) -> Result<Vec<NotePlan>> {
+        // Compile up front so template errors surface before any note is rendered.
+        let templates = TemplateSet::load(self.config.templates_dir())?;
+        if let Some(name) = template
+            && !templates.contains(name)
+        {
+            bail!(
+                "Unknown note template `{name}` (available: {})",
+                templates.names().join(", ")
+            );
+        }
+
+        let album_pattern = NotePathPattern::parse::<AlbumRecord>(
+            "note_path_pattern",
+            self.config.note_path_pattern(),
+        )?;
+        let artist_pattern = NotePathPattern::parse::<ArtistRecord>(
+            "artist_note_path_pattern",
+            self.config.artist_note_path_pattern(),
+        )?;
+
+        let library = self.library.all_albums()?;
+        let mut claimed: HashMap<PathBuf, String> = library
+            .iter()
+            .filter_map(|record| {
+                Some((
+                    PathBuf::from(record.note_path.clone()?),
+                    record.mbid.clone(),
+                ))
+            })
+            .collect();
+
+        // Artist note paths come first so album notes can link to them.
+        let mut artists: Vec<(ArtistRecord, PathBuf)> = Vec::new();
+        for album in albums {
+            if album.title.is_empty() {
+                continue;
+            }
+            for credit in album.artist_credits() {
+                if credit.artist_id.is_empty()
+                    || artists
+                        .iter()
+                        .any(|(artist, _)| artist.id == credit.artist_id)
+                {
+                    continue;
+                }
+                let artist = self
+                    .library
+                    .get_artist(&credit.artist_id)?
+                    .unwrap_or_else(|| ArtistRecord::new(&credit.artist_id, &credit.name, ""));
+                if let Some(path) = &artist.note_path {
+                    claimed.insert(PathBuf::from(path), artist.id.clone());
+                }
+                let path = resolve_note_path(
+                    &self.config,
+                    NoteOwner {
+                        mbid: &artist.id,
+                        disambiguation: &artist.disambiguation,
+                        previous: artist.note_path.as_deref(),
+                    },
+                    |suffix| artist_pattern.render(&artist, suffix),
+                    &mut claimed,
+                )?;
+                artists.push((artist, path));
+            }
+        }
+
+        let album_paths = albums
+            .iter()
+            .map(|album| {
+                resolve_note_path(
+                    &self.config,
+                    NoteOwner {
+                        mbid: &album.mbid,
+                        disambiguation: &album.disambiguation,
+                        previous: album.note_path.as_deref(),
+                    },
+                    |suffix| album_pattern.render(album, suffix),
+                    &mut claimed,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Shortest links depend on which file names are unique in the vault,
+        // counting the notes about to be written.
+        let link_style = self.config.link_style().with_files(
+            album_paths
+                .iter()
+                .chain(artists.iter().map(|(_, path)| path))
+                .cloned(),
+        );
+
+        let run = PlanRun {
+            artists: &artists,
+            templates: &templates,
+            link_style: &link_style,
+        };
+
+        let mut plans = Vec::with_capacity(albums.len() + artists.len());
+        for (album, path) in albums.iter().zip(album_paths) {
+            let template = template.unwrap_or_else(|| self.template_for(album));
+            plans.push(self.plan_album_note(album, path, mode, template, &run)?);
+        }
+
+        for (artist, path) in &artists {
+            plans.push(self.plan_artist_note(artist, path, &library, &plans, &run)?);
+        }
+
+        Ok(plans)
+    }
+
+    fn plan_album_note(
+        &self,
+        album: &AlbumRecord,
+        path: PathBuf,
+        mode: NoteMode,
+        template: &str,
+        run: &PlanRun,
+    ) -> Result<NotePlan> {
+        let PlanRun {
+            artists,
+            templates,
+            link_style,
+        } = run;
+        let mut plan = NotePlan {
+            kind: NoteKind::Album,
+            mbid: album.mbid.clone(),
+            artist: album.artist.clone(),
+            title: album.title.clone(),
+            template: template.to_string(),
+            path: None,
+            action: NoteAction::Skip,
+            reason: None,
+            diff: String::new(),
+            content: String::new(),
+        };
+
+        // Skip if artist or title is empty (metadata not yet fetched)
+        if album.artist.is_empty() || album.title.is_empty() {
+            return Ok(plan.skip("metadata not yet loaded"));
+        }
+
+        plan.path = Some(path.clone());
+
+        let existing = read_existing(&path)?;
+        if existing.is_some() && mode == NoteMode::Create {
+            return Ok(plan.skip("note already exists"));
+        }
+
+        // Wait for cover art path to be set (either downloaded or marked unavailable)
+        let Some(art_path) = &album.cover_art_path else {
+            return Ok(plan.skip("waiting for cover art"));
+        };
+        let art_path = Path::new(art_path);
+        let note_dir = path.parent().unwrap_or(self.config.notes_dir());
+        let cover_art = CoverArtContext {
+            url: album
+                .cover_art_image
+                .as_ref()
+                .map(|image| image.url.clone())
+                .unwrap_or_else(|| album.cover_art_url.clone()),
+            source: album
+                .cover_art_source
+                .as_ref()
+                .map(CoverArtSource::as_str)
+                .unwrap_or_default(),
+            path: link_style.link_path(art_path, note_dir),
+            link: link_style.property_link(art_path, note_dir),
+            embed: link_style.embed(art_path, note_dir),
+        };
+        let gallery: Vec<GalleryImageContext> = album
+            .gallery_images
+            .iter()
+            .map(|image| {
+                let image_path = Path::new(&image.path);
+                GalleryImageContext {
+                    types: image.types.clone(),
+                    comment: image.comment.clone(),
+                    path: link_style.link_path(image_path, note_dir),
+                    link: link_style.property_link(image_path, note_dir),
+                    embed: link_style.embed(image_path, note_dir),
+                }
+            })
+            .collect();
+        let credits: Vec<CreditContext> = album
+            .artist_credits()
+            .into_iter()
+            .map(|credit| {
+                let link = artists
+                    .iter()
+                    .find(|(artist, _)| {
+                        !credit.artist_id.is_empty() && artist.id == credit.artist_id
+                    })
+                    .map(|(artist, artist_note)| {
+                        let alias = credit.credited_as.as_str();
+                        let alias = (!alias.is_empty() && alias != artist.name).then_some(alias);
+                        link_style.note_link(artist_note, note_dir, alias)
+                    })
+                    .unwrap_or_default();
+                CreditContext::new(credit, link)
+            })
+            .collect();
+
+        let properties = Frontmatter::for_album(album, &cover_art.link, &gallery, &credits);
+        let context = NoteContext::new(album, cover_art, gallery, credits);
+        match templates.render(template, &context) {
+            Ok(rendered) => Ok(plan.with_content(existing, &properties, &rendered)),
+            Err(err) => Ok(plan.fail(err)),
+        }
+    }
+
+    /// Plans the note for `artist`, linking every library album of theirs that
+    /// has a note or is about to get one in `plans`.
+    fn plan_artist_note(
+        &self,
+        artist: &ArtistRecord,
+        path: &Path,
+        library: &[AlbumRecord],
+        plans: &[NotePlan],
+        run: &PlanRun,
+    ) -> Result<NotePlan> {
+        let PlanRun {
+            templates,
+            link_style,
+            ..
+        } = run;
+        let template = self.config.artist_template();
+        let plan = NotePlan {
+            kind: NoteKind::Artist,
+            mbid: artist.id.clone(),
+            artist: artist.name.clone(),
+            title: String::new(),
+            template: template.to_string(),
+            path: Some(path.to_path_buf()),
+            action: NoteAction::Skip,
+            reason: None,
+            diff: String::new(),
+            content: String::new(),
+        };
+
+        if !templates.contains(template) {
+            return Ok(plan.skip(format!("artist template `{template}` not found")));
+        }
+
+        let mut albums: Vec<(&AlbumRecord, PathBuf)> = library
+            .iter()
+            .filter(|album| {
+                album
+                    .artist_credits()
+                    .iter()
+                    .any(|credit| credit.artist_id == artist.id)
+            })
+            .filter_map(|album| {
+                let planned = plans
+                    .iter()
+                    .find(|plan| plan.kind == NoteKind::Album && plan.mbid == album.mbid)
+                    .filter(|plan| plan.action != NoteAction::Skip)
+                    .and_then(|plan| plan.path.clone());
+                let path = planned.or_else(|| {
+                    album
+                        .note_path
+                        .as_deref()
+                        .map(PathBuf::from)
+                        .filter(|path| path.exists())
+                })?;
+                Some((album, path))
+            })
+            .collect();
+        albums.sort_by(|(a, _), (b, _)| {
+            let (a_date, b_date) = (
+                parse_partial_date(&a.release_date),
+                parse_partial_date(&b.release_date),
+            );
+            a_date
+                .is_none()
+                .cmp(&b_date.is_none())
+                .then(a_date.cmp(&b_date))
+                .then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+        });
+
+        let note_dir = path.parent().unwrap_or(self.config.notes_dir());
+        let context = ArtistContext {
+            mbid: &artist.id,
+            name: &artist.name,
+            disambiguation: &artist.disambiguation,
+            albums: albums
+                .iter()
+                .map(|(album, album_path)| ArtistAlbumContext {
+                    mbid: &album.mbid,
+                    title: &album.title,
+                    primary_type: &album.primary_type,
+                    release_date: &album.release_date,
+                    year: album.release_date.get(..4).unwrap_or_default(),
+                    link: link_style.note_link(album_path, note_dir, Some(&album.title)),
+                })
+                .collect(),
+        };
+        let properties = Frontmatter::for_artist(
+            artist,
+            albums
+                .iter()
+                .map(|(_, album_path)| link_style.note_link(album_path, note_dir, None))
+                .collect(),
+        );
+
+        let existing = read_existing(path)?;
+        match templates.render(template, &context) {
+            Ok(rendered) => Ok(plan.with_content(existing, &properties, &rendered)),
+            Err(err) => Ok(plan.fail(err)),
+        }
+    }
+
+    /// Writes the notes in `plans` that would be created or updated and
+    /// returns a log line for every plan.
+    pub fn write_notes(&self, plans: &[NotePlan]) -> Result<Vec<String>> {
+        let mut logs = Vec::new();
+
+        for plan in plans {
+            let Some(path) = plan
+                .path
+                .as_ref()
+                .filter(|_| matches!(plan.action, NoteAction::Create | NoteAction::Update))
+            else {
+                logs.push(plan.summary());
+                continue;
+            };
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).with_context(|| {
+                    format!("Unable to create note folder {}", parent.display())
+                })?;
+            }
+            fs::write(path, &plan.content)
+                .with_context(|| format!("Unable to write note {}", path.display()))?;
+
+            let note_path = path.to_string_lossy().to_string();
+            match plan.kind {
+                NoteKind::Album => self.library.mark_note_generated(&plan.mbid, note_path)?,
+                NoteKind::Artist => self
+                    .library
+                    .mark_artist_note_generated(&plan.mbid, note_path)?,
+            }
+
+            let verb = if plan.action == NoteAction::Update {
+                "Updated"
+            } else {
+                "Generated"
+            };
+            logs.push(format!("{verb} note: {}", path.to_string_lossy()));
+        }
+
+        Ok(logs)
+    }
+}
+
+/// Highest counter tried when every other note path suffix is taken.
+const MAX_NOTE_SUFFIX: usize = 100;
+
+/// Identifies the album or artist a note belongs to when resolving its path.
+struct NoteOwner<'a> {
+    mbid: &'a str,
+    disambiguation: &'a str,
+    /// Where the note was written before, if anywhere.
+    previous: Option<&'a str>,
+}
+
+/// Picks the note file for `owner`: the file it was written to before, or the
+/// pattern's path from `render`. A path that belongs to another album or
+/// artist, in the library or by the `musicbrainz_id` of an existing file, gets
+/// a suffix: the owner's disambiguation first, then the start of its MBID,
+/// then a counter up to [`MAX_NOTE_SUFFIX`].
+fn resolve_note_path(
+    config: &AppConfig,
+    owner: NoteOwner,
+    render: impl Fn(&str) -> PathBuf,
+    claimed: &mut HashMap<PathBuf, String>,
+) -> Result<PathBuf> {
+    if let Some(previous) = owner.previous.map(PathBuf::from)
+        && previous.exists()
+    {
+        claimed.insert(previous.clone(), owner.mbid.to_string());
+        return Ok(previous);
+    }
+
+    let short_id = owner.mbid.get(..8).unwrap_or(owner.mbid);
+    let mut suffixes = vec![String::new()];
+    if !owner.disambiguation.trim().is_empty() {
+        suffixes.push(format!(" ({})", owner.disambiguation.trim()));
+    }
+    suffixes.push(format!(" [{short_id}]"));
+    let numbered = (2..=MAX_NOTE_SUFFIX).map(|n| format!(" [{short_id}] ({n})"));
+
+    for suffix in suffixes.into_iter().chain(numbered) {
+        let path = config.notes_dir().join(render(&suffix));
+        let current = claimed.get(&path).cloned().or_else(|| note_owner(&path));
+        if current.is_none_or(|current| current == owner.mbid) {
+            claimed.insert(path.clone(), owner.mbid.to_string());
+            return Ok(path);
+        }
+    }
+    bail!(
+        "No free note path for {}: {} is taken by other notes",
+        owner.mbid,
+        config.notes_dir().join(render("")).display()
+    )
+}
+
+fn read_existing(path: &Path) -> Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    fs::read_to_string(path)
+        .map(Some)
+        .with_context(|| format!("Unable to read existing note {}", path.display()))
+}
+
+/// The `musicbrainz_id` recorded in the note at `path`, or an empty string for
+/// a note without one. `None` when there is no file.
+fn note_owner(path: &Path) -> Option<String> {
+    if !path.exists() {
+        return None;
+    }
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let (yaml, _) = frontmatter::split_frontmatter(&text);
+    let owner = frontmatter::top_level_entries(yaml.unwrap_or_default())
+        .into_iter()
+        .find(|(key, _)| key == "musicbrainz_id")
+        .and_then(|(_, raw)| {
+            raw.split_once(':')
+                .map(|(_, value)| value.trim().trim_matches(['"', '\'']).to_string())
+        });
+    Some(owner.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::config::{ConfigLayer, TemplateRule};
+    use crate::models::CoverArtImage;
+
+    /// A note service writing into an Obsidian vault with default settings,
+    /// so links use the shortest path.
+    fn vault_service(dir: &Path) -> NoteService {
+        let config = AppConfig::in_dir(dir);
+        let library = LibraryStore::open(&config).unwrap();
+        NoteService::new(config, library)
+    }
+
+    fn rule(
+        primary_type: Option<&str>,
+        secondary_type: Option<&str>,
+        template: &str,
+    ) -> TemplateRule {
+        TemplateRule {
+            primary_type: primary_type.map(str::to_string),
+            secondary_type: secondary_type.map(str::to_string),
+            template: template.to_string(),
+        }
+    }
+
+    /// A note service whose config has `rules` as its `template_rules`.
+    fn service_with_rules(dir: &Path, rules: Vec<TemplateRule>) -> NoteService {
+        let config = AppConfig::in_dir_with(
+            dir,
+            ConfigLayer {
+                template_rules: Some(rules),
+                ..ConfigLayer::default()
+            },
+        );
+        let library = LibraryStore::open(&config).unwrap();
+        NoteService::new(config, library)
+    }
+
+    fn typed_album(primary_type: &str, secondary_types: &[&str]) -> AlbumRecord {
+        AlbumRecord {
+            primary_type: primary_type.to_string(),
+            secondary_types: secondary_types.iter().map(|t| t.to_string()).collect(),
+            ..AlbumRecord::default()
+        }
+    }
+
+    #[test]
+    fn an_album_override_beats_rules_and_rules_beat_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = service_with_rules(dir.path(), vec![rule(Some("EP"), None, "ep")]);
+
+        let mut ep = typed_album("EP", &[]);
+        assert_eq!(service.template_for(&ep), "ep");
+
+        ep.template = Some(String::from("favourite"));
+        assert_eq!(service.template_for(&ep), "favourite");
+
+        let album = typed_album("Album", &[]);
+        assert_eq!(service.template_for(&album), "note_template");
+    }
+
+    #[test]
+    fn the_first_matching_rule_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = service_with_rules(
+            dir.path(),
+            vec![
+                rule(Some("Album"), Some("Live"), "live_album"),
+                rule(None, Some("Live"), "live"),
+                rule(Some("Album"), None, "album"),
+            ],
+        );
+
+        assert_eq!(
+            service.template_for(&typed_album("Album", &["Live"])),
+            "live_album"
+        );
+        assert_eq!(service.template_for(&typed_album("EP", &["Live"])), "live");
+        assert_eq!(
+            service.template_for(&typed_album("Album", &["Compilation"])),
+            "album"
+        );
+    }
+
+    #[test]
+    fn rules_match_release_types_case_insensitively() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = service_with_rules(
+            dir.path(),
+            vec![
+                rule(Some("ep"), None, "ep"),
+                rule(None, Some("LIVE"), "live"),
+            ],
+        );
+
+        assert_eq!(service.template_for(&typed_album("EP", &[])), "ep");
+        assert_eq!(
+            service.template_for(&typed_album("Album", &["Compilation", "Live"])),
+            "live"
+        );
+        assert_eq!(
+            service.template_for(&typed_album("Single", &[])),
+            "note_template"
+        );
+    }
+
+    /// An album with its cover and a front and back gallery image on disk.
+    fn album_with_gallery(service: &NoteService, mbid: &str, title: &str) -> AlbumRecord {
+        let mut album = AlbumRecord {
+            mbid: mbid.to_string(),
+            title: title.to_string(),
+            artist: String::from("Portishead"),
+            ..AlbumRecord::default()
+        };
+        let art_dir = service.config.album_art_dir();
+        let cover = art_dir.join(album.cover_art_filename("jpg"));
+        fs::write(&cover, "").unwrap();
+        album.cover_art_path = Some(cover.to_string_lossy().into_owned());
+
+        let gallery_dir = art_dir.join(album.gallery_dirname());
+        fs::create_dir_all(&gallery_dir).unwrap();
+        for (position, types) in [(1, "Front"), (2, "Back")] {
+            let types = vec![types.to_string()];
+            let path = gallery_dir.join(album.gallery_filename(position, &types, "jpg"));
+            fs::write(&path, "").unwrap();
+            album.gallery_images.push(CoverArtImage {
+                types,
+                path: path.to_string_lossy().into_owned(),
+                ..CoverArtImage::default()
+            });
+        }
+        album.gallery = true;
+        album
+    }
+
+    #[test]
+    fn gallery_links_differ_between_albums() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let albums = [
+            album_with_gallery(&service, "76df3287-6cda-33eb-8e9a-044b5e15ffdd", "Dummy"),
+            album_with_gallery(
+                &service,
+                "48140466-cff6-3222-bd55-63c27e43190d",
+                "Portishead",
+            ),
+        ];
+
+        let plans = service.plan_notes(&albums, NoteMode::Create, None).unwrap();
+        let gallery_links = |plan: &NotePlan| {
+            plan.content
+                .lines()
+                .filter(|line| line.contains("Front.jpg") || line.contains("Back.jpg"))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let first = gallery_links(&plans[0]);
+        let second = gallery_links(&plans[1]);
+        assert!(!first.is_empty());
+        assert!(first.iter().all(|line| line.contains("76df3287")));
+        assert!(second.iter().all(|line| line.contains("48140466")));
+        assert!(first.iter().all(|line| !second.contains(line)));
+        assert!(
+            plans[0]
+                .content
+                .contains("[[76df3287-6cda-33eb-8e9a-044b5e15ffdd 01 Front.jpg]]")
+        );
+    }
+
+    fn album(mbid: &str, disambiguation: &str) -> AlbumRecord {
+        AlbumRecord {
+            mbid: mbid.to_string(),
+            title: String::from("Blue"),
+            artist: String::from("Joni Mitchell"),
+            disambiguation: disambiguation.to_string(),
+            ..AlbumRecord::default()
+        }
+    }
+
+    /// File names of the album notes planned for `albums`.
+    fn note_names(service: &NoteService, albums: &[AlbumRecord]) -> Vec<String> {
+        service
+            .plan_notes(albums, NoteMode::Create, None)
+            .unwrap()
+            .into_iter()
+            .filter(|plan| plan.kind == NoteKind::Album)
+            .map(|plan| {
+                let path = plan.path.unwrap();
+                path.file_name().unwrap().to_string_lossy().into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_title_albums_are_told_apart_by_disambiguation() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let albums = [
+            album("76df3287-6cda-33eb-8e9a-044b5e15ffdd", ""),
+            album("48140466-cff6-3222-bd55-63c27e43190d", "deluxe"),
+        ];
+
+        assert_eq!(
+            note_names(&service, &albums),
+            [
+                "Joni Mitchell - Blue.md",
+                "Joni Mitchell - Blue (deluxe).md"
+            ]
+        );
+    }
+
+    #[test]
+    fn same_disambiguation_falls_back_to_the_mbid() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let albums = [
+            album("76df3287-6cda-33eb-8e9a-044b5e15ffdd", "remaster"),
+            album("48140466-cff6-3222-bd55-63c27e43190d", "remaster"),
+            album("0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b", "remaster"),
+        ];
+
+        assert_eq!(
+            note_names(&service, &albums),
+            [
+                "Joni Mitchell - Blue.md",
+                "Joni Mitchell - Blue (remaster).md",
+                "Joni Mitchell - Blue [0f1f2c4e].md",
+            ]
+        );
+    }
+
+    #[test]
+    fn existing_notes_of_other_albums_are_not_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let notes_dir = service.config.notes_dir();
+        fs::create_dir_all(notes_dir).unwrap();
+        fs::write(
+            notes_dir.join("Joni Mitchell - Blue.md"),
+            "---\nmusicbrainz_id: \"48140466-cff6-3222-bd55-63c27e43190d\"\n---\n",
+        )
+        .unwrap();
+
+        let other = album("76df3287-6cda-33eb-8e9a-044b5e15ffdd", "");
+        assert_eq!(
+            note_names(&service, &[other]),
+            ["Joni Mitchell - Blue [76df3287].md"]
+        );
+        let owner = album("48140466-cff6-3222-bd55-63c27e43190d", "");
+        assert_eq!(note_names(&service, &[owner]), ["Joni Mitchell - Blue.md"]);
+    }
+
+    #[test]
+    fn running_out_of_note_paths_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let notes_dir = service.config.notes_dir();
+        fs::create_dir_all(notes_dir).unwrap();
+        fs::write(notes_dir.join("Blue.md"), "Someone else's note\n").unwrap();
+
+        let resolved = resolve_note_path(
+            &service.config,
+            NoteOwner {
+                mbid: "76df3287-6cda-33eb-8e9a-044b5e15ffdd",
+                disambiguation: "",
+                previous: None,
+            },
+            |_| PathBuf::from("Blue.md"),
+            &mut HashMap::new(),
+        );
+
+        assert!(resolved.is_err());
+    }
+
+    /// Every file under `dir` with its contents.
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    files.insert(path.clone(), fs::read(&path).unwrap());
+                }
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn planning_reports_actions_and_diffs_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let mut changed =
+            album_with_gallery(&service, "76df3287-6cda-33eb-8e9a-044b5e15ffdd", "Dummy");
+        let mut unchanged = album_with_gallery(
+            &service,
+            "48140466-cff6-3222-bd55-63c27e43190d",
+            "Portishead",
+        );
+        let written = service
+            .plan_notes(
+                &[changed.clone(), unchanged.clone()],
+                NoteMode::Create,
+                None,
+            )
+            .unwrap();
+        service.write_notes(&written).unwrap();
+        changed.note_path = written[0]
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string());
+        unchanged.note_path = written[1]
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string());
+        changed.label = String::from("Go! Beat");
+
+        let new = album_with_gallery(&service, "0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b", "Third");
+        let waiting = AlbumRecord {
+            mbid: String::from("5b6c7d8e-1111-4222-8333-944455566677"),
+            title: String::from("Roseland NYC Live"),
+            artist: String::from("Portishead"),
+            ..AlbumRecord::default()
+        };
+        let albums = [changed.clone(), unchanged, new, waiting];
+        let vault = dir.path().join("vault");
+        let before = snapshot(&vault);
+
+        let plans = service.plan_notes(&albums, NoteMode::Sync, None).unwrap();
+        let actions: Vec<_> = plans.iter().map(|plan| plan.action).collect();
+        assert_eq!(
+            actions,
+            [
+                NoteAction::Update,
+                NoteAction::Unchanged,
+                NoteAction::Create,
+                NoteAction::Skip
+            ]
+        );
+
+        let changed_path = changed.note_path.unwrap();
+        let update = &plans[0].diff;
+        assert!(update.starts_with(&format!("--- {changed_path}\n+++ {changed_path}\n@@ ")));
+        assert!(update.contains("\n+label: \"Go! Beat\"\n"), "{update}");
+        assert_eq!(
+            update.lines().filter(|line| line.starts_with('+')).count(),
+            2
+        );
+        assert_eq!(
+            update.lines().filter(|line| line.starts_with('-')).count(),
+            1
+        );
+        assert!(plans[1].diff.is_empty());
+        let create = &plans[2].diff;
+        assert!(create.starts_with("--- /dev/null\n+++ "), "{create}");
+        assert!(create.lines().skip(3).all(|line| line.starts_with('+')));
+        assert_eq!(plans[3].reason.as_deref(), Some("waiting for cover art"));
+
+        let created = service
+            .plan_notes(&albums[..1], NoteMode::Create, None)
+            .unwrap();
+        assert_eq!(created[0].action, NoteAction::Skip);
+        assert_eq!(created[0].reason.as_deref(), Some("note already exists"));
+
+        assert_eq!(snapshot(&vault), before, "planning wrote to the vault");
+    }
+
+    #[test]
+    fn artist_notes_list_albums_by_release_date_and_albums_link_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        let artist_id = "8f6bd1e4-fbe1-4f50-aa9b-94c450ec0f11";
+        let by_portishead = |mbid: &str, title: &str, release_date: &str| {
+            let mut album = album_with_gallery(&service, mbid, title);
+            album.artist_id = artist_id.to_string();
+            album.release_date = release_date.to_string();
+            service.library.upsert_album(album.clone()).unwrap();
+            album
+        };
+        let third = by_portishead(
+            "0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b",
+            "Third",
+            "2008-04-28",
+        );
+        let dummy = by_portishead(
+            "76df3287-6cda-33eb-8e9a-044b5e15ffdd",
+            "Dummy",
+            "1994-08-22",
+        );
+        service
+            .library
+            .upsert_artist(ArtistRecord::new(artist_id, "Portishead", ""))
+            .unwrap();
+
+        let plans = service
+            .plan_notes(&[third, dummy], NoteMode::Create, None)
+            .unwrap();
+
+        let artist = plans
+            .iter()
+            .find(|plan| plan.kind == NoteKind::Artist)
+            .unwrap();
+        assert_eq!(
+            artist.path.as_deref(),
+            Some(dir.path().join("vault/Portishead.md").as_path())
+        );
+        assert!(
+            artist.content.contains(
+                "albums:\n  - \"[[Portishead - Dummy]]\"\n  - \"[[Portishead - Third]]\"\n"
+            ),
+            "{}",
+            artist.content
+        );
+
+        let albums: Vec<_> = plans
+            .iter()
+            .filter(|plan| plan.kind == NoteKind::Album)
+            .collect();
+        assert_eq!(albums.len(), 2);
+        for album in albums {
+            assert_eq!(album.action, NoteAction::Create);
+            assert!(
+                album.content.contains("artist: \"[[Portishead]]\"\n"),
+                "{}",
+                album.content
+            );
+        }
+    }
+
+    #[test]
+    fn render_errors_fail_the_note_without_stopping_the_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = vault_service(dir.path());
+        fs::write(
+            dir.path().join("templates/broken.md"),
+            "# {{ title }}\n\n{{ missing.field }}\n",
+        )
+        .unwrap();
+        let mut broken =
+            album_with_gallery(&service, "76df3287-6cda-33eb-8e9a-044b5e15ffdd", "Dummy");
+        broken.template = Some(String::from("broken"));
+        let working = album_with_gallery(&service, "0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b", "Third");
+
+        let plans = service
+            .plan_notes(&[broken, working], NoteMode::Create, None)
+            .unwrap();
+
+        assert_eq!(plans[0].action, NoteAction::Failed);
+        assert!(
+            plans[0]
+                .summary()
+                .starts_with("Failed Portishead - Dummy: Template error in `broken` on line 3:"),
+            "{}",
+            plans[0].summary()
+        );
+        assert_eq!(plans[1].action, NoteAction::Create);
+
+        let logs = service.write_notes(&plans).unwrap();
+        assert_eq!(logs[0], plans[0].summary());
+        assert!(!plans[0].path.as_ref().unwrap().exists());
+        assert!(plans[1].path.as_ref().unwrap().exists());
+    }
+}
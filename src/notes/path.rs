@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+
+use crate::models::{AlbumRecord, ArtistRecord};
+
+/// Values a note path pattern can be filled in from.
+pub trait PathFields {
+    /// Placeholder names the pattern may use, without braces.
+    const PLACEHOLDERS: &'static [&'static str];
+
+    fn field(&self, name: &str) -> String;
+}
+
+impl PathFields for AlbumRecord {
+    const PLACEHOLDERS: &'static [&'static str] = &[
+        "artist",
+        "title",
+        "year",
+        "release_date",
+        "primary_type",
+        "secondary_types",
+        "status",
+        "label",
+        "country",
+        "disambiguation",
+        "mbid",
+        "release_id",
+    ];
+
+    fn field(&self, name: &str) -> String {
+        match name {
+            "artist" => self.artist.clone(),
+            "title" => self.title.clone(),
+            "year" => self.release_date.get(..4).unwrap_or_default().to_string(),
+            "release_date" => self.release_date.clone(),
+            "primary_type" => self.primary_type.clone(),
+            "secondary_types" => self.secondary_types.join(", "),
+            "status" => self.status.clone(),
+            "label" => self.label.clone(),
+            "country" => self.country.clone(),
+            "disambiguation" => self.disambiguation.clone(),
+            "mbid" => self.mbid.clone(),
+            "release_id" => self.release_id.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl PathFields for ArtistRecord {
+    const PLACEHOLDERS: &'static [&'static str] = &["artist", "disambiguation", "mbid"];
+
+    fn field(&self, name: &str) -> String {
+        match name {
+            "artist" => self.name.clone(),
+            "disambiguation" => self.disambiguation.clone(),
+            "mbid" => self.id.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Where notes are written relative to `notes_dir`, e.g. `{artist}/{year} - {title}.md`.
+///
+/// `/` separates folders. Each segment is filled in and sanitized on its own,
+/// so a `/` inside an album or artist value can never add a folder or leave `notes_dir`.
+#[derive(Debug, Clone)]
+pub struct NotePathPattern {
+    segments: Vec<String>,
+}
+
+impl NotePathPattern {
+    /// Parses `pattern` for notes about `T`. `setting` names the option in errors.
+    pub fn parse<T: PathFields>(setting: &str, pattern: &str) -> Result<Self> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            bail!("{setting} must not be empty");
+        }
+        if pattern.starts_with('/') || pattern.contains('\\') {
+            bail!("{setting} `{pattern}` must be a `/`-separated path relative to notes_dir");
+        }
+
+        let segments: Vec<String> = pattern.split('/').map(|s| s.trim().to_string()).collect();
+        for segment in &segments {
+            if segment.is_empty() || segment == "." || segment == ".." {
+                bail!("{setting} `{pattern}` contains an empty, `.` or `..` folder");
+            }
+            for name in placeholders(setting, segment)? {
+                if !T::PLACEHOLDERS.contains(&name) {
+                    bail!(
+                        "{setting} `{pattern}` uses unknown placeholder `{{{name}}}` (available: {})",
+                        T::PLACEHOLDERS.join(", ")
+                    );
+                }
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// The note path for `fields`, relative to `notes_dir`. `suffix` is added to
+    /// the file name, before `.md`, to tell apart notes that share a path.
+    pub fn render(&self, fields: &impl PathFields, suffix: &str) -> PathBuf {
+        let last = self.segments.len() - 1;
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(idx, segment)| {
+                let value = fill(segment, fields);
+                if idx == last {
+                    let stem = value.strip_suffix(".md").unwrap_or(&value).trim_end();
+                    sanitize_segment(&format!("{stem}{suffix}.md"))
+                } else {
+                    sanitize_segment(&value)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Placeholder names in `segment`, in order.
+fn placeholders<'a>(setting: &str, segment: &'a str) -> Result<Vec<&'a str>> {
+    let mut names = Vec::new();
+    let mut rest = segment;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start + 1..].find('}') else {
+            bail!("{setting} segment `{segment}` has an unclosed `{{`");
+        };
+        names.push(&rest[start + 1..start + 1 + len]);
+        rest = &rest[start + len + 2..];
+    }
+    Ok(names)
+}
+
+fn fill(segment: &str, fields: &impl PathFields) -> String {
+    let mut filled = String::with_capacity(segment.len());
+    let mut rest = segment;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let len = rest[start + 1..].find('}').unwrap_or_default();
+        filled.push_str(&fields.field(&rest[start + 1..start + 1 + len]));
+        rest = &rest[start + len + 2..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Sanitizes one path segment. Segments left empty by missing values become
+/// `Unknown` rather than collapsing the folder structure.
+fn sanitize_segment(value: &str) -> String {
+    let sanitized = sanitize_filename::sanitize(value.trim());
+    let sanitized = sanitized.trim();
+    if sanitized.is_empty() || sanitized == ".md" {
+        format!("Unknown{}", if value.ends_with(".md") { ".md" } else { "" })
+    } else {
+        sanitized.to_string()
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+
+use super::frontmatter::{self, Frontmatter};
+
+const REGION_START: &str = "<!-- album:start ";
+const REGION_END: &str = "<!-- album:end ";
+const MARKER_CLOSE: &str = " -->";
+
+/// Re-applies a freshly rendered note to an existing one, touching only what
+/// the tool owns:
+///
+/// - managed frontmatter keys are replaced in place (or removed when the album
+///   no longer has a value), other properties are kept as written;
+/// - properties declared in the template's frontmatter are added if missing;
+/// - body regions fenced with `<!-- album:start NAME -->` / `<!-- album:end NAME -->`
+///   are replaced with the newly rendered region of the same name.
+///
+/// Everything else in the existing note, such as the `## Notes` section, is left untouched.
+pub fn sync_note(existing: &str, managed: &Frontmatter, rendered: &str) -> String {
+    let (existing_yaml, existing_body) = frontmatter::split_frontmatter(existing);
+    let (template_yaml, rendered_body) = frontmatter::split_frontmatter(rendered);
+
+    let managed_entries: Vec<(&str, String)> = managed.entries().collect();
+    let mut written: HashSet<String> = HashSet::new();
+    let mut yaml = String::new();
+
+    for (key, raw) in frontmatter::top_level_entries(existing_yaml.unwrap_or_default()) {
+        if let Some((_, fresh)) = managed_entries.iter().find(|(managed, _)| *managed == key) {
+            yaml.push_str(fresh);
+        } else if !managed.managed_keys().contains(&key.as_str()) {
+            yaml.push_str(&raw);
+        }
+        written.insert(key);
+    }
+
+    for (key, fresh) in &managed_entries {
+        if written.insert(key.to_string()) {
+            yaml.push_str(fresh);
+        }
+    }
+
+    if let Some(template_yaml) = template_yaml {
+        for (key, raw) in frontmatter::top_level_entries(template_yaml) {
+            if written.insert(key) {
+                yaml.push_str(&raw);
+            }
+        }
+    }
+
+    let mut body = existing_body.to_string();
+    for (name, content) in regions(rendered_body) {
+        body = replace_region(&body, &name, content);
+    }
+
+    if existing_yaml.is_some() {
+        format!("---\n{yaml}---\n{body}")
+    } else {
+        format!("---\n{yaml}---\n\n{body}")
+    }
+}
+
+/// Named managed regions in `text`, with the content between their markers.
+fn regions(text: &str) -> Vec<(String, &str)> {
+    let mut found = Vec::new();
+    let mut rest = text;
+
+    while let Some((name, content_start)) = next_start_marker(rest) {
+        let end_marker = format!("{REGION_END}{name}{MARKER_CLOSE}");
+        let Some(content_len) = rest[content_start..].find(&end_marker) else {
+            break;
+        };
+        found.push((
+            name.to_string(),
+            &rest[content_start..content_start + content_len],
+        ));
+        rest = &rest[content_start + content_len + end_marker.len()..];
+    }
+
+    found
+}
+
+/// Finds the next start marker, returning its region name and the offset just past it.
+fn next_start_marker(text: &str) -> Option<(&str, usize)> {
+    let start = text.find(REGION_START)?;
+    let name_start = start + REGION_START.len();
+    let name_len = text[name_start..].find(MARKER_CLOSE)?;
+    let name = text[name_start..name_start + name_len].trim();
+    Some((name, name_start + name_len + MARKER_CLOSE.len()))
+}
+
+/// Replaces the content of region `name` in `body`. Bodies where the user has
+/// removed the region are returned unchanged.
+fn replace_region(body: &str, name: &str, content: &str) -> String {
+    let start_marker = format!("{REGION_START}{name}{MARKER_CLOSE}");
+    let end_marker = format!("{REGION_END}{name}{MARKER_CLOSE}");
+
+    let Some(start) = body.find(&start_marker) else {
+        return body.to_string();
+    };
+    let content_start = start + start_marker.len();
+    let Some(content_len) = body[content_start..].find(&end_marker) else {
+        return body.to_string();
+    };
+
+    format!(
+        "{}{content}{}",
+        &body[..content_start],
+        &body[content_start + content_len..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AlbumRecord;
+
+    fn managed(title: &str) -> Frontmatter {
+        let album = AlbumRecord {
+            title: title.to_string(),
+            artist: String::from("Portishead"),
+            release_date: String::from("1994-08-22"),
+            ..AlbumRecord::default()
+        };
+        Frontmatter::for_album(&album, "", &[], &[])
+    }
+
+    fn rendered(tracks: &str) -> String {
+        format!(
+            "---\nrating:\n---\n# Dummy\n\n<!-- album:start tracks -->\n{tracks}\n<!-- album:end tracks -->\n\n## Notes\n"
+        )
+    }
+
+    const USER_TEXT: &str =
+        "\n## Notes\n\nBristol, 1994.  Trailing spaces  \n\n\tIndented\r\n* a list\n";
+
+    fn existing() -> String {
+        format!(
+            "---\nrating: 5\ntitle: \"Old\"\ntags:\n  - trip-hop\nyear: 1990\nlabel: \"Go! Beat\"\nmood: dark\n---\n# Dummy\n\nMy intro.\n<!-- album:start tracks -->\n1. Old track\n<!-- album:end tracks -->\n{USER_TEXT}"
+        )
+    }
+
+    #[test]
+    fn text_outside_regions_is_kept_byte_for_byte() {
+        let synced = sync_note(&existing(), &managed("Dummy"), &rendered("1. Mysterons"));
+
+        let (_, body) = frontmatter::split_frontmatter(&synced);
+        assert_eq!(
+            body,
+            format!(
+                "# Dummy\n\nMy intro.\n<!-- album:start tracks -->\n1. Mysterons\n<!-- album:end tracks -->\n{USER_TEXT}"
+            )
+        );
+    }
+
+    #[test]
+    fn unmanaged_keys_keep_their_values_and_order() {
+        let synced = sync_note(&existing(), &managed("Dummy"), &rendered("1. Mysterons"));
+
+        let (yaml, _) = frontmatter::split_frontmatter(&synced);
+        let keys: Vec<String> = frontmatter::top_level_entries(yaml.unwrap())
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "rating",
+                "title",
+                "tags",
+                "year",
+                "mood",
+                "artist",
+                "release_date",
+                "secondary_types",
+            ]
+        );
+        let yaml = yaml.unwrap();
+        assert!(yaml.starts_with(
+            "rating: 5\ntitle: \"Dummy\"\ntags:\n  - trip-hop\nyear: 1994\nmood: dark\n"
+        ));
+        assert!(
+            !yaml.contains("label"),
+            "managed key without a value is removed"
+        );
+    }
+
+    #[test]
+    fn missing_or_unbalanced_markers_keep_content() {
+        let without_end = "---\ntitle: \"Dummy\"\n---\n<!-- album:start tracks -->\n1. Mine\n\n## Notes\nKeep me\n";
+        let without_start =
+            "---\ntitle: \"Dummy\"\n---\n1. Mine\n<!-- album:end tracks -->\n## Notes\nKeep me\n";
+        let without_region = "---\ntitle: \"Dummy\"\n---\n## Notes\nKeep me\n";
+
+        for existing in [without_end, without_start, without_region] {
+            let synced = sync_note(existing, &managed("Dummy"), &rendered("1. Mysterons"));
+            let (_, before) = frontmatter::split_frontmatter(existing);
+            let (_, after) = frontmatter::split_frontmatter(&synced);
+            assert_eq!(after, before);
+        }
+
+        let unbalanced_template = "# Dummy\n<!-- album:start tracks -->\n1. Mysterons\n";
+        let synced = sync_note(&existing(), &managed("Dummy"), unbalanced_template);
+        assert!(synced.contains("1. Old track\n<!-- album:end tracks -->"));
+        assert!(synced.ends_with(USER_TEXT));
+    }
+
+    #[test]
+    fn syncing_twice_changes_nothing() {
+        for existing in [existing(), String::from("# Dummy\n\nNo frontmatter yet.\n")] {
+            let once = sync_note(&existing, &managed("Dummy"), &rendered("1. Mysterons"));
+            let twice = sync_note(&once, &managed("Dummy"), &rendered("1. Mysterons"));
+            assert_eq!(twice, once);
+        }
+    }
+}
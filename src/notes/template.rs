@@ -0,0 +1,581 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use minijinja::{AutoEscape, Environment, Error, ErrorKind, UndefinedBehavior, Value};
+use serde::Serialize;
+
+use crate::models::{AlbumRecord, ArtistCredit, parse_full_date, parse_partial_date};
+
+/// The note templates in the templates directory, compiled once.
+///
+/// Each `<name>.md` file becomes a template called `<name>`. Templates use
+/// Jinja syntax (`{{ title }}`, `{% if label %}`, `{% for track in tracks %}`)
+/// and are rendered against a [`NoteContext`] (album notes) or an
+/// [`ArtistContext`] (artist notes); they may also `{% include %}`
+/// or `{% extends %}` one another by name. Besides the MiniJinja builtins
+/// (`default`, `join`, `length`, `upper`, ...) these filters are available:
+///
+/// - `yaml`: quotes a value so it is safe as a YAML scalar, e.g. `title: {{ title | yaml }}`
+/// - `date(format)`: formats a MusicBrainz date (`YYYY`, `YYYY-MM` or `YYYY-MM-DD`) with
+///   a strftime pattern, e.g. `{{ release_date | date("%B %Y") }}`
+/// - `duration`: formats milliseconds as `m:ss`, e.g. `{{ track.length_ms | duration }}`
+/// - `wikilink`: wraps a path in `[[...]]`
+pub struct TemplateSet {
+    env: Environment<'static>,
+    names: Vec<String>,
+}
+
+impl TemplateSet {
+    /// Reads and compiles every template in `dir`. Syntax errors are reported
+    /// with the file name and line number.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("Unable to read templates directory {}", dir.display()))?;
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let is_template = path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+            if let (true, Some(stem)) = (is_template, path.file_stem()) {
+                files.push((stem.to_string_lossy().into_owned(), path));
+            }
+        }
+        files.sort();
+
+        let mut env = new_environment();
+        let mut names = Vec::with_capacity(files.len());
+        for (name, path) in files {
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Unable to read note template at {}", path.display()))?;
+            let source = upgrade_legacy_syntax(&source).unwrap_or(source);
+            env.add_template_owned(name.clone(), source)
+                .map_err(|err| {
+                    anyhow!(
+                        "Invalid note template {}:{}: {}",
+                        path.display(),
+                        err.line().unwrap_or_default(),
+                        describe(&err)
+                    )
+                })?;
+            names.push(name);
+        }
+
+        Ok(Self { env, names })
+    }
+
+    /// Template names, sorted.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|existing| existing == name)
+    }
+
+    pub fn render(&self, name: &str, context: &impl Serialize) -> Result<String> {
+        let template = self
+            .env
+            .get_template(name)
+            .map_err(|_| anyhow!("Unknown note template `{name}`"))?;
+        template.render(context).map_err(|err| {
+            anyhow!(
+                "Template error in `{name}` on line {}: {}",
+                err.line().unwrap_or_default(),
+                describe(&err)
+            )
+        })
+    }
+}
+
+fn new_environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    env.set_keep_trailing_newline(true);
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.add_filter("yaml", yaml_filter);
+    env.add_filter("date", date_filter);
+    env.add_filter("duration", duration_filter);
+    env.add_filter("wikilink", wikilink_filter);
+    env
+}
+
+/// Everything a note template can reference.
+#[derive(Debug, Serialize)]
+pub struct NoteContext<'a> {
+    /// Release-group MBID.
+    pub mbid: &'a str,
+    /// Same as `mbid`, for templates that want to be explicit.
+    pub release_group_id: &'a str,
+    /// MBID of the release (edition) the metadata was taken from, or empty.
+    pub release_id: &'a str,
+    pub title: &'a str,
+    /// The full credit as printed, e.g. `A & B feat. C`.
+    pub artist: &'a str,
+    /// MBID of the first credited artist.
+    pub artist_id: &'a str,
+    /// Wikilink to the first credited artist's note, or empty when the artist is unknown.
+    pub artist_link: String,
+    /// Every credited artist, in credit order.
+    pub artists: Vec<CreditContext>,
+    pub primary_type: &'a str,
+    /// Secondary types such as `Live` or `Compilation`, as a list.
+    pub secondary_types: &'a [String],
+    /// Secondary types joined with `, `, or `None`.
+    pub secondary_types_label: String,
+    pub status: &'a str,
+    /// First release date as stored by MusicBrainz (`YYYY`, `YYYY-MM` or `YYYY-MM-DD`).
+    pub release_date: &'a str,
+    /// Four-digit year of `release_date`, or empty.
+    pub year: &'a str,
+    pub label: &'a str,
+    pub country: &'a str,
+    /// UPC/EAN barcode of the release, or empty.
+    pub barcode: &'a str,
+    pub disambiguation: &'a str,
+    pub cover_art: CoverArtContext,
+    /// Every image downloaded into the album's gallery, in archive order.
+    /// Empty unless the gallery is turned on.
+    pub gallery: Vec<GalleryImageContext>,
+    /// Tracks in release order.
+    pub tracks: Vec<TrackContext<'a>>,
+    /// Pre-formatted numbered track list, one track per line.
+    pub track_listing: String,
+    pub created_at: &'a str,
+    pub updated_at: &'a str,
+}
+
+/// Links to the album's cover art from the note being rendered.
+#[derive(Debug, Serialize)]
+pub struct CoverArtContext {
+    /// Cover Art Archive URL the image was requested from.
+    pub url: String,
+    /// Where the image came from: `release`, `release_group`, `other_release`,
+    /// or empty when there is no cover.
+    pub source: &'static str,
+    /// Link path from the note to the image, e.g. `album_art/<mbid>.jpg`.
+    pub path: String,
+    /// Wikilink for the `cover_art` property, which Bases uses for card images.
+    pub link: String,
+    /// Embed for the note body in the configured link syntax.
+    pub embed: String,
+}
+
+/// One image of the album's gallery.
+#[derive(Debug, Serialize)]
+pub struct GalleryImageContext {
+    /// Image types such as `Front`, `Back` or `Booklet`.
+    pub types: Vec<String>,
+    /// The uploader's comment, often naming the page or side.
+    pub comment: String,
+    /// Link path from the note to the image.
+    pub path: String,
+    /// Wikilink for the `gallery` property.
+    pub link: String,
+    /// Embed for the note body in the configured link syntax.
+    pub embed: String,
+}
+
+/// One entry of the album's artist credit.
+#[derive(Debug, Serialize)]
+pub struct CreditContext {
+    /// Artist MBID.
+    pub id: String,
+    /// The artist's own name.
+    pub name: String,
+    /// The name printed on the release, falling back to `name`.
+    pub credited_as: String,
+    /// Text joining this credit to the next one, such as ` & ` or ` feat. `.
+    pub join_phrase: String,
+    /// Wikilink to the artist note, or empty when the artist is unknown.
+    pub link: String,
+}
+
+impl CreditContext {
+    pub fn new(credit: ArtistCredit, link: String) -> Self {
+        Self {
+            credited_as: credit.display_name().to_string(),
+            id: credit.artist_id,
+            name: credit.name,
+            join_phrase: credit.join_phrase,
+            link,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackContext<'a> {
+    /// 1-based index across all media.
+    pub number: usize,
+    /// Position as printed on the medium, e.g. `A1` or `3`.
+    pub position: &'a str,
+    pub title: &'a str,
+    /// Length in milliseconds, `0` when unknown.
+    pub length_ms: i64,
+    /// Length as `m:ss`, or empty when unknown.
+    pub duration: String,
+}
+
+impl<'a> NoteContext<'a> {
+    pub fn new(
+        album: &'a AlbumRecord,
+        cover_art: CoverArtContext,
+        gallery: Vec<GalleryImageContext>,
+        artists: Vec<CreditContext>,
+    ) -> Self {
+        let track_listing = if album.tracklist.is_empty() {
+            String::from("- Track details unavailable")
+        } else {
+            album.as_track_listing_lines().join("\n")
+        };
+
+        Self {
+            mbid: &album.mbid,
+            release_group_id: &album.mbid,
+            release_id: &album.release_id,
+            title: &album.title,
+            artist: &album.artist,
+            artist_id: &album.artist_id,
+            artist_link: artists
+                .first()
+                .map(|credit| credit.link.clone())
+                .unwrap_or_default(),
+            artists,
+            primary_type: &album.primary_type,
+            secondary_types: &album.secondary_types,
+            secondary_types_label: album.secondary_types_label(),
+            status: &album.status,
+            release_date: &album.release_date,
+            year: album.release_date.get(..4).unwrap_or_default(),
+            label: &album.label,
+            country: &album.country,
+            barcode: &album.barcode,
+            disambiguation: &album.disambiguation,
+            cover_art,
+            gallery,
+            tracks: album
+                .tracklist
+                .iter()
+                .enumerate()
+                .map(|(idx, track)| TrackContext {
+                    number: idx + 1,
+                    position: &track.position,
+                    title: &track.title,
+                    length_ms: track.length_ms,
+                    duration: format_duration(track.length_ms),
+                })
+                .collect(),
+            track_listing,
+            created_at: &album.created_at_utc,
+            updated_at: &album.updated_at_utc,
+        }
+    }
+}
+
+/// Everything an artist note template can reference.
+#[derive(Debug, Serialize)]
+pub struct ArtistContext<'a> {
+    /// Artist MBID.
+    pub mbid: &'a str,
+    pub name: &'a str,
+    pub disambiguation: &'a str,
+    /// The artist's albums that have notes, oldest first.
+    pub albums: Vec<ArtistAlbumContext<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistAlbumContext<'a> {
+    /// Release-group MBID.
+    pub mbid: &'a str,
+    pub title: &'a str,
+    pub primary_type: &'a str,
+    pub release_date: &'a str,
+    pub year: &'a str,
+    /// Wikilink to the album note, showing the album title.
+    pub link: String,
+}
+
+fn describe(err: &Error) -> String {
+    err.detail()
+        .map(str::to_string)
+        .unwrap_or_else(|| err.kind().to_string())
+}
+
+/// Rewrites the original `{placeholder}` syntax into Jinja expressions so
+/// existing templates keep working. Returns `None` for Jinja templates.
+fn upgrade_legacy_syntax(source: &str) -> Option<String> {
+    const LEGACY: [(&str, &str); 10] = [
+        ("{title}", "{{ title }}"),
+        ("{artist}", "{{ artist }}"),
+        ("{release_date}", "{{ release_date }}"),
+        ("{musicbrainz_id}", "{{ mbid }}"),
+        ("{primary_type}", "{{ primary_type }}"),
+        ("{secondary_types}", "{{ secondary_types_label }}"),
+        ("{track_listing}", "{{ track_listing }}"),
+        ("{cover_art_relative_path}", "{{ cover_art.path }}"),
+        ("{cover_art_link}", "{{ cover_art.link }}"),
+        ("{cover_art_embed}", "{{ cover_art.embed }}"),
+    ];
+
+    if source.contains("{{") || source.contains("{%") {
+        return None;
+    }
+
+    let mut upgraded = source.to_string();
+    for (legacy, jinja) in LEGACY {
+        upgraded = upgraded.replace(legacy, jinja);
+    }
+
+    (upgraded != source).then_some(upgraded)
+}
+
+fn yaml_filter(value: Value) -> String {
+    match value.as_str() {
+        Some(text) => yaml_quote(text),
+        None if value.is_undefined() || value.is_none() => String::from("\"\""),
+        None => yaml_quote(&value.to_string()),
+    }
+}
+
+/// Double-quotes `text` as a YAML scalar, escaping characters that would
+/// otherwise end or corrupt the string.
+pub fn yaml_quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats a full date. A bare year or month is passed through as written,
+/// since formatting it would make up the missing day.
+fn date_filter(value: String, format: Option<String>) -> Result<String, Error> {
+    let format = format.as_deref().unwrap_or("%Y-%m-%d");
+    match parse_full_date(&value) {
+        Some(date) => Ok(date.format(format).to_string()),
+        None if value.is_empty() || parse_partial_date(&value).is_some() => Ok(value),
+        None => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("`{value}` is not a date"),
+        )),
+    }
+}
+
+fn duration_filter(length_ms: i64) -> String {
+    format_duration(length_ms)
+}
+
+fn wikilink_filter(value: String) -> String {
+    if value.is_empty() || value.starts_with("[[") {
+        value
+    } else {
+        format!("[[{value}]]")
+    }
+}
+
+fn format_duration(length_ms: i64) -> String {
+    if length_ms <= 0 {
+        return String::new();
+    }
+    let total_seconds = length_ms / 1000;
+    format!("{}:{:02}", total_seconds / 60, total_seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::models::album::TrackInfo;
+
+    /// Parses `text` back out of a YAML mapping and list entry.
+    fn round_trip(text: &str) -> (String, Vec<String>) {
+        let yaml = format!(
+            "title: {quoted}\nlist:\n  - {quoted}\n",
+            quoted = yaml_quote(text)
+        );
+        let mut parsed: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(&yaml)
+            .unwrap_or_else(|err| panic!("{yaml:?} is not valid YAML: {err}"));
+        let title = serde_yaml::from_value(parsed.remove("title").unwrap()).unwrap();
+        let list = serde_yaml::from_value(parsed.remove("list").unwrap()).unwrap();
+        (title, list)
+    }
+
+    #[test]
+    fn yaml_quote_round_trips() {
+        let titles = [
+            "Dummy",
+            "Mezzanine: Remastered",
+            "Side A #1",
+            "The \"Black\" Album",
+            "Don't Stop",
+            "[Untitled]",
+            "{Awayland}",
+            "*NSYNC",
+            "&Burn",
+            "!!Destroy-Oh-Boy!!",
+            "- Single",
+            "-",
+            "yes",
+            "no",
+            "null",
+            "~",
+            "true",
+            "1994",
+            "0x10",
+            "a\\b",
+            "Line one\nLine two",
+            "Tabs\tand\rreturns",
+            "Bell \u{7}",
+            " padded ",
+            "",
+        ];
+        for title in titles {
+            let (parsed, list) = round_trip(title);
+            assert_eq!(parsed, title, "quoted as {}", yaml_quote(title));
+            assert_eq!(list, [title]);
+        }
+    }
+
+    #[test]
+    fn date_filter_formats_full_dates_only() {
+        let format = || Some(String::from("%d %B %Y"));
+        assert_eq!(
+            date_filter(String::from("1994-08-22"), format()).unwrap(),
+            "22 August 1994"
+        );
+        assert_eq!(
+            date_filter(String::from("1994-08"), format()).unwrap(),
+            "1994-08"
+        );
+        assert_eq!(date_filter(String::from("1994"), format()).unwrap(), "1994");
+        assert_eq!(date_filter(String::new(), format()).unwrap(), "");
+        assert!(date_filter(String::from("soon"), format()).is_err());
+    }
+
+    /// A templates directory holding `files` as `(name, source)` pairs.
+    fn templates(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, source) in files {
+            fs::write(dir.path().join(format!("{name}.md")), source).unwrap();
+        }
+        dir
+    }
+
+    fn cover_art() -> CoverArtContext {
+        CoverArtContext {
+            url: String::new(),
+            source: "",
+            path: String::new(),
+            link: String::new(),
+            embed: String::new(),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_name_the_file_and_line() {
+        let dir = templates(&[
+            ("album", "# {{ title }}\n"),
+            ("broken", "# {{ title }}\n\nlabel: {{ label }\n\n## Notes\n"),
+        ]);
+
+        let err = TemplateSet::load(dir.path()).err().unwrap().to_string();
+
+        let file = dir.path().join("broken.md");
+        assert!(
+            err.starts_with(&format!("Invalid note template {}:3: ", file.display())),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn the_documented_context_renders() {
+        let dir = templates(&[(
+            "album",
+            r#"{% if label %}
+label: {{ label | yaml }}
+{% endif %}
+{% if country %}
+country: {{ country }}
+{% endif %}
+{% if status %}
+status: {{ status }}
+{% endif %}
+{% for track in tracks %}
+{{ track.number }}. {{ track.title }}{% if track.duration %} ({{ track.duration }}){% endif %}
+
+{% else %}
+- Track details unavailable
+{% endfor %}
+"#,
+        )]);
+        let templates = TemplateSet::load(dir.path()).unwrap();
+
+        let mut album = AlbumRecord {
+            title: String::from("Dummy"),
+            label: String::from("Go! Beat"),
+            country: String::from("GB"),
+            tracklist: vec![
+                TrackInfo {
+                    position: String::from("1"),
+                    title: String::from("Mysterons"),
+                    length_ms: 306_000,
+                },
+                TrackInfo {
+                    position: String::from("2"),
+                    title: String::from("Sour Times"),
+                    length_ms: 0,
+                },
+            ],
+            ..AlbumRecord::default()
+        };
+        let render = |album: &AlbumRecord| {
+            let context = NoteContext::new(album, cover_art(), Vec::new(), Vec::new());
+            templates.render("album", &context).unwrap()
+        };
+
+        assert_eq!(
+            render(&album),
+            "label: \"Go! Beat\"\ncountry: GB\n1. Mysterons (5:06)\n2. Sour Times\n"
+        );
+
+        album.label.clear();
+        album.country.clear();
+        album.status = String::from("Official");
+        album.tracklist.clear();
+        assert_eq!(
+            render(&album),
+            "status: Official\n- Track details unavailable\n"
+        );
+    }
+
+    #[test]
+    fn render_errors_name_the_template_and_line() {
+        let dir = templates(&[("album", "# {{ title }}\n\n{{ missing.field }}\n")]);
+        let templates = TemplateSet::load(dir.path()).unwrap();
+        let album = AlbumRecord::default();
+        let context = NoteContext::new(&album, cover_art(), Vec::new(), Vec::new());
+
+        let err = templates.render("album", &context).unwrap_err().to_string();
+
+        assert!(
+            err.starts_with("Template error in `album` on line 3:"),
+            "{err}"
+        );
+    }
+}
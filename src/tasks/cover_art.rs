@@ -1,22 +1,32 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{Context, Result};
-use reqwest::{Client, header};
+use anyhow::{Context, Result, anyhow};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use tokio::time::{MissedTickBehavior, interval};
+use tokio::time::{Interval, MissedTickBehavior, interval};
 
+use crate::api::MetadataProvider;
+use crate::api::cover_art_archive::{CoverArtArchiveClient, CoverArtIndex, IndexImage};
 use crate::app::AppMessage;
-use crate::config::AppConfig;
+use crate::config::{AppConfig, CoverArtSize, SquareCover};
 use crate::library::LibraryStore;
-use crate::models::{AlbumRecord, CoverArtStatus};
+use crate::models::{AlbumRecord, CoverArtImage, CoverArtSource, CoverArtStatus};
+
+use super::square;
 
 #[derive(Clone)]
 pub struct CoverArtDownloaderHandle {
     tx: UnboundedSender<CoverArtJob>,
+    base_url: String,
 }
 
 impl CoverArtDownloaderHandle {
+    /// Root of the Cover Art Archive the downloader uses.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     pub fn enqueue(&self, record: AlbumRecord) -> Result<()> {
         self.tx
             .send(CoverArtJob { record })
@@ -24,138 +34,736 @@ impl CoverArtDownloaderHandle {
     }
 }
 
+#[cfg(test)]
+impl CoverArtDownloaderHandle {
+    /// A handle whose downloader has already stopped, so enqueueing fails.
+    pub fn stopped(base_url: &str) -> Self {
+        let (tx, _) = mpsc::unbounded_channel();
+        Self {
+            tx,
+            base_url: base_url.to_string(),
+        }
+    }
+
+    /// A handle whose downloader accepts jobs but never reports on them.
+    pub fn idle(base_url: &str) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<CoverArtJob>();
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+        Self {
+            tx,
+            base_url: base_url.to_string(),
+        }
+    }
+}
+
 pub fn spawn(
     config: AppConfig,
+    musicbrainz: Arc<dyn MetadataProvider>,
     library: LibraryStore,
     message_tx: UnboundedSender<AppMessage>,
 ) -> Result<CoverArtDownloaderHandle> {
     let (tx, rx) = mpsc::unbounded_channel();
+    let base_url = config.cover_art_url().to_string();
+
+    let mut throttle = interval(Duration::from_secs(1));
+    throttle.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-    let client = build_client(&config)?;
-    let album_art_dir = PathBuf::from(config.album_art_dir());
+    let downloader = Downloader {
+        archive: CoverArtArchiveClient::new(&config)?,
+        musicbrainz,
+        library,
+        message_tx,
+        size: config.cover_art_size(),
+        image_types: config.cover_art_types().to_vec(),
+        square: config.square_cover(),
+        config,
+        throttle,
+    };
 
-    tokio::spawn(async move {
-        run_downloader(client, library, message_tx, album_art_dir, rx).await;
-    });
+    tokio::spawn(downloader.run(rx));
 
-    Ok(CoverArtDownloaderHandle { tx })
+    Ok(CoverArtDownloaderHandle { tx, base_url })
 }
 
 struct CoverArtJob {
     record: AlbumRecord,
 }
 
-fn build_client(config: &AppConfig) -> Result<Client> {
-    let mut headers = header::HeaderMap::new();
-    headers.insert(
-        header::USER_AGENT,
-        header::HeaderValue::from_str(config.user_agent())
-            .context("invalid user agent header value for cover art client")?,
-    );
-    headers.insert(
-        "X-Client-Id",
-        header::HeaderValue::from_str(config.client_id())
-            .context("invalid client identifier for cover art client")?,
-    );
-    headers.insert(
-        header::ACCEPT,
-        header::HeaderValue::from_static("image/jpeg, image/png, application/json"),
-    );
-
-    let client = Client::builder()
-        .default_headers(headers)
-        .build()
-        .context("unable to create HTTP client for cover art downloads")?;
-
-    Ok(client)
+/// A cover saved to disk, with the listing it was picked from.
+#[derive(Debug)]
+struct SavedCover {
+    path: PathBuf,
+    source: CoverArtSource,
+    image: CoverArtImage,
+    index: CoverArtIndex,
 }
 
-async fn run_downloader(
-    client: Client,
+struct Downloader {
+    archive: CoverArtArchiveClient,
+    musicbrainz: Arc<dyn MetadataProvider>,
     library: LibraryStore,
     message_tx: UnboundedSender<AppMessage>,
-    album_art_dir: PathBuf,
-    mut rx: UnboundedReceiver<CoverArtJob>,
-) {
-    let mut throttle = interval(Duration::from_secs(1));
-    throttle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    /// Decides which folder each album's cover art is saved in.
+    config: AppConfig,
+    size: CoverArtSize,
+    image_types: Vec<String>,
+    square: Option<SquareCover>,
+    throttle: Interval,
+}
+
+impl Downloader {
+    async fn run(mut self, mut rx: UnboundedReceiver<CoverArtJob>) {
+        while let Some(job) = rx.recv().await {
+            let record = job.record;
+            let mbid = record.mbid.clone();
+
+            let _ = self.message_tx.send(AppMessage::CoverArtStatus {
+                mbid: mbid.clone(),
+                status: CoverArtStatus::Queued,
+                path: None,
+                source: None,
+            });
+
+            match self.download_cover_art(&record).await {
+                Ok(SavedCover {
+                    path,
+                    source,
+                    image,
+                    index,
+                }) => {
+                    if source != CoverArtSource::Release {
+                        self.log(format!(
+                            "Cover art for {} taken from {source}",
+                            record.title
+                        ));
+                    }
 
-    while let Some(job) = rx.recv().await {
-        let record = job.record;
-        let mbid = record.mbid.clone();
-
-        let _ = message_tx.send(AppMessage::CoverArtStatus {
-            mbid: mbid.clone(),
-            status: CoverArtStatus::Queued,
-            path: None,
-        });
-
-        throttle.tick().await;
-
-        let download_result = download_cover_art(&client, &record, &album_art_dir).await;
-
-        match download_result {
-            Ok(path) => {
-                if let Err(err) = library.set_cover_art_path(
-                    &mbid,
-                    Some(path.to_string_lossy().to_string()),
-                    CoverArtStatus::Completed,
-                ) {
-                    let _ = message_tx.send(AppMessage::DownloadLog(format!(
-                        "Failed to update library for {mbid}: {err}"
-                    )));
+                    if let Err(err) = self
+                        .library
+                        .set_cover_art_path(
+                            &mbid,
+                            Some(path.to_string_lossy().to_string()),
+                            CoverArtStatus::Completed,
+                        )
+                        .and_then(|_| {
+                            self.library.set_cover_art_details(
+                                &mbid,
+                                Some(source.clone()),
+                                Some(image),
+                            )
+                        })
+                    {
+                        self.log(format!("Failed to update library for {mbid}: {err}"));
+                    }
+
+                    if record.gallery {
+                        let images = self.download_gallery(&record, &index).await;
+                        self.log(format!(
+                            "Downloaded {} of {} gallery images for {}",
+                            images.len(),
+                            index.images.len(),
+                            record.title
+                        ));
+                        if let Err(err) = self.library.set_gallery_images(&mbid, images) {
+                            self.log(format!("Failed to update library for {mbid}: {err}"));
+                        }
+                        if let Ok(all) = self.library.all_albums() {
+                            let _ = self.message_tx.send(AppMessage::LibraryRefreshed(all));
+                        }
+                    }
+
+                    let _ = self.message_tx.send(AppMessage::CoverArtStatus {
+                        mbid,
+                        status: CoverArtStatus::Completed,
+                        path: Some(path.to_string_lossy().to_string()),
+                        source: Some(source),
+                    });
                 }
+                Err(err) => {
+                    let _ =
+                        self.library
+                            .set_cover_art_path(&mbid, None, CoverArtStatus::Unavailable);
+                    let _ = self.library.set_cover_art_details(&mbid, None, None);
+                    self.log(format!("Cover art unavailable for {mbid}: {err}"));
+                    let _ = self.message_tx.send(AppMessage::CoverArtStatus {
+                        mbid,
+                        status: CoverArtStatus::Unavailable,
+                        path: None,
+                        source: None,
+                    });
+                }
+            }
+        }
+    }
 
-                let _ = message_tx.send(AppMessage::CoverArtStatus {
-                    mbid,
-                    status: CoverArtStatus::Completed,
-                    path: Some(path.to_string_lossy().to_string()),
-                });
+    /// Downloads the album's cover, looking in turn at the images of its own
+    /// release, of the release the archive picked for the release group, and
+    /// of any other release in the group with front art. Each listing is
+    /// searched for the configured image types in order of preference.
+    async fn download_cover_art(&mut self, record: &AlbumRecord) -> Result<SavedCover> {
+        let mut failures = Vec::new();
+
+        if !record.release_id.is_empty() {
+            self.throttle.tick().await;
+            let index = self.archive.release_index(&record.release_id).await;
+            match self.try_index(record, CoverArtSource::Release, index).await {
+                Ok(saved) => return Ok(saved),
+                Err(err) => failures.push(err.to_string()),
             }
-            Err(err) => {
-                let _ = library.set_cover_art_path(&mbid, None, CoverArtStatus::Unavailable);
-                let _ = message_tx.send(AppMessage::DownloadLog(format!(
-                    "Cover art unavailable for {mbid}: {err}"
-                )));
-                let _ = message_tx.send(AppMessage::CoverArtStatus {
-                    mbid,
-                    status: CoverArtStatus::Unavailable,
-                    path: None,
-                });
+        }
+
+        self.throttle.tick().await;
+        let index = self.archive.release_group_index(&record.mbid).await;
+        match self
+            .try_index(record, CoverArtSource::ReleaseGroup, index)
+            .await
+        {
+            Ok(saved) => return Ok(saved),
+            Err(err) => failures.push(err.to_string()),
+        }
+
+        match self.musicbrainz.releases_for_group(&record.mbid).await {
+            Ok(releases) => {
+                let mut others = releases
+                    .into_iter()
+                    .filter(|release| release.has_front_cover && release.id != record.release_id)
+                    .peekable();
+                if others.peek().is_none() {
+                    failures.push(String::from("no other release has front art"));
+                }
+                for release in others {
+                    self.throttle.tick().await;
+                    let index = self.archive.release_index(&release.id).await;
+                    let source = CoverArtSource::OtherRelease(release.id);
+                    match self.try_index(record, source, index).await {
+                        Ok(saved) => return Ok(saved),
+                        Err(err) => failures.push(err.to_string()),
+                    }
+                }
+            }
+            Err(err) => failures.push(format!("other releases: {err}")),
+        }
+
+        Err(anyhow!(failures.join("; ")))
+    }
+
+    /// Picks an image from one listing and saves it, describing why not otherwise.
+    async fn try_index(
+        &mut self,
+        record: &AlbumRecord,
+        source: CoverArtSource,
+        index: Result<Option<CoverArtIndex>>,
+    ) -> Result<SavedCover> {
+        let index = index
+            .map_err(|err| anyhow!("{source}: {err}"))?
+            .ok_or_else(|| anyhow!("{source}: no images"))?;
+        let image = index
+            .select(&self.image_types)
+            .ok_or_else(|| anyhow!("{source}: no {} image", self.image_types.join("/")))?;
+
+        self.throttle.tick().await;
+        let (image, bytes) = self
+            .fetch_index_image(image)
+            .await
+            .map_err(|err| anyhow!("{source}: {err}"))?;
+        let image = self.store_cover(record, image, bytes).await?;
+
+        Ok(SavedCover {
+            path: PathBuf::from(&image.path),
+            image,
+            source,
+            index,
+        })
+    }
+
+    /// Downloads every image in `index` into the album's gallery folder,
+    /// replacing any earlier gallery. Images that fail are logged and skipped.
+    async fn download_gallery(
+        &mut self,
+        record: &AlbumRecord,
+        index: &CoverArtIndex,
+    ) -> Vec<CoverArtImage> {
+        for old in &record.gallery_images {
+            let _ = tokio::fs::remove_file(&old.path).await;
+        }
+
+        let dir = self
+            .config
+            .album_art_dir_for(record)
+            .join(record.gallery_dirname());
+        let mut images = Vec::with_capacity(index.images.len());
+        for (position, image) in index.images.iter().enumerate() {
+            self.throttle.tick().await;
+            let saved = match self.fetch_index_image(image).await {
+                Ok((mut image, bytes)) => {
+                    let file_name = record.gallery_filename(
+                        position + 1,
+                        &image.types,
+                        extension_for(&image.content_type),
+                    );
+                    save_image(&dir, &file_name, &bytes).await.map(|path| {
+                        image.path = path.to_string_lossy().into_owned();
+                        image
+                    })
+                }
+                Err(err) => Err(err),
+            };
+            match saved {
+                Ok(saved) => images.push(saved),
+                Err(err) => self.log(format!(
+                    "Gallery image {} of {} failed: {err}",
+                    position + 1,
+                    record.title
+                )),
+            }
+        }
+        images
+    }
+
+    /// Downloads `image` at the configured size. The returned details have
+    /// no `path` until the bytes are saved.
+    async fn fetch_index_image(&self, image: &IndexImage) -> Result<(CoverArtImage, Vec<u8>)> {
+        let (url, size) = image.url_for(self.size);
+        let download = self.archive.download(url).await?;
+
+        let content_type = download
+            .content_type
+            .filter(|content_type| content_type.starts_with("image/"))
+            .unwrap_or_else(|| sniff_content_type(&download.bytes).to_string());
+
+        let details = CoverArtImage {
+            id: image.id(),
+            types: image.types.clone(),
+            comment: image.comment.clone(),
+            size: size.to_string(),
+            url: url.to_string(),
+            content_type,
+            bytes: download.bytes.len() as u64,
+            ..CoverArtImage::default()
+        };
+        Ok((details, download.bytes))
+    }
+
+    /// Saves a downloaded cover, squaring it first when configured. A cover
+    /// that cannot be processed is logged and saved as downloaded.
+    async fn store_cover(
+        &self,
+        record: &AlbumRecord,
+        mut image: CoverArtImage,
+        bytes: Vec<u8>,
+    ) -> Result<CoverArtImage> {
+        let extension = extension_for(&image.content_type);
+        let Some(settings) = self.square else {
+            return self.save_cover(record, image, extension, &bytes).await;
+        };
+
+        let processed =
+            tokio::task::spawn_blocking(move || (square::square_cover(&bytes, &settings), bytes))
+                .await
+                .context("cover processing task failed")?;
+
+        match processed {
+            (Ok(squared), original) => {
+                if settings.keep_original {
+                    let original_name = |extension: &str| {
+                        record.cover_art_filename(&format!("original.{extension}"))
+                    };
+                    let art_dir = self.config.album_art_dir_for(record);
+                    remove_other_formats(&art_dir, original_name, extension).await;
+                    let file_name = original_name(extension);
+                    let path = save_image(&art_dir, &file_name, &original).await?;
+                    image.original_path = path.to_string_lossy().into_owned();
+                }
+                image.content_type = settings.format.content_type().to_string();
+                image.bytes = squared.len() as u64;
+                self.save_cover(record, image, settings.format.extension(), &squared)
+                    .await
+            }
+            (Err(err), original) => {
+                self.log(format!("Keeping {} as downloaded: {err:#}", record.title));
+                self.save_cover(record, image, extension, &original).await
             }
         }
     }
+
+    async fn save_cover(
+        &self,
+        record: &AlbumRecord,
+        mut image: CoverArtImage,
+        extension: &str,
+        bytes: &[u8],
+    ) -> Result<CoverArtImage> {
+        let art_dir = self.config.album_art_dir_for(record);
+        remove_other_formats(
+            &art_dir,
+            |extension| record.cover_art_filename(extension),
+            extension,
+        )
+        .await;
+        let file_name = record.cover_art_filename(extension);
+        let path = save_image(&art_dir, &file_name, bytes).await?;
+        image.path = path.to_string_lossy().into_owned();
+        Ok(image)
+    }
+
+    fn log(&self, message: String) {
+        let _ = self.message_tx.send(AppMessage::DownloadLog(message));
+    }
 }
 
-async fn download_cover_art(
-    client: &Client,
-    record: &AlbumRecord,
-    album_art_dir: &PathBuf,
-) -> Result<PathBuf> {
-    let url = &record.cover_art_url;
-    let response = client
-        .get(url)
-        .send()
+async fn save_image(dir: &Path, file_name: &str, bytes: &[u8]) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .context("failed to ensure album art directory exists")?;
+    let path = dir.join(file_name);
+    tokio::fs::write(&path, bytes)
         .await
-        .context("failed to request cover art")?;
+        .context("failed to write cover art to disk")?;
+    Ok(path)
+}
 
-    if response.status().is_success() {
-        let bytes = response
-            .bytes()
-            .await
-            .context("failed to read cover art bytes")?;
-        tokio::fs::create_dir_all(album_art_dir)
+/// Extensions images are saved with, see [`extension_for`].
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "png", "gif", "webp"];
+
+/// Removes the files `file_name` gives for every image extension but `keep`,
+/// so a cover downloaded in a new format does not leave the old one behind.
+async fn remove_other_formats(dir: &Path, file_name: impl Fn(&str) -> String, keep: &str) {
+    for extension in IMAGE_EXTENSIONS
+        .iter()
+        .filter(|extension| **extension != keep)
+    {
+        let _ = tokio::fs::remove_file(dir.join(file_name(extension))).await;
+    }
+}
+
+/// Identifies an image format from its first bytes, for servers that do not
+/// send a usable `Content-Type`.
+fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+fn extension_for(content_type: &str) -> &'static str {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    match mime.to_ascii_lowercase().as_str() {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "jpg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::api::fixtures::{StubArchive, StubProvider};
+    use crate::config::{ConfigLayer, CoverArtLayer};
+    use crate::models::{Album, Release};
+
+    const CAA: &str = "https://coverartarchive.org";
+    const GROUP: &str = "76df3287-6cda-33eb-8e9a-044b5e15ffdd";
+    const RELEASE: &str = "3a3aca1e-ae9b-4b35-9b7b-c2d6c5f2b4f3";
+    const REISSUE: &str = "0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b";
+    const BOOTLEG: &str = "5b6c7d8e-1111-4222-8333-944455566677";
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0];
+
+    fn listing(images: serde_json::Value) -> CoverArtIndex {
+        serde_json::from_value(serde_json::json!({ "images": images })).unwrap()
+    }
+
+    /// A listing with one front image, and that image's bytes.
+    fn front_listing(name: &str) -> (CoverArtIndex, (String, Vec<u8>)) {
+        let url = format!("{CAA}/images/{name}.jpg");
+        let index = listing(serde_json::json!([
+            { "id": 1, "types": ["Front"], "front": true, "image": url }
+        ]));
+        (index, (url, JPEG.to_vec()))
+    }
+
+    fn archive(listings: &[(String, &str)], broken: &[String]) -> StubArchive {
+        let mut archive = StubArchive::default();
+        archive.broken = broken.to_vec();
+        for (url, name) in listings {
+            let (index, (image_url, bytes)) = front_listing(name);
+            archive.listings.insert(url.clone(), index);
+            archive
+                .images
+                .insert(image_url, (bytes, Some(String::from("image/jpeg"))));
+        }
+        archive
+    }
+
+    fn releases() -> Vec<Release> {
+        let release = |id: &str, has_front_cover| Release {
+            id: id.to_string(),
+            release_group_id: GROUP.to_string(),
+            has_front_cover,
+            ..Release::default()
+        };
+        vec![
+            release(RELEASE, true),
+            release(BOOTLEG, false),
+            release(REISSUE, true),
+        ]
+    }
+
+    fn downloader(dir: &Path, archive: Arc<StubArchive>, releases: Vec<Release>) -> Downloader {
+        let config = AppConfig::in_dir(dir);
+        let (message_tx, _) = mpsc::unbounded_channel();
+        Downloader {
+            archive: CoverArtArchiveClient::with_source(CAA, archive),
+            musicbrainz: Arc::new(StubProvider {
+                releases,
+                ..StubProvider::default()
+            }),
+            library: LibraryStore::open(&config).unwrap(),
+            message_tx,
+            config,
+            size: CoverArtSize::Original,
+            image_types: vec![String::from("front")],
+            square: None,
+            throttle: interval(Duration::from_millis(1)),
+        }
+    }
+
+    fn record() -> AlbumRecord {
+        AlbumRecord {
+            mbid: GROUP.to_string(),
+            release_id: RELEASE.to_string(),
+            title: String::from("Dummy"),
+            ..AlbumRecord::default()
+        }
+    }
+
+    fn release_url(id: &str) -> String {
+        format!("{CAA}/release/{id}")
+    }
+
+    fn group_url() -> String {
+        format!("{CAA}/release-group/{GROUP}")
+    }
+
+    #[tokio::test]
+    async fn the_chosen_release_comes_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(archive(
+            &[(release_url(RELEASE), "release"), (group_url(), "group")],
+            &[],
+        ));
+        let mut downloader = downloader(dir.path(), archive.clone(), releases());
+
+        let saved = downloader.download_cover_art(&record()).await.unwrap();
+
+        assert_eq!(saved.source, CoverArtSource::Release);
+        assert_eq!(saved.image.url, format!("{CAA}/images/release.jpg"));
+        assert_eq!(fs::read(&saved.path).unwrap(), JPEG);
+        assert_eq!(archive.requests().len(), 2, "no fallback was tried");
+    }
+
+    #[tokio::test]
+    async fn the_release_group_is_next() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(archive(&[(group_url(), "group")], &[]));
+        let mut downloader = downloader(dir.path(), archive.clone(), releases());
+
+        let saved = downloader.download_cover_art(&record()).await.unwrap();
+
+        assert_eq!(saved.source, CoverArtSource::ReleaseGroup);
+        assert_eq!(saved.image.url, format!("{CAA}/images/group.jpg"));
+        assert_eq!(
+            archive.requests(),
+            [
+                release_url(RELEASE),
+                group_url(),
+                format!("{CAA}/images/group.jpg")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn other_releases_with_front_art_are_last() {
+        let dir = tempfile::tempdir().unwrap();
+        // The release's listing fails and has no front image in the group's.
+        let mut archive = archive(
+            &[(release_url(REISSUE), "reissue")],
+            &[release_url(RELEASE)],
+        );
+        archive.listings.insert(
+            group_url(),
+            listing(serde_json::json!([
+                { "id": 2, "types": ["Back"], "front": false, "image": format!("{CAA}/images/back.jpg") }
+            ])),
+        );
+        let archive = Arc::new(archive);
+        let mut downloader = downloader(dir.path(), archive.clone(), releases());
+
+        let saved = downloader.download_cover_art(&record()).await.unwrap();
+
+        assert_eq!(
+            saved.source,
+            CoverArtSource::OtherRelease(REISSUE.to_string())
+        );
+        let requests = archive.requests();
+        assert!(!requests.contains(&release_url(BOOTLEG)), "no front art");
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|url| **url == release_url(RELEASE))
+                .count(),
+            1,
+            "the chosen release is not tried twice"
+        );
+    }
+
+    #[tokio::test]
+    async fn every_failed_step_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = archive(&[], &[group_url()]);
+        // The reissue lists an image that cannot be downloaded.
+        let (index, _) = front_listing("missing");
+        archive.listings.insert(release_url(REISSUE), index);
+        let archive = Arc::new(archive);
+        let mut downloader = downloader(dir.path(), archive, releases());
+
+        let err = downloader
+            .download_cover_art(&record())
             .await
-            .context("failed to ensure album art directory exists")?;
-        let path = album_art_dir.join(record.cover_art_filename());
-        tokio::fs::write(&path, &bytes)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("release: no images"), "{err}");
+        assert!(err.contains("503"), "{err}");
+        assert!(err.contains("404"), "{err}");
+        let cover = downloader
+            .config
+            .album_art_dir_for(&record())
+            .join(record().cover_art_filename("jpg"));
+        assert!(!cover.exists(), "nothing is written");
+    }
+
+    #[tokio::test]
+    async fn no_other_release_with_front_art_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = Arc::new(archive(&[], &[]));
+        let mut downloader = downloader(dir.path(), archive, Vec::new());
+
+        let err = downloader
+            .download_cover_art(&record())
             .await
-            .context("failed to write cover art to disk")?;
-        Ok(path)
-    } else {
-        Err(anyhow::anyhow!(
-            "cover art returned status {}",
-            response.status()
-        ))
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("other releases"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn a_cover_in_a_new_format_replaces_the_old_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut archive = archive(&[], &[]);
+        let (index, (url, _)) = front_listing("release");
+        archive.listings.insert(release_url(RELEASE), index);
+        archive.images.insert(url, (png.clone(), None));
+        let mut downloader = downloader(dir.path(), Arc::new(archive), releases());
+        let art_dir = downloader.config.album_art_dir_for(&record());
+        let old = art_dir.join(record().cover_art_filename("jpg"));
+        let unrelated = art_dir.join(format!("{GROUP}.md"));
+        fs::write(&old, JPEG).unwrap();
+        fs::write(&unrelated, "").unwrap();
+
+        let saved = downloader.download_cover_art(&record()).await.unwrap();
+
+        assert_eq!(saved.path, art_dir.join(record().cover_art_filename("png")));
+        assert_eq!(saved.image.content_type, "image/png");
+        assert_eq!(fs::read(&saved.path).unwrap(), png);
+        assert!(!old.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[tokio::test]
+    async fn cover_art_urls_use_the_configured_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig::in_dir_with(
+            dir.path(),
+            ConfigLayer {
+                cover_art: CoverArtLayer {
+                    base_url: Some(String::from("https://caa.example.org/")),
+                    ..CoverArtLayer::default()
+                },
+                ..ConfigLayer::default()
+            },
+        );
+        let library = LibraryStore::open(&config).unwrap();
+        let (message_tx, _) = mpsc::unbounded_channel();
+        let handle = spawn(
+            config,
+            Arc::new(StubProvider::default()),
+            library,
+            message_tx,
+        )
+        .unwrap();
+
+        let mut album = Album {
+            id: GROUP.to_string(),
+            ..Album::default()
+        };
+        assert_eq!(
+            AlbumRecord::from_album(&album, handle.base_url()).cover_art_url,
+            format!("https://caa.example.org/release-group/{GROUP}/front")
+        );
+        album.release_id = RELEASE.to_string();
+        assert_eq!(
+            AlbumRecord::from_album(&album, handle.base_url()).cover_art_url,
+            format!("https://caa.example.org/release/{RELEASE}/front")
+        );
+    }
+
+    #[test]
+    fn content_types_are_sniffed_from_magic_bytes() {
+        assert_eq!(sniff_content_type(JPEG), "image/jpeg");
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n"), "image/png");
+        assert_eq!(sniff_content_type(b"GIF89a"), "image/gif");
+        assert_eq!(sniff_content_type(b"RIFF\x10\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_content_type(b"RIFF\x10\0\0\0WAVEfmt "), "image/jpeg");
+        assert_eq!(sniff_content_type(b""), "image/jpeg");
+    }
+
+    #[test]
+    fn extensions_follow_the_content_type() {
+        assert_eq!(extension_for("image/png"), "png");
+        assert_eq!(extension_for("IMAGE/PNG; charset=binary"), "png");
+        assert_eq!(extension_for("image/gif"), "gif");
+        assert_eq!(extension_for("image/webp"), "webp");
+        assert_eq!(extension_for("image/jpeg"), "jpg");
+        assert_eq!(extension_for("application/octet-stream"), "jpg");
+        for extension in [
+            extension_for("image/png"),
+            extension_for("image/gif"),
+            extension_for("image/webp"),
+            extension_for("image/jpeg"),
+        ] {
+            assert!(IMAGE_EXTENSIONS.contains(&extension));
+        }
     }
 }
@@ -1 +1,2 @@
 pub mod cover_art;
+pub mod square;
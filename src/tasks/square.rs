@@ -0,0 +1,133 @@
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::config::{SquareCover, SquareFit, SquareFormat};
+
+/// Decodes a downloaded cover and re-encodes it as a square image of
+/// `settings.size` pixels. Transparent areas and padding are filled with the
+/// background colour, since neither output format is stored with alpha.
+pub fn square_cover(bytes: &[u8], settings: &SquareCover) -> Result<Vec<u8>> {
+    let source = image::load_from_memory(bytes).context("unable to decode cover image")?;
+    let size = settings.size;
+
+    let scaled = match settings.fit {
+        SquareFit::Crop => source.resize_to_fill(size, size, FilterType::Lanczos3),
+        SquareFit::Pad => source.resize(size, size, FilterType::Lanczos3),
+    };
+
+    let [r, g, b] = settings.background.0;
+    let mut canvas = RgbaImage::from_pixel(size, size, Rgba([r, g, b, 255]));
+    let x = (size - scaled.width()) / 2;
+    let y = (size - scaled.height()) / 2;
+    imageops::overlay(&mut canvas, &scaled.to_rgba8(), x.into(), y.into());
+    let square = DynamicImage::ImageRgba8(canvas).into_rgb8();
+
+    let mut out = Cursor::new(Vec::new());
+    match settings.format {
+        SquareFormat::Jpeg => {
+            square.write_with_encoder(JpegEncoder::new_with_quality(&mut out, settings.quality))
+        }
+        SquareFormat::Webp => square.write_with_encoder(WebPEncoder::new_lossless(&mut out)),
+    }
+    .context("unable to encode square cover")?;
+
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, Rgb, RgbImage};
+
+    use super::*;
+    use crate::config;
+
+    const GREEN: [u8; 3] = [0, 160, 0];
+    const WHITE: [u8; 3] = [255, 255, 255];
+
+    /// A solid green PNG of `width` × `height`.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        RgbImage::from_pixel(width, height, Rgb(GREEN))
+            .write_to(&mut out, ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn settings(fit: SquareFit, format: SquareFormat) -> SquareCover {
+        SquareCover {
+            size: 16,
+            fit,
+            format,
+            quality: 90,
+            background: config::Rgb(WHITE),
+            keep_original: false,
+        }
+    }
+
+    /// Squares `source`, checking the output format and size.
+    fn square(source: &[u8], fit: SquareFit, format: SquareFormat) -> RgbImage {
+        let bytes = square_cover(source, &settings(fit, format)).unwrap();
+        let expected = match format {
+            SquareFormat::Jpeg => ImageFormat::Jpeg,
+            SquareFormat::Webp => ImageFormat::WebP,
+        };
+        assert_eq!(image::guess_format(&bytes).unwrap(), expected);
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+        assert_eq!(decoded.dimensions(), (16, 16));
+        decoded
+    }
+
+    fn close_to(pixel: &Rgb<u8>, expected: [u8; 3]) -> bool {
+        pixel
+            .0
+            .iter()
+            .zip(expected)
+            .all(|(channel, expected)| channel.abs_diff(expected) <= 24)
+    }
+
+    #[test]
+    fn crop_fills_the_square() {
+        for source in [png(40, 20), png(20, 40)] {
+            for format in [SquareFormat::Jpeg, SquareFormat::Webp] {
+                let squared = square(&source, SquareFit::Crop, format);
+                for (x, y) in [(0, 0), (15, 0), (0, 15), (15, 15), (8, 8)] {
+                    assert!(close_to(squared.get_pixel(x, y), GREEN), "({x}, {y})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pad_letterboxes_wide_images() {
+        for format in [SquareFormat::Jpeg, SquareFormat::Webp] {
+            let squared = square(&png(40, 20), SquareFit::Pad, format);
+            // Scaled to 16 × 8, with four rows of padding above and below.
+            assert!(close_to(squared.get_pixel(8, 0), WHITE));
+            assert!(close_to(squared.get_pixel(8, 15), WHITE));
+            assert!(close_to(squared.get_pixel(0, 8), GREEN));
+            assert!(close_to(squared.get_pixel(15, 8), GREEN));
+        }
+    }
+
+    #[test]
+    fn pad_pillarboxes_tall_images() {
+        for format in [SquareFormat::Jpeg, SquareFormat::Webp] {
+            let squared = square(&png(20, 40), SquareFit::Pad, format);
+            assert!(close_to(squared.get_pixel(0, 8), WHITE));
+            assert!(close_to(squared.get_pixel(15, 8), WHITE));
+            assert!(close_to(squared.get_pixel(8, 0), GREEN));
+            assert!(close_to(squared.get_pixel(8, 15), GREEN));
+        }
+    }
+
+    #[test]
+    fn undecodable_covers_are_an_error() {
+        let settings = settings(SquareFit::Crop, SquareFormat::Jpeg);
+        assert!(square_cover(b"not an image", &settings).is_err());
+    }
+}
@@ -1,29 +1,43 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, anyhow, bail};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::task;
 
-use crate::api::musicbrainz::{MusicBrainzClient, MusicBrainzError};
+use crate::api::MetadataProvider;
+use crate::api::musicbrainz::MusicBrainzError;
+use crate::api::search::ReleaseQuery;
 use crate::app::AppMessage;
 use crate::library::LibraryStore;
-use crate::models::{Album, AlbumRecord, Artist, CoverArtStatus};
-use crate::notes::NoteService;
+use crate::models::{
+    Album, AlbumRecord, Artist, ArtistRecord, CoverArtStatus, DiscographyFilter, Release,
+};
+use crate::notes::{NoteMode, NotePlan, NoteService};
 use crate::tasks::cover_art::CoverArtDownloaderHandle;
 
 #[derive(Clone)]
 pub struct AppController {
-    client: MusicBrainzClient,
+    client: Arc<dyn MetadataProvider>,
     library: LibraryStore,
     downloader: CoverArtDownloaderHandle,
     notes: NoteService,
+    /// The configured discography filter, which the Albums pane starts from.
+    discography: DiscographyFilter,
+    /// Disambiguations of artists whose albums were browsed, by artist MBID.
+    /// Search results are the only place they show up, so they are kept until
+    /// one of the artist's albums is added and the artist is saved.
+    browsed_artists: Arc<Mutex<HashMap<String, String>>>,
     message_tx: UnboundedSender<AppMessage>,
 }
 
 impl AppController {
     pub fn new(
-        client: MusicBrainzClient,
+        client: Arc<dyn MetadataProvider>,
         library: LibraryStore,
         downloader: CoverArtDownloaderHandle,
         notes: NoteService,
+        discography: DiscographyFilter,
         message_tx: UnboundedSender<AppMessage>,
     ) -> Self {
         Self {
@@ -31,10 +45,16 @@ impl AppController {
             library,
             downloader,
             notes,
+            discography,
+            browsed_artists: Arc::default(),
             message_tx,
         }
     }
 
+    pub fn default_discography(&self) -> &DiscographyFilter {
+        &self.discography
+    }
+
     pub fn load_library(&self) -> Result<Vec<AlbumRecord>> {
         self.library.all_albums()
     }
@@ -44,59 +64,215 @@ impl AppController {
             return;
         }
 
-        let client = self.client.clone();
-        let tx = self.message_tx.clone();
+        let controller = self.clone();
         task::spawn(async move {
-            match client.search_artists(&query).await {
-                Ok(artists) => {
-                    let _ = tx.send(AppMessage::ArtistResults(artists));
+            let message = match controller.find_artists(&query).await {
+                Ok(artists) => AppMessage::ArtistResults(artists),
+                Err(MusicBrainzError::Empty) => {
+                    AppMessage::SearchFailed(format!("No artists found for '{query}'"))
                 }
+                Err(err) => AppMessage::SearchFailed(format!(
+                    "Artist search failed: {}",
+                    err.user_message()
+                )),
+            };
+            let _ = controller.message_tx.send(message);
+        });
+    }
+
+    /// Searches MusicBrainz for artists matching `query`.
+    pub async fn find_artists(&self, query: &str) -> Result<Vec<Artist>, MusicBrainzError> {
+        self.client.search_artists(query).await
+    }
+
+    /// Searches release groups in the background; results go to the Albums
+    /// pane, from where they are added like an artist's albums.
+    pub fn search_albums(&self, text: String) {
+        let query = ReleaseQuery::parse(&text);
+        if query.is_empty() {
+            return;
+        }
+
+        let controller = self.clone();
+        task::spawn(async move {
+            let message = match controller.find_release_groups(&query).await {
+                Ok(albums) => AppMessage::AlbumSearchResults {
+                    query: text,
+                    albums,
+                },
                 Err(MusicBrainzError::Empty) => {
-                    let _ = tx.send(AppMessage::SearchFailed(format!(
-                        "No artists found for '{query}'"
-                    )));
+                    AppMessage::SearchFailed(format!("No albums found for '{text}'"))
                 }
                 Err(err) => {
-                    let _ = tx.send(AppMessage::SearchFailed(format!(
-                        "Artist search failed: {err}"
-                    )));
+                    AppMessage::SearchFailed(format!("Album search failed: {}", err.user_message()))
                 }
-            }
+            };
+            let _ = controller.message_tx.send(message);
         });
     }
 
-    pub fn load_albums_for_artist(&self, artist: Artist) {
-        let client = self.client.clone();
-        let tx = self.message_tx.clone();
-        let artist_id = artist.id.clone();
-        let fallback_name = artist.display_name();
+    pub async fn find_release_groups(
+        &self,
+        query: &ReleaseQuery,
+    ) -> Result<Vec<Album>, MusicBrainzError> {
+        self.client.search_release_groups(query).await
+    }
+
+    /// Searches releases in the background; results open the release picker.
+    pub fn search_releases(&self, text: String) {
+        let query = ReleaseQuery::parse(&text);
+        if query.is_empty() {
+            return;
+        }
 
+        let controller = self.clone();
         task::spawn(async move {
-            match client.albums_for_artist(&artist_id).await {
-                Ok(mut albums) => {
-                    for album in &mut albums {
-                        if album.artist.is_empty() {
-                            album.artist = fallback_name.clone();
-                        }
-                    }
+            let message = match controller.find_releases_matching(&query).await {
+                Ok(releases) => AppMessage::ReleaseSearchResults {
+                    query: text,
+                    releases,
+                },
+                Err(MusicBrainzError::Empty) => {
+                    AppMessage::SearchFailed(format!("No releases found for '{text}'"))
+                }
+                Err(err) => AppMessage::SearchFailed(format!(
+                    "Release search failed: {}",
+                    err.user_message()
+                )),
+            };
+            let _ = controller.message_tx.send(message);
+        });
+    }
 
-                    let _ = tx.send(AppMessage::AlbumsLoaded(albums));
+    pub async fn find_releases_matching(
+        &self,
+        query: &ReleaseQuery,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        self.client.search_releases(query).await
+    }
+
+    /// Looks up the release with a barcode or catalog number in the
+    /// background. A single match is added to the library; several open the
+    /// release picker to choose from.
+    pub fn add_album_by_code(&self, code: String) {
+        let controller = self.clone();
+
+        task::spawn(async move {
+            match controller.find_releases_by_code(&code).await {
+                Ok(releases) if releases.len() == 1 => {
+                    if let Err(err) = controller.add_by_id(&releases[0].id).await {
+                        controller.log(err.to_string());
+                    }
                 }
-                Err(MusicBrainzError::Empty) => {
-                    let _ = tx.send(AppMessage::SearchFailed(format!(
-                        "No albums found for {}",
-                        fallback_name
-                    )));
+                Ok(releases) => {
+                    let _ = controller
+                        .message_tx
+                        .send(AppMessage::ReleaseSearchResults {
+                            query: code,
+                            releases,
+                        });
                 }
+                Err(MusicBrainzError::Empty) => controller.log(format!(
+                    "No release found with barcode or catalog number '{code}'"
+                )),
                 Err(err) => {
-                    let _ = tx.send(AppMessage::SearchFailed(format!(
-                        "Album fetch failed: {err}"
-                    )));
+                    controller.log(format!("Lookup of '{code}' failed: {}", err.user_message()))
                 }
             }
         });
     }
 
+    /// Releases whose barcode, or failing that catalog number, is `code`.
+    pub async fn find_releases_by_code(
+        &self,
+        code: &str,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        let mut found = Err(MusicBrainzError::Empty);
+        for query in ReleaseQuery::for_code(code) {
+            found = self.client.search_releases(&query).await;
+            if !matches!(found, Err(MusicBrainzError::Empty)) {
+                break;
+            }
+        }
+        found
+    }
+
+    pub fn load_albums_for_artist(&self, artist: Artist, filter: DiscographyFilter) {
+        let controller = self.clone();
+
+        task::spawn(async move {
+            let message = match controller.find_albums(&artist, &filter).await {
+                Ok(albums) => AppMessage::AlbumsLoaded(albums),
+                Err(MusicBrainzError::Empty) => AppMessage::SearchFailed(format!(
+                    "No albums found for {} ({})",
+                    artist.display_name(),
+                    filter.summary()
+                )),
+                Err(err) => {
+                    AppMessage::SearchFailed(format!("Album fetch failed: {}", err.user_message()))
+                }
+            };
+            let _ = controller.message_tx.send(message);
+        });
+    }
+
+    /// Lists the artist's release groups that pass `filter`, credited to the
+    /// artist's display name until full metadata is fetched.
+    pub async fn find_albums(
+        &self,
+        artist: &Artist,
+        filter: &DiscographyFilter,
+    ) -> Result<Vec<Album>, MusicBrainzError> {
+        let mut albums = self.client.albums_for_artist(&artist.id, filter).await?;
+        let fallback_name = artist.display_name();
+
+        if !artist.disambiguation.is_empty()
+            && let Ok(mut browsed) = self.browsed_artists.lock()
+        {
+            browsed.insert(artist.id.clone(), artist.disambiguation.clone());
+        }
+
+        for album in &mut albums {
+            if album.artist.is_empty() {
+                album.artist = fallback_name.clone();
+            }
+        }
+
+        Ok(albums)
+    }
+
+    /// Loads the editions of a release group in the background and sends them
+    /// as [`AppMessage::ReleasesLoaded`] for the release picker.
+    pub fn load_releases(&self, release_group_id: String, title: String) {
+        let controller = self.clone();
+
+        task::spawn(async move {
+            let message = match controller.find_releases(&release_group_id).await {
+                Ok(releases) => AppMessage::ReleasesLoaded {
+                    release_group_id,
+                    title,
+                    releases,
+                },
+                Err(MusicBrainzError::Empty) => {
+                    AppMessage::SearchFailed(format!("No releases found for {title}"))
+                }
+                Err(err) => AppMessage::SearchFailed(format!(
+                    "Release fetch failed: {}",
+                    err.user_message()
+                )),
+            };
+            let _ = controller.message_tx.send(message);
+        });
+    }
+
+    /// Lists every release (edition) of a release group, oldest first.
+    pub async fn find_releases(
+        &self,
+        release_group_id: &str,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        self.client.releases_for_group(release_group_id).await
+    }
+
     pub fn add_albums(&self, albums: Vec<Album>) -> Result<()> {
         if albums.is_empty() {
             return Ok(());
@@ -109,12 +285,13 @@ impl AppController {
 
             if existing.is_none() {
                 // Add minimal record immediately
-                let mut record = AlbumRecord::from_album(&album);
+                let mut record = AlbumRecord::from_album(&album, self.downloader.base_url());
                 record.cover_art_status = CoverArtStatus::Pending;
                 self.library.upsert_album(record.clone())?;
                 added_any = true;
 
                 // Fetch full details in background
+                let controller = self.clone();
                 let client = self.client.clone();
                 let library = self.library.clone();
                 let downloader = self.downloader.clone();
@@ -129,10 +306,14 @@ impl AppController {
 
                     match client.fetch_album_details(&release_group_id).await {
                         Ok(full_album) => {
-                            let mut full_record = AlbumRecord::from_album(&full_album);
+                            let mut full_record =
+                                AlbumRecord::from_album(&full_album, downloader.base_url());
                             full_record.cover_art_status = CoverArtStatus::Queued;
 
-                            if let Err(err) = library.upsert_album(full_record.clone()) {
+                            if let Err(err) = library
+                                .upsert_album(full_record.clone())
+                                .and_then(|_| controller.save_artists(&full_record))
+                            {
                                 let _ = tx.send(AppMessage::DownloadLog(format!(
                                     "Failed to save metadata for {}: {err}",
                                     full_record.title
@@ -146,11 +327,8 @@ impl AppController {
                             )));
 
                             // Queue cover art download
-                            if let Err(err) = downloader.enqueue(full_record.clone()) {
-                                let _ = tx.send(AppMessage::DownloadLog(format!(
-                                    "Failed to queue cover art for {}: {err}",
-                                    full_record.title
-                                )));
+                            if let Err(err) = controller.enqueue_cover_art(&mut full_record) {
+                                let _ = tx.send(AppMessage::DownloadLog(format!("{err:#}")));
                             }
 
                             // Refresh library view
@@ -160,8 +338,9 @@ impl AppController {
                         }
                         Err(err) => {
                             let _ = tx.send(AppMessage::DownloadLog(format!(
-                                "Failed to fetch metadata for {}: {err}",
-                                record.title
+                                "Failed to fetch metadata for {}: {}",
+                                record.title,
+                                err.user_message()
                             )));
                         }
                     }
@@ -177,189 +356,586 @@ impl AppController {
         Ok(())
     }
 
-    pub fn generate_notes(&self, records: Vec<AlbumRecord>) {
+    /// Library albums with metadata that `mode` would write: those without a
+    /// note when creating, every album when syncing.
+    pub fn note_candidates(&self, mode: NoteMode) -> Result<Vec<AlbumRecord>> {
+        Ok(self
+            .library
+            .all_albums()?
+            .into_iter()
+            .filter(|record| {
+                !record.artist.is_empty() && (mode == NoteMode::Sync || record.note_path.is_none())
+            })
+            .collect())
+    }
+
+    /// Renders notes for `records` in the background and sends the plans back
+    /// as [`AppMessage::NotePreview`] for confirmation.
+    pub fn preview_notes(&self, records: Vec<AlbumRecord>, mode: NoteMode) {
         if records.is_empty() {
             return;
         }
 
-        let notes = self.notes.clone();
-        let tx = self.message_tx.clone();
+        let controller = self.clone();
 
         task::spawn(async move {
-            let outcome = task::spawn_blocking(move || notes.generate_notes(&records)).await;
+            let message = match controller.plan_notes(records, mode, None).await {
+                Ok(plans) => AppMessage::NotePreview(plans),
+                Err(err) => AppMessage::DownloadLog(format!("Note generation failed: {err}")),
+            };
+            let _ = controller.message_tx.send(message);
+        });
+    }
 
-            match outcome {
-                Ok(Ok(logs)) => {
-                    let _ = tx.send(AppMessage::NotesGenerated(logs));
-                }
-                Ok(Err(err)) => {
-                    let _ = tx.send(AppMessage::DownloadLog(format!(
-                        "Note generation failed: {err}"
-                    )));
-                }
-                Err(join_err) => {
-                    let _ = tx.send(AppMessage::DownloadLog(format!(
-                        "Note generation task panicked: {join_err}"
-                    )));
-                }
-            }
+    /// Writes previously previewed notes in the background.
+    pub fn apply_notes(&self, plans: Vec<NotePlan>) {
+        let controller = self.clone();
+
+        task::spawn(async move {
+            let message = match controller.write_notes(plans).await {
+                Ok(logs) => AppMessage::NotesGenerated(logs),
+                Err(err) => AppMessage::DownloadLog(format!("Note generation failed: {err}")),
+            };
+            let _ = controller.message_tx.send(message);
         });
     }
 
-    pub fn add_album_by_release_id(&self, id: String) {
-        let id = id.trim().to_string();
-        
-        if id.is_empty() {
-            return;
+    /// Renders notes for `records` on a blocking thread without writing them.
+    /// `template` forces one template for every album instead of each album's own.
+    pub async fn plan_notes(
+        &self,
+        records: Vec<AlbumRecord>,
+        mode: NoteMode,
+        template: Option<String>,
+    ) -> Result<Vec<NotePlan>> {
+        let notes = self.notes.clone();
+        task::spawn_blocking(move || notes.plan_notes(&records, mode, template.as_deref()))
+            .await
+            .context("Note generation task panicked")?
+    }
+
+    /// Writes planned notes on a blocking thread and returns the log lines.
+    pub async fn write_notes(&self, plans: Vec<NotePlan>) -> Result<Vec<String>> {
+        let notes = self.notes.clone();
+        task::spawn_blocking(move || notes.write_notes(&plans))
+            .await
+            .context("Note generation task panicked")?
+    }
+
+    /// The template `record` is rendered with unless one is picked explicitly.
+    pub fn template_for<'a>(&'a self, record: &'a AlbumRecord) -> &'a str {
+        self.notes.template_for(record)
+    }
+
+    pub fn available_templates(&self) -> Result<Vec<String>> {
+        self.notes.available_templates()
+    }
+
+    /// Sets or clears (`None`) the template override for an album.
+    pub fn set_template(&self, mbid: &str, template: Option<String>) -> Result<AlbumRecord> {
+        if let Some(name) = &template {
+            let available = self.available_templates()?;
+            if !available.contains(name) {
+                bail!(
+                    "Unknown note template `{name}` (available: {})",
+                    available.join(", ")
+                );
+            }
         }
 
-        // Validate UUID format (basic check)
-        if id.len() != 36 || id.chars().filter(|c| *c == '-').count() != 4 {
-            let _ = self.message_tx.send(AppMessage::DownloadLog(format!(
-                "Invalid ID format: {} (expected UUID format like 'xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx')",
-                id
-            )));
-            return;
+        self.library.set_template(mbid, template)?;
+        let record = self
+            .library
+            .get_album(mbid)?
+            .ok_or_else(|| anyhow!("Album {mbid} is not in the library"))?;
+
+        if let Ok(all) = self.library.all_albums() {
+            let _ = self.message_tx.send(AppMessage::LibraryRefreshed(all));
+        }
+        Ok(record)
+    }
+
+    /// Moves an album's override to the next available template, wrapping
+    /// back to the configured rules after the last one.
+    pub fn cycle_template(&self, record: &AlbumRecord) -> Result<()> {
+        let available = self.available_templates()?;
+        let next = match &record.template {
+            None => available.first().cloned(),
+            Some(current) => available
+                .iter()
+                .position(|name| name == current)
+                .and_then(|idx| available.get(idx + 1))
+                .cloned(),
+        };
+
+        let record = self.set_template(&record.mbid, next)?;
+        self.log(format!(
+            "Template for {} - {}: {}",
+            record.artist,
+            record.title,
+            match &record.template {
+                Some(name) => name.clone(),
+                None => format!("{} (from rules)", self.template_for(&record)),
+            }
+        ));
+        Ok(())
+    }
+
+    /// Turns the image gallery on or off for an album. Enabling it queues a
+    /// fresh download so every image in the archive's listing is fetched.
+    pub fn set_gallery(&self, mbid: &str, enabled: bool) -> Result<AlbumRecord> {
+        self.library.set_gallery(mbid, enabled)?;
+        let record = self
+            .library
+            .get_album(mbid)?
+            .ok_or_else(|| anyhow!("Album {mbid} is not in the library"))?;
+
+        if enabled {
+            self.queue_cover_art(vec![record.clone()])?;
+        }
+        if let Ok(all) = self.library.all_albums() {
+            let _ = self.message_tx.send(AppMessage::LibraryRefreshed(all));
+        }
+        Ok(record)
+    }
+
+    pub fn toggle_gallery(&self, record: &AlbumRecord) -> Result<()> {
+        let record = self.set_gallery(&record.mbid, !record.gallery)?;
+        self.log(format!(
+            "Gallery for {} - {}: {}",
+            record.artist,
+            record.title,
+            if record.gallery {
+                "on, downloading images"
+            } else {
+                "off"
+            }
+        ));
+        Ok(())
+    }
+
+    /// Queues cover art downloads for `records`, returning how many were queued.
+    pub fn queue_cover_art(&self, records: Vec<AlbumRecord>) -> Result<usize> {
+        let mut queued = 0;
+        for mut record in records {
+            record.cover_art_status = CoverArtStatus::Queued;
+            self.library.set_cover_art_path(
+                &record.mbid,
+                record.cover_art_path.clone(),
+                CoverArtStatus::Queued,
+            )?;
+            self.enqueue_cover_art(&mut record)?;
+            queued += 1;
         }
+        Ok(queued)
+    }
 
-        let client = self.client.clone();
-        let library = self.library.clone();
-        let downloader = self.downloader.clone();
-        let tx = self.message_tx.clone();
+    pub fn add_album_by_release_id(&self, id: String) {
+        let controller = self.clone();
 
         task::spawn(async move {
-            let _ = tx.send(AppMessage::DownloadLog(format!(
-                "Fetching {}...",
-                id
-            )));
-
-            // Try as release ID first
-            match client.fetch_album_by_release_id(&id).await {
-                Ok(album) => {
-                    Self::process_fetched_album(album, library, downloader, tx).await;
-                    return;
-                }
-                Err(err) => {
-                    let err_str = err.to_string();
-                    if err_str.contains("404") {
-                        // Not found as release, try as release-group
-                        let _ = tx.send(AppMessage::DownloadLog(format!(
-                            "Not a release ID, trying as release-group ID..."
-                        )));
-                        
-                        match client.fetch_album_details(&id).await {
-                            Ok(album) => {
-                                Self::process_fetched_album(album, library, downloader, tx).await;
-                                return;
-                            }
-                            Err(rg_err) => {
-                                let _ = tx.send(AppMessage::DownloadLog(format!(
-                                    "ID not found as release or release-group: {} (check the ID is correct)",
-                                    id
-                                )));
-                                return;
-                            }
-                        }
-                    } else if err_str.contains("503") {
-                        let _ = tx.send(AppMessage::DownloadLog(
-                            "MusicBrainz service unavailable (rate limited). Wait a moment and try again.".to_string()
-                        ));
-                        return;
-                    } else {
-                        let _ = tx.send(AppMessage::DownloadLog(format!(
-                            "Failed to fetch: {}",
-                            err
-                        )));
-                        return;
+            if let Err(err) = controller.add_by_id(&id).await {
+                let _ = controller
+                    .message_tx
+                    .send(AppMessage::DownloadLog(err.to_string()));
+            }
+        });
+    }
+
+    /// Adds the album identified by a release or release-group MBID, updating
+    /// the library entry if the release group is already present.
+    pub async fn add_by_id(&self, id: &str) -> Result<AlbumRecord> {
+        let id = id.trim();
+
+        // Validate UUID format (basic check)
+        if id.len() != 36 || id.chars().filter(|c| *c == '-').count() != 4 {
+            bail!(
+                "Invalid ID format: {id} (expected UUID format like 'xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx')"
+            );
+        }
+
+        self.log(format!("Fetching {id}..."));
+
+        // Try as release ID first
+        let album = match self.client.fetch_album_by_release_id(id).await {
+            Ok(album) => album,
+            Err(MusicBrainzError::NotFound) => {
+                // Not found as release, try as release-group
+                self.log("Not a release ID, trying as release-group ID...");
+
+                match self.client.fetch_album_details(id).await {
+                    Ok(album) => album,
+                    Err(MusicBrainzError::NotFound) => bail!(
+                        "ID not found as release or release-group: {id} (check the ID is correct)"
+                    ),
+                    Err(MusicBrainzError::Empty) => {
+                        bail!("Release group {id} has no official releases to take metadata from")
                     }
+                    Err(err) => bail!("Failed to fetch {id}: {}", err.user_message()),
                 }
             }
-        });
+            Err(err) => bail!("Failed to fetch {id}: {}", err.user_message()),
+        };
+
+        self.process_fetched_album(album)
     }
 
-    async fn process_fetched_album(
-        album: crate::models::Album,
-        library: LibraryStore,
-        downloader: CoverArtDownloaderHandle,
-        tx: UnboundedSender<AppMessage>,
-    ) {
-        match library.get_album(&album.id) {
-            Ok(Some(existing)) => {
+    fn process_fetched_album(&self, album: Album) -> Result<AlbumRecord> {
+        let library = &self.library;
+        let mut record = AlbumRecord::from_album(&album, self.downloader.base_url());
+
+        let requeue = match library.get_album(&album.id).context("Database error")? {
+            Some(existing) => {
                 // Album exists - update it with new release info if different
-                let mut record = AlbumRecord::from_album(&album);
-                
-                // Preserve existing cover art and note status if already processed
-                if existing.cover_art_status == CoverArtStatus::Completed {
-                    record.cover_art_status = existing.cover_art_status;
-                    record.cover_art_path = existing.cover_art_path;
-                } else {
+                // Preserve existing cover art and note status if already processed,
+                // unless a different release (and so different cover) was picked
+                let requeue = existing.cover_art_status != CoverArtStatus::Completed
+                    || existing.cover_art_url != record.cover_art_url;
+                if requeue {
                     // Re-queue cover art download with new release ID
                     record.cover_art_status = CoverArtStatus::Queued;
+                } else {
+                    record.cover_art_status = existing.cover_art_status;
+                    record.cover_art_path = existing.cover_art_path;
+                    record.cover_art_source = existing.cover_art_source;
+                    record.cover_art_image = existing.cover_art_image;
+                    record.gallery_images = existing.gallery_images;
                 }
-                
+
                 record.note_path = existing.note_path;
                 record.note_status = existing.note_status;
+                record.template = existing.template;
+                record.gallery = existing.gallery;
 
-                if let Err(err) = library.upsert_album(record.clone()) {
-                    let _ = tx.send(AppMessage::DownloadLog(format!(
-                        "Failed to update album: {err}"
-                    )));
-                    return;
-                }
-
-                let _ = tx.send(AppMessage::DownloadLog(format!(
+                library
+                    .upsert_album(record.clone())
+                    .context("Failed to update album")?;
+                self.log(format!(
                     "Updated album in library: {} - {}",
                     record.artist, record.title
-                )));
-
-                // Re-queue cover art if it wasn't completed
-                if existing.cover_art_status != CoverArtStatus::Completed {
-                    if let Err(err) = downloader.enqueue(record.clone()) {
-                        let _ = tx.send(AppMessage::DownloadLog(format!(
-                            "Failed to queue cover art: {err}"
-                        )));
-                    }
-                }
-
-                // Refresh library view
-                if let Ok(all) = library.all_albums() {
-                    let _ = tx.send(AppMessage::LibraryRefreshed(all));
-                }
+                ));
+                requeue
             }
-            Ok(None) => {
+            None => {
                 // Add new album to library
-                let mut record = AlbumRecord::from_album(&album);
                 record.cover_art_status = CoverArtStatus::Queued;
 
-                if let Err(err) = library.upsert_album(record.clone()) {
-                    let _ = tx.send(AppMessage::DownloadLog(format!(
-                        "Failed to save album: {err}"
-                    )));
-                    return;
-                }
-
-                let _ = tx.send(AppMessage::DownloadLog(format!(
+                library
+                    .upsert_album(record.clone())
+                    .context("Failed to save album")?;
+                self.log(format!(
                     "Added to library: {} - {}",
                     record.artist, record.title
-                )));
+                ));
+                true
+            }
+        };
 
-                // Queue cover art download
-                if let Err(err) = downloader.enqueue(record.clone()) {
-                    let _ = tx.send(AppMessage::DownloadLog(format!(
-                        "Failed to queue cover art: {err}"
-                    )));
-                }
+        self.save_artists(&record)?;
+
+        // Queue cover art download
+        let queued = if requeue {
+            self.enqueue_cover_art(&mut record)
+        } else {
+            Ok(())
+        };
+
+        // Refresh library view
+        if let Ok(all) = library.all_albums() {
+            let _ = self.message_tx.send(AppMessage::LibraryRefreshed(all));
+        }
+
+        queued?;
+        Ok(record)
+    }
+
+    /// Hands `record` to the cover art downloader. A job that never reaches
+    /// the downloader must not stay Queued, or anything waiting on it would
+    /// wait forever, so on failure the record goes back to Pending.
+    fn enqueue_cover_art(&self, record: &mut AlbumRecord) -> Result<()> {
+        let Err(err) = self.downloader.enqueue(record.clone()) else {
+            return Ok(());
+        };
+        record.cover_art_status = CoverArtStatus::Pending;
+        self.library
+            .set_cover_art_path(
+                &record.mbid,
+                record.cover_art_path.clone(),
+                CoverArtStatus::Pending,
+            )
+            .context("Failed to update album")?;
+        Err(err).with_context(|| {
+            format!(
+                "Saved {} - {} but could not queue its cover art",
+                record.artist, record.title
+            )
+        })
+    }
+
+    /// Saves every artist credited on `record`, with the disambiguation seen
+    /// when their albums were browsed.
+    fn save_artists(&self, record: &AlbumRecord) -> Result<()> {
+        for mut artist in artists_of(record) {
+            if let Ok(browsed) = self.browsed_artists.lock()
+                && let Some(disambiguation) = browsed.get(&artist.id)
+            {
+                artist.disambiguation = disambiguation.clone();
+            }
+            self.library
+                .upsert_artist(artist)
+                .context("Failed to save artist")?;
+        }
+        Ok(())
+    }
+
+    fn log<S: Into<String>>(&self, message: S) {
+        let _ = self
+            .message_tx
+            .send(AppMessage::DownloadLog(message.into()));
+    }
+}
+
+#[cfg(test)]
+impl AppController {
+    /// A controller over a fresh library in `dir`, with the receiving end of
+    /// its messages.
+    pub fn in_dir(
+        dir: &std::path::Path,
+        client: Arc<dyn MetadataProvider>,
+        downloader: CoverArtDownloaderHandle,
+    ) -> (Self, tokio::sync::mpsc::UnboundedReceiver<AppMessage>) {
+        let config = crate::config::AppConfig::in_dir(dir);
+        let library = LibraryStore::open(&config).unwrap();
+        let notes = NoteService::new(config.clone(), library.clone());
+        let (message_tx, message_rx) = tokio::sync::mpsc::unbounded_channel();
+        let controller = Self::new(
+            client,
+            library,
+            downloader,
+            notes,
+            config.discography().clone(),
+            message_tx,
+        );
+        (controller, message_rx)
+    }
+}
+
+/// Every artist credited on a fully fetched album.
+fn artists_of(record: &AlbumRecord) -> Vec<ArtistRecord> {
+    record
+        .artist_credits()
+        .iter()
+        .map(|credit| ArtistRecord::new(&credit.artist_id, &credit.name, ""))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fixtures::StubProvider;
+    use std::time::Duration;
+
+    use crate::models::ArtistCredit;
+
+    const CAA: &str = "https://coverartarchive.org";
+
+    fn release(id: &str, barcode: &str, catalog_number: &str) -> Release {
+        Release {
+            id: id.to_string(),
+            barcode: barcode.to_string(),
+            catalog_number: catalog_number.to_string(),
+            ..Release::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn numeric_codes_fall_back_to_catalog_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = StubProvider {
+            releases: vec![
+                release("3a3aca1e-ae9b-4b35-9b7b-c2d6c5f2b4f3", "042282855329", ""),
+                release("0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b", "", "74321 45678"),
+            ],
+            ..StubProvider::default()
+        };
+        let (controller, _messages) = AppController::in_dir(
+            dir.path(),
+            Arc::new(provider),
+            CoverArtDownloaderHandle::idle(CAA),
+        );
+
+        let by_barcode = controller.find_releases_by_code("0 42282 85532 9").await;
+        assert_eq!(
+            by_barcode.unwrap()[0].id,
+            "3a3aca1e-ae9b-4b35-9b7b-c2d6c5f2b4f3"
+        );
+        let by_catno = controller.find_releases_by_code("74321 45678").await;
+        assert_eq!(
+            by_catno.unwrap()[0].id,
+            "0f1f2c4e-5d0b-4b8e-9a43-1c2d3e4f5a6b"
+        );
+        let unknown = controller.find_releases_by_code("SOMETHING-1").await;
+        assert!(matches!(unknown, Err(MusicBrainzError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn browsed_albums_are_credited_to_the_artist() {
+        let dir = tempfile::tempdir().unwrap();
+        let artist = Artist {
+            id: String::from("8f6bd1e4-fbe1-4f50-aa9b-94c450ec0f11"),
+            name: String::from("Portishead"),
+            ..Artist::default()
+        };
+        let provider = StubProvider {
+            albums: vec![Album {
+                id: String::from("76df3287-6cda-33eb-8e9a-044b5e15ffdd"),
+                title: String::from("Dummy"),
+                artist_id: artist.id.clone(),
+                primary_type: String::from("Album"),
+                ..Album::default()
+            }],
+            ..StubProvider::default()
+        };
+        let (controller, _messages) = AppController::in_dir(
+            dir.path(),
+            Arc::new(provider),
+            CoverArtDownloaderHandle::idle(CAA),
+        );
+
+        let albums = controller
+            .find_albums(&artist, controller.default_discography())
+            .await
+            .unwrap();
+
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].artist, "Portishead");
+    }
 
-                // Refresh library view
-                if let Ok(all) = library.all_albums() {
-                    let _ = tx.send(AppMessage::LibraryRefreshed(all));
+    /// An artist with a disambiguation and one album credited to them.
+    fn portishead() -> (Artist, Album) {
+        let artist = Artist {
+            id: String::from("8f6bd1e4-fbe1-4f50-aa9b-94c450ec0f11"),
+            name: String::from("Portishead"),
+            disambiguation: String::from("Bristol trip hop band"),
+            ..Artist::default()
+        };
+        let album = Album {
+            id: String::from("76df3287-6cda-33eb-8e9a-044b5e15ffdd"),
+            title: String::from("Dummy"),
+            artist: artist.name.clone(),
+            artist_id: artist.id.clone(),
+            credits: vec![ArtistCredit {
+                artist_id: artist.id.clone(),
+                name: artist.name.clone(),
+                ..ArtistCredit::default()
+            }],
+            primary_type: String::from("Album"),
+            ..Album::default()
+        };
+        (artist, album)
+    }
+
+    fn browsing_controller(dir: &std::path::Path, album: &Album) -> AppController {
+        let provider = StubProvider {
+            albums: vec![album.clone()],
+            ..StubProvider::default()
+        };
+        let (controller, _messages) =
+            AppController::in_dir(dir, Arc::new(provider), CoverArtDownloaderHandle::idle(CAA));
+        controller
+    }
+
+    #[tokio::test]
+    async fn artists_are_saved_when_an_album_is_added_not_when_browsed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (artist, album) = portishead();
+        let controller = browsing_controller(dir.path(), &album);
+
+        controller
+            .find_albums(&artist, controller.default_discography())
+            .await
+            .unwrap();
+        assert!(controller.library.get_artist(&artist.id).unwrap().is_none());
+
+        controller.add_by_id(&album.id).await.unwrap();
+        let saved = controller.library.get_artist(&artist.id).unwrap().unwrap();
+        assert_eq!(saved.name, "Portishead");
+        assert_eq!(saved.disambiguation, "Bristol trip hop band");
+    }
+
+    #[tokio::test]
+    async fn albums_added_from_the_albums_pane_save_the_browsed_artist() {
+        let dir = tempfile::tempdir().unwrap();
+        let (artist, album) = portishead();
+        let controller = browsing_controller(dir.path(), &album);
+
+        let browsed = controller
+            .find_albums(&artist, controller.default_discography())
+            .await
+            .unwrap();
+        controller.add_albums(browsed).unwrap();
+
+        let saved = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(saved) = controller.library.get_artist(&artist.id).unwrap() {
+                    return saved;
                 }
+                tokio::time::sleep(Duration::from_millis(10)).await;
             }
-            Err(err) => {
-                let _ = tx.send(AppMessage::DownloadLog(format!(
-                    "Database error: {err}"
-                )));
+        })
+        .await
+        .expect("the background fetch should save the artist");
+        assert_eq!(saved.disambiguation, "Bristol trip hop band");
+    }
+
+    #[tokio::test]
+    async fn albums_pane_adds_reset_cover_art_that_cannot_be_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, album) = portishead();
+        let provider = StubProvider {
+            albums: vec![album.clone()],
+            ..StubProvider::default()
+        };
+        let (controller, mut messages) = AppController::in_dir(
+            dir.path(),
+            Arc::new(provider),
+            CoverArtDownloaderHandle::stopped(CAA),
+        );
+
+        controller.add_albums(vec![album.clone()]).unwrap();
+
+        let failure = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match messages.recv().await {
+                    Some(AppMessage::DownloadLog(entry)) if entry.contains("cover art") => {
+                        return entry;
+                    }
+                    Some(_) => {}
+                    None => panic!("controller dropped its messages"),
+                }
             }
-        }
+        })
+        .await
+        .expect("the background fetch should report the queue failure");
+        assert!(failure.contains("could not queue its cover art"));
+        let record = controller.library.get_album(&album.id).unwrap().unwrap();
+        assert_eq!(record.cover_art_status, CoverArtStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn requeued_cover_art_is_reset_when_it_cannot_be_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, album) = portishead();
+        let (controller, _messages) = AppController::in_dir(
+            dir.path(),
+            Arc::new(StubProvider::default()),
+            CoverArtDownloaderHandle::stopped(CAA),
+        );
+        let record = AlbumRecord::from_album(&album, CAA);
+        controller.library.upsert_album(record.clone()).unwrap();
+
+        assert!(controller.queue_cover_art(vec![record]).is_err());
+        let record = controller.library.get_album(&album.id).unwrap().unwrap();
+        assert_eq!(record.cover_art_status, CoverArtStatus::Pending);
     }
 }
@@ -15,6 +15,9 @@ use futures::StreamExt;
 use ratatui::{Terminal, backend::CrosstermBackend};
 use tokio::time::interval;
 
+use crate::models::discography::PRIMARY_TYPES;
+use crate::notes::NoteMode;
+
 pub use controller::AppController;
 pub use state::{App, FocusArea};
 
@@ -68,29 +71,71 @@ fn handle_event(app: &mut App, event: Event) -> Result<()> {
 
 fn handle_key_event(app: &mut App, key: KeyEvent) -> Result<()> {
     match key.code {
-        KeyCode::Char('q') if app.focus != FocusArea::ManualAdd => {
+        KeyCode::Char('q')
+            if !matches!(
+                app.focus,
+                FocusArea::ManualAdd
+                    | FocusArea::CodeLookup
+                    | FocusArea::NotePreview
+                    | FocusArea::ReleasePicker
+                    | FocusArea::YearFilter
+            ) =>
+        {
             app.should_quit = true;
         }
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.should_quit = true;
         }
-        KeyCode::Char('m') if key.modifiers.contains(KeyModifiers::CONTROL) && app.focus != FocusArea::ManualAdd => {
+        KeyCode::Char('m')
+            if key.modifiers.contains(KeyModifiers::CONTROL)
+                && app.focus != FocusArea::ManualAdd =>
+        {
             // Ctrl+M to open manual add mode
             app.focus = FocusArea::ManualAdd;
             app.manual_add_input.clear();
         }
+        KeyCode::Char('b')
+            if key.modifiers.contains(KeyModifiers::CONTROL)
+                && app.focus != FocusArea::CodeLookup =>
+        {
+            app.focus = FocusArea::CodeLookup;
+            app.code_input.clear();
+        }
         KeyCode::Esc => {
-            if app.focus == FocusArea::ManualAdd {
+            if app.focus == FocusArea::NotePreview {
+                app.cancel_note_preview();
+            } else if app.focus == FocusArea::ReleasePicker {
+                app.close_release_picker();
+            } else if app.focus == FocusArea::ManualAdd {
                 app.focus = FocusArea::Library;
                 app.manual_add_input.clear();
+            } else if app.focus == FocusArea::CodeLookup {
+                app.focus = FocusArea::Library;
+                app.code_input.clear();
+            } else if app.focus == FocusArea::YearFilter {
+                app.focus = FocusArea::Albums;
             } else if app.focus == FocusArea::Search {
                 app.search_input.clear();
             } else if app.focus == FocusArea::Albums {
                 app.selected_album_ids.clear();
             }
         }
-        KeyCode::Tab if app.focus != FocusArea::ManualAdd => app.next_focus(),
-        KeyCode::BackTab if app.focus != FocusArea::ManualAdd => app.previous_focus(),
+        KeyCode::Tab
+            if !matches!(
+                app.focus,
+                FocusArea::ManualAdd | FocusArea::CodeLookup | FocusArea::YearFilter
+            ) =>
+        {
+            app.next_focus()
+        }
+        KeyCode::BackTab
+            if !matches!(
+                app.focus,
+                FocusArea::ManualAdd | FocusArea::CodeLookup | FocusArea::YearFilter
+            ) =>
+        {
+            app.previous_focus()
+        }
         _ => match app.focus {
             FocusArea::Search => handle_search_keys(app, key)?,
             FocusArea::Artists => handle_artists_keys(app, key),
@@ -98,6 +143,10 @@ fn handle_key_event(app: &mut App, key: KeyEvent) -> Result<()> {
             FocusArea::Library => handle_library_keys(app, key)?,
             FocusArea::Logs => {}
             FocusArea::ManualAdd => handle_manual_add_keys(app, key)?,
+            FocusArea::CodeLookup => handle_code_lookup_keys(app, key),
+            FocusArea::NotePreview => handle_note_preview_keys(app, key),
+            FocusArea::ReleasePicker => handle_release_picker_keys(app, key),
+            FocusArea::YearFilter => handle_year_filter_keys(app, key),
         },
     }
     Ok(())
@@ -105,18 +154,18 @@ fn handle_key_event(app: &mut App, key: KeyEvent) -> Result<()> {
 
 fn handle_search_keys(app: &mut App, key: KeyEvent) -> Result<()> {
     match key.code {
-        KeyCode::Enter => {
-            app.controller.search_artists(app.search_input.clone());
+        KeyCode::Enter => app.submit_search(),
+        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.search_mode = app.search_mode.next();
         }
         KeyCode::Backspace => {
             app.search_input.pop();
         }
-        KeyCode::Char(ch) => {
+        KeyCode::Char(ch)
             if !key.modifiers.contains(KeyModifiers::ALT)
-                && !key.modifiers.contains(KeyModifiers::CONTROL)
-            {
-                app.search_input.push(ch);
-            }
+                && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            app.search_input.push(ch);
         }
         _ => {}
     }
@@ -129,7 +178,7 @@ fn handle_artists_keys(app: &mut App, key: KeyEvent) {
         KeyCode::Down => app.move_artist_selection(1),
         KeyCode::Enter => {
             if let Some(artist) = app.selected_artist() {
-                app.controller.load_albums_for_artist(artist);
+                app.load_albums(artist);
             }
         }
         _ => {}
@@ -151,6 +200,29 @@ fn handle_albums_keys(app: &mut App, key: KeyEvent) -> Result<()> {
                 app.controller.add_albums(albums)?;
             }
         }
+        KeyCode::Char('r') => {
+            if let Some(album) = app.selected_album() {
+                app.open_release_picker(album.id, album.title);
+            }
+        }
+        KeyCode::Char(digit @ '1'..='5') => {
+            let primary_type = PRIMARY_TYPES[digit as usize - '1' as usize];
+            app.update_discography(|filter| filter.toggle_primary_type(primary_type));
+        }
+        KeyCode::Char(key @ ('l' | 'c' | 'x' | 's' | 'd')) => {
+            let secondary_type = match key {
+                'l' => "Live",
+                'c' => "Compilation",
+                'x' => "Remix",
+                's' => "Soundtrack",
+                _ => "Demo",
+            };
+            app.update_discography(|filter| filter.cycle_secondary_type(secondary_type));
+        }
+        KeyCode::Char('o') => {
+            app.update_discography(|filter| filter.official_only = !filter.official_only);
+        }
+        KeyCode::Char('y') => app.open_year_filter(),
         _ => {}
     }
     Ok(())
@@ -178,7 +250,50 @@ fn handle_library_keys(app: &mut App, key: KeyEvent) -> Result<()> {
                     app.push_log("No albums in library");
                 }
             } else {
-                app.controller.generate_notes(pending);
+                app.controller.preview_notes(pending, NoteMode::Create);
+            }
+        }
+        KeyCode::Char('u') => {
+            let albums: Vec<_> = app
+                .library
+                .iter()
+                .filter(|record| !record.artist.is_empty())
+                .cloned()
+                .collect();
+            if albums.is_empty() {
+                app.push_log("No albums with metadata to sync");
+            } else {
+                app.controller.preview_notes(albums, NoteMode::Sync);
+            }
+        }
+        KeyCode::Char('t') => {
+            if let Some(record) = app
+                .library_state
+                .selected()
+                .and_then(|idx| app.library.get(idx))
+                && let Err(err) = app.controller.cycle_template(record)
+            {
+                app.push_log(format!("Unable to change template: {err}"));
+            }
+        }
+        KeyCode::Char('i') => {
+            if let Some(record) = app
+                .library_state
+                .selected()
+                .and_then(|idx| app.library.get(idx))
+                && let Err(err) = app.controller.toggle_gallery(record)
+            {
+                app.push_log(format!("Unable to change gallery: {err}"));
+            }
+        }
+        KeyCode::Char('r') => {
+            if let Some(record) = app
+                .library_state
+                .selected()
+                .and_then(|idx| app.library.get(idx))
+            {
+                let (mbid, title) = (record.mbid.clone(), record.title.clone());
+                app.open_release_picker(mbid, title);
             }
         }
         _ => {}
@@ -186,6 +301,65 @@ fn handle_library_keys(app: &mut App, key: KeyEvent) -> Result<()> {
     Ok(())
 }
 
+fn handle_note_preview_keys(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Enter | KeyCode::Char('y') => app.confirm_note_preview(),
+        KeyCode::Char('n') => app.cancel_note_preview(),
+        KeyCode::Up => app.scroll_note_preview(-1),
+        KeyCode::Down => app.scroll_note_preview(1),
+        KeyCode::PageUp => app.scroll_note_preview(-10),
+        KeyCode::PageDown => app.scroll_note_preview(10),
+        _ => {}
+    }
+}
+
+fn handle_release_picker_keys(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Enter => app.confirm_release(),
+        KeyCode::Up => app.move_release_selection(-1),
+        KeyCode::Down => app.move_release_selection(1),
+        KeyCode::PageUp => app.move_release_selection(-10),
+        KeyCode::PageDown => app.move_release_selection(10),
+        _ => {}
+    }
+}
+
+fn handle_year_filter_keys(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Enter => app.confirm_year_filter(),
+        KeyCode::Backspace => {
+            app.year_filter_input.pop();
+        }
+        KeyCode::Char(ch) if ch.is_ascii_digit() || ch == '-' => {
+            app.year_filter_input.push(ch);
+        }
+        _ => {}
+    }
+}
+
+fn handle_code_lookup_keys(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Enter => {
+            let code = app.code_input.trim().to_string();
+            if !code.is_empty() {
+                app.controller.add_album_by_code(code);
+                app.code_input.clear();
+                app.focus = FocusArea::Library;
+            }
+        }
+        KeyCode::Backspace => {
+            app.code_input.pop();
+        }
+        KeyCode::Char(ch)
+            if !key.modifiers.contains(KeyModifiers::ALT)
+                && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            app.code_input.push(ch);
+        }
+        _ => {}
+    }
+}
+
 fn handle_manual_add_keys(app: &mut App, key: KeyEvent) -> Result<()> {
     match key.code {
         KeyCode::Enter => {
@@ -199,12 +373,11 @@ fn handle_manual_add_keys(app: &mut App, key: KeyEvent) -> Result<()> {
         KeyCode::Backspace => {
             app.manual_add_input.pop();
         }
-        KeyCode::Char(ch) => {
+        KeyCode::Char(ch)
             if !key.modifiers.contains(KeyModifiers::ALT)
-                && !key.modifiers.contains(KeyModifiers::CONTROL)
-            {
-                app.manual_add_input.push(ch);
-            }
+                && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            app.manual_add_input.push(ch);
         }
         _ => {}
     }
@@ -5,7 +5,8 @@ use ratatui::widgets::ListState;
 use tokio::sync::mpsc::UnboundedReceiver;
 
 use crate::app::AppMessage;
-use crate::models::{Album, AlbumRecord, Artist};
+use crate::models::{Album, AlbumRecord, Artist, DiscographyFilter, Release};
+use crate::notes::{NoteAction, NotePlan};
 
 use super::controller::AppController;
 
@@ -19,6 +20,10 @@ pub enum FocusArea {
     Library,
     Logs,
     ManualAdd,
+    CodeLookup,
+    NotePreview,
+    ReleasePicker,
+    YearFilter,
 }
 
 impl FocusArea {
@@ -30,6 +35,10 @@ impl FocusArea {
             FocusArea::Library => FocusArea::Logs,
             FocusArea::Logs => FocusArea::Search,
             FocusArea::ManualAdd => FocusArea::ManualAdd, // Stay in manual add mode
+            FocusArea::CodeLookup => FocusArea::CodeLookup,
+            FocusArea::NotePreview => FocusArea::NotePreview,
+            FocusArea::ReleasePicker => FocusArea::ReleasePicker,
+            FocusArea::YearFilter => FocusArea::YearFilter,
         }
     }
 
@@ -41,6 +50,36 @@ impl FocusArea {
             FocusArea::Library => FocusArea::Albums,
             FocusArea::Logs => FocusArea::Library,
             FocusArea::ManualAdd => FocusArea::ManualAdd, // Stay in manual add mode
+            FocusArea::CodeLookup => FocusArea::CodeLookup,
+            FocusArea::NotePreview => FocusArea::NotePreview,
+            FocusArea::ReleasePicker => FocusArea::ReleasePicker,
+            FocusArea::YearFilter => FocusArea::YearFilter,
+        }
+    }
+}
+
+/// What the search box looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Artist,
+    Album,
+    Release,
+}
+
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::Artist => SearchMode::Album,
+            SearchMode::Album => SearchMode::Release,
+            SearchMode::Release => SearchMode::Artist,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchMode::Artist => "Artist",
+            SearchMode::Album => "Album",
+            SearchMode::Release => "Release",
         }
     }
 }
@@ -49,15 +88,38 @@ pub struct App {
     pub controller: AppController,
     pub msg_rx: UnboundedReceiver<AppMessage>,
     pub search_input: String,
+    pub search_mode: SearchMode,
     pub manual_add_input: String,
+    /// Barcode or catalog number typed into the lookup dialog.
+    pub code_input: String,
     pub artist_results: Vec<Artist>,
     pub artist_state: ListState,
     pub albums: Vec<Album>,
     pub album_state: ListState,
+    /// Artist whose albums are listed, reloaded when the filter changes.
+    pub albums_artist: Option<Artist>,
+    /// Search text when the Albums pane shows album search results instead.
+    pub album_search: Option<String>,
+    pub discography: DiscographyFilter,
+    pub year_filter_input: String,
     pub selected_album_ids: HashSet<String>,
     pub library: Vec<AlbumRecord>,
     pub library_state: ListState,
     pub logs: VecDeque<String>,
+    /// Rendered notes shown for confirmation before they are written.
+    pub note_preview: Vec<NotePlan>,
+    pub preview_scroll: u16,
+    /// Editions of one release group, offered when choosing which to store.
+    pub releases: Vec<Release>,
+    pub release_state: ListState,
+    pub release_group_title: String,
+    /// Whether the picker shows release search results rather than the
+    /// editions of one release group; `release_group_title` is then the search.
+    pub releases_searched: bool,
+    /// Release currently stored for the group, marked in the picker.
+    pub current_release_id: String,
+    /// Where focus returns once the picker closes.
+    release_picker_origin: FocusArea,
     pub focus: FocusArea,
     pub should_quit: bool,
 }
@@ -71,19 +133,35 @@ impl App {
         let mut library_state = ListState::default();
         library_state.select(None);
 
+        let discography = controller.default_discography().clone();
+
         Self {
             controller,
             msg_rx,
             search_input: String::new(),
+            search_mode: SearchMode::Artist,
             manual_add_input: String::new(),
+            code_input: String::new(),
             artist_results: Vec::new(),
             artist_state,
             albums: Vec::new(),
             album_state,
+            albums_artist: None,
+            album_search: None,
+            discography,
+            year_filter_input: String::new(),
             selected_album_ids: HashSet::new(),
             library: Vec::new(),
             library_state,
             logs: VecDeque::with_capacity(LOG_CAPACITY),
+            note_preview: Vec::new(),
+            preview_scroll: 0,
+            releases: Vec::new(),
+            release_state: ListState::default(),
+            release_group_title: String::new(),
+            releases_searched: false,
+            current_release_id: String::new(),
+            release_picker_origin: FocusArea::Albums,
             focus: FocusArea::Search,
             should_quit: false,
         }
@@ -110,23 +188,53 @@ impl App {
                 self.push_log("Artist search completed");
             }
             AppMessage::AlbumsLoaded(albums) => {
-                self.albums = albums;
-                self.album_state.select(if self.albums.is_empty() {
-                    None
-                } else {
-                    Some(0)
-                });
-                self.selected_album_ids.clear();
-                self.focus = FocusArea::Albums;
+                self.show_albums(albums);
                 self.push_log("Albums loaded");
             }
+            AppMessage::AlbumSearchResults { query, albums } => {
+                self.albums_artist = None;
+                self.album_search = Some(query);
+                self.show_albums(albums);
+                self.push_log("Album search completed");
+            }
+            AppMessage::ReleaseSearchResults { query, releases } => {
+                self.current_release_id.clear();
+                self.show_releases(releases, 0);
+                self.release_group_title = query;
+                self.releases_searched = true;
+                self.push_log("Release search completed");
+            }
+            AppMessage::ReleasesLoaded {
+                release_group_id,
+                title,
+                releases,
+            } => {
+                self.current_release_id = self
+                    .library
+                    .iter()
+                    .find(|record| record.mbid == release_group_id)
+                    .map(|record| record.release_id.clone())
+                    .unwrap_or_default();
+                let current = releases
+                    .iter()
+                    .position(|release| release.id == self.current_release_id);
+                self.show_releases(releases, current.unwrap_or(0));
+                self.release_group_title = title;
+                self.releases_searched = false;
+            }
             AppMessage::SearchFailed(reason) => {
                 self.push_log(reason);
             }
-            AppMessage::CoverArtStatus { mbid, status, path } => {
+            AppMessage::CoverArtStatus {
+                mbid,
+                status,
+                path,
+                source,
+            } => {
                 if let Some(record) = self.library.iter_mut().find(|record| record.mbid == mbid) {
                     record.cover_art_status = status;
                     record.cover_art_path = path.clone();
+                    record.cover_art_source = source;
                 }
             }
             AppMessage::DownloadLog(entry) => {
@@ -145,6 +253,21 @@ impl App {
                     self.library_state.select(None);
                 }
             }
+            AppMessage::NotePreview(plans) => {
+                let writes = plans
+                    .iter()
+                    .any(|plan| matches!(plan.action, NoteAction::Create | NoteAction::Update));
+                if writes {
+                    self.note_preview = plans;
+                    self.preview_scroll = 0;
+                    self.focus = FocusArea::NotePreview;
+                } else {
+                    for plan in plans {
+                        self.push_log(plan.summary());
+                    }
+                    self.push_log("No notes to write");
+                }
+            }
             AppMessage::NotesGenerated(logs) => {
                 for log in logs {
                     self.push_log(log);
@@ -153,6 +276,37 @@ impl App {
         }
     }
 
+    fn show_albums(&mut self, albums: Vec<Album>) {
+        self.albums = albums;
+        self.album_state.select(if self.albums.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+        self.selected_album_ids.clear();
+        self.focus = FocusArea::Albums;
+    }
+
+    /// Opens the release picker on `releases` with `selected` highlighted.
+    fn show_releases(&mut self, releases: Vec<Release>, selected: usize) {
+        self.releases = releases;
+        self.release_state.select(Some(selected));
+        if self.focus != FocusArea::ReleasePicker {
+            self.release_picker_origin = self.focus;
+        }
+        self.focus = FocusArea::ReleasePicker;
+    }
+
+    /// Runs the search box text as a search of the current mode.
+    pub fn submit_search(&mut self) {
+        let text = self.search_input.clone();
+        match self.search_mode {
+            SearchMode::Artist => self.controller.search_artists(text),
+            SearchMode::Album => self.controller.search_albums(text),
+            SearchMode::Release => self.controller.search_releases(text),
+        }
+    }
+
     pub fn next_focus(&mut self) {
         self.focus = self.focus.next();
     }
@@ -168,6 +322,94 @@ impl App {
         self.logs.push_back(message.into());
     }
 
+    /// Writes the previewed notes and closes the preview.
+    pub fn confirm_note_preview(&mut self) {
+        let plans = std::mem::take(&mut self.note_preview);
+        self.controller.apply_notes(plans);
+        self.focus = FocusArea::Library;
+    }
+
+    pub fn cancel_note_preview(&mut self) {
+        self.note_preview.clear();
+        self.focus = FocusArea::Library;
+        self.push_log("Note generation cancelled");
+    }
+
+    pub fn scroll_note_preview(&mut self, delta: i32) {
+        self.preview_scroll = self.preview_scroll.saturating_add_signed(delta as i16);
+    }
+
+    /// Opens the release picker for a release group once its editions load.
+    pub fn open_release_picker(&mut self, release_group_id: String, title: String) {
+        self.push_log(format!("Loading releases of {title}..."));
+        self.controller.load_releases(release_group_id, title);
+    }
+
+    /// Stores the highlighted release and closes the picker.
+    pub fn confirm_release(&mut self) {
+        if let Some(release) = self
+            .release_state
+            .selected()
+            .and_then(|idx| self.releases.get(idx))
+        {
+            self.controller.add_album_by_release_id(release.id.clone());
+        }
+        self.close_release_picker();
+        self.focus = FocusArea::Library;
+    }
+
+    pub fn close_release_picker(&mut self) {
+        self.releases.clear();
+        self.release_state.select(None);
+        self.focus = self.release_picker_origin;
+    }
+
+    pub fn move_release_selection(&mut self, delta: isize) {
+        let len = self.releases.len();
+        update_list_state(&mut self.release_state, len, delta);
+    }
+
+    /// Lists the albums of `artist` that pass the current filter.
+    pub fn load_albums(&mut self, artist: Artist) {
+        self.albums_artist = Some(artist.clone());
+        self.album_search = None;
+        self.controller
+            .load_albums_for_artist(artist, self.discography.clone());
+    }
+
+    /// Applies a change to the discography filter and lists the current
+    /// artist's albums again.
+    pub fn update_discography(&mut self, change: impl FnOnce(&mut DiscographyFilter)) {
+        change(&mut self.discography);
+        self.push_log(format!("Album filter: {}", self.discography.summary()));
+        if let Some(artist) = self.albums_artist.clone() {
+            self.load_albums(artist);
+        }
+    }
+
+    pub fn open_year_filter(&mut self) {
+        self.year_filter_input = match (self.discography.year_from, self.discography.year_to) {
+            (None, None) => String::new(),
+            (from, to) => format!(
+                "{}-{}",
+                from.map(|year| year.to_string()).unwrap_or_default(),
+                to.map(|year| year.to_string()).unwrap_or_default()
+            ),
+        };
+        self.focus = FocusArea::YearFilter;
+    }
+
+    pub fn confirm_year_filter(&mut self) {
+        let mut filter = self.discography.clone();
+        match filter.set_years(&self.year_filter_input) {
+            Ok(()) => {
+                self.focus = FocusArea::Albums;
+                self.update_discography(|current| *current = filter);
+            }
+            Err(err) => self.push_log(format!("Invalid year range: {err}")),
+        }
+    }
+
     pub fn selected_artist(&self) -> Option<Artist> {
         self.artist_state
             .selected()
@@ -221,7 +463,7 @@ fn update_list_state(state: &mut ListState, len: usize, delta: isize) {
     }
 
     let current = state.selected().unwrap_or(0);
-    let step = delta.abs() as usize;
+    let step = delta.unsigned_abs();
     let new_index = if delta < 0 {
         current.saturating_sub(step)
     } else {
@@ -3,12 +3,16 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::Line,
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
 };
 
-use crate::models::{Album, AlbumRecord, Artist, CoverArtStatus};
+use crate::models::{Album, AlbumRecord, Artist, CoverArtSource, CoverArtStatus, Release};
+use crate::notes::NoteAction;
 
-use super::{App, state::FocusArea};
+use super::{
+    App,
+    state::{FocusArea, SearchMode},
+};
 
 pub fn draw(frame: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
@@ -49,11 +53,48 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
     if app.focus == FocusArea::ManualAdd {
         draw_manual_add_dialog(frame, app);
     }
+
+    if app.focus == FocusArea::CodeLookup {
+        draw_input_dialog(
+            frame,
+            "Add Album by Barcode or Catalog Number",
+            "UPC/EAN barcode or label catalog number:",
+            &app.code_input,
+            "Press Enter to look up, Esc to cancel",
+        );
+    }
+
+    if app.focus == FocusArea::NotePreview {
+        draw_note_preview(frame, app);
+    }
+
+    if app.focus == FocusArea::ReleasePicker {
+        draw_release_picker(frame, app);
+    }
+
+    if app.focus == FocusArea::YearFilter {
+        draw_input_dialog(
+            frame,
+            "Filter Albums by Year",
+            "Year or range, e.g. 1994, 1990-1999 or 2000-:",
+            &app.year_filter_input,
+            "Press Enter to apply (empty clears), Esc to cancel",
+        );
+    }
 }
 
 fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
+    let hint = match app.search_mode {
+        SearchMode::Artist => "",
+        SearchMode::Album => " • artist: year:",
+        SearchMode::Release => " • artist: year: barcode: catno:",
+    };
     let block = Block::default()
-        .title("Search Artist")
+        .title(format!(
+            "Search {}{hint} • Ctrl+T: search for {}",
+            app.search_mode.label(),
+            app.search_mode.next().label().to_lowercase()
+        ))
         .borders(Borders::ALL)
         .border_style(border_style(app.focus, FocusArea::Search));
 
@@ -109,7 +150,10 @@ fn draw_album_list(frame: &mut Frame, app: &mut App, area: Rect) {
     let list = List::new(items)
         .block(
             Block::default()
-                .title("Albums")
+                .title(match &app.album_search {
+                    Some(query) => format!("Albums matching '{query}'"),
+                    None => format!("Albums [{}]", app.discography.summary()),
+                })
                 .borders(Borders::ALL)
                 .border_style(border_style(app.focus, FocusArea::Albums)),
         )
@@ -129,7 +173,7 @@ fn draw_library(frame: &mut Frame, app: &mut App, area: Rect) {
     } else {
         app.library
             .iter()
-            .map(|record| ListItem::new(library_lines(record)))
+            .map(|record| ListItem::new(library_lines(record, app.controller.template_for(record))))
             .collect()
     };
 
@@ -173,13 +217,24 @@ fn draw_logs(frame: &mut Frame, app: &App, area: Rect) {
 
 fn draw_footer(frame: &mut Frame, area: Rect) {
     let footer = Paragraph::new(
-        "Tab: cycle • Enter: confirm • Space: toggle • a: add albums • g: generate notes • Ctrl+M: manual add • q: quit",
+        "Tab: cycle • Enter: confirm • Space: toggle • a: add albums • 1-5/l/c/x/s/d/o/y: filter albums • r: pick release • g: generate notes • u: sync notes • t: template • i: gallery • Ctrl+M: manual add • Ctrl+B: add by barcode • q: quit",
     )
     .style(Style::default().fg(Color::Gray));
     frame.render_widget(footer, area);
 }
 
 fn draw_manual_add_dialog(frame: &mut Frame, app: &App) {
+    draw_input_dialog(
+        frame,
+        "Add Album by Release ID",
+        "Enter MusicBrainz Release ID:",
+        &app.manual_add_input,
+        "Press Enter to add, Esc to cancel",
+    );
+}
+
+/// A one-line text prompt centred over the panes.
+fn draw_input_dialog(frame: &mut Frame, title: &str, prompt: &str, input: &str, help: &str) {
     use ratatui::layout::Alignment;
 
     // Center the dialog
@@ -197,33 +252,157 @@ fn draw_manual_add_dialog(frame: &mut Frame, app: &App) {
     };
 
     // Clear the area
-    let clear_block = Block::default()
-        .style(Style::default().bg(Color::Black));
+    let clear_block = Block::default().style(Style::default().bg(Color::Black));
     frame.render_widget(clear_block, dialog_area);
 
     // Draw the dialog
     let block = Block::default()
-        .title("Add Album by Release ID")
+        .title(title.to_string())
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+        .border_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
 
     let inner = block.inner(dialog_area);
     frame.render_widget(block, dialog_area);
 
     let text = vec![
-        Line::from("Enter MusicBrainz Release ID:"),
-        Line::from(format!("> {}", app.manual_add_input)),
+        Line::from(prompt.to_string()),
+        Line::from(format!("> {input}")),
         Line::from(""),
-        Line::from("Press Enter to add, Esc to cancel")
-            .style(Style::default().fg(Color::DarkGray)),
+        Line::from(help.to_string()).style(Style::default().fg(Color::DarkGray)),
     ];
 
-    let paragraph = Paragraph::new(text)
-        .alignment(Alignment::Left);
+    let paragraph = Paragraph::new(text).alignment(Alignment::Left);
 
     frame.render_widget(paragraph, inner);
 }
 
+fn draw_note_preview(frame: &mut Frame, app: &App) {
+    let area = frame.size();
+    let dialog_area = Rect {
+        x: area.width / 20,
+        y: area.height / 20,
+        width: area.width - area.width / 10,
+        height: area.height - area.height / 10,
+    };
+    frame.render_widget(Clear, dialog_area);
+
+    let count = |action| {
+        app.note_preview
+            .iter()
+            .filter(|plan| plan.action == action)
+            .count()
+    };
+    let block = Block::default()
+        .title(format!(
+            "Preview: {} to create, {} to update • Enter/y: write • Esc/n: cancel • ↑↓: scroll",
+            count(NoteAction::Create),
+            count(NoteAction::Update)
+        ))
+        .borders(Borders::ALL)
+        .border_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    let mut lines = Vec::new();
+    for plan in &app.note_preview {
+        let style = match plan.action {
+            NoteAction::Create => Style::default().fg(Color::Green),
+            NoteAction::Update => Style::default().fg(Color::Yellow),
+            NoteAction::Failed => Style::default().fg(Color::Red),
+            NoteAction::Unchanged | NoteAction::Skip => dim_style(),
+        };
+        lines.push(Line::from(plan.summary()).style(style.add_modifier(Modifier::BOLD)));
+
+        if matches!(plan.action, NoteAction::Create | NoteAction::Update) {
+            lines.extend(plan.diff.lines().map(diff_line));
+            lines.push(Line::from(""));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .scroll((app.preview_scroll, 0));
+    frame.render_widget(paragraph, dialog_area);
+}
+
+fn draw_release_picker(frame: &mut Frame, app: &mut App) {
+    let area = frame.size();
+    let dialog_area = Rect {
+        x: area.width / 10,
+        y: area.height / 10,
+        width: area.width - area.width / 5,
+        height: area.height - area.height / 5,
+    };
+    frame.render_widget(Clear, dialog_area);
+
+    let items: Vec<ListItem> = app
+        .releases
+        .iter()
+        .map(|release| {
+            ListItem::new(release_lines(
+                release,
+                &app.current_release_id,
+                app.releases_searched,
+            ))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "{} {} ({}) • Enter: {} • Esc: cancel",
+                    if app.releases_searched {
+                        "Releases matching"
+                    } else {
+                        "Releases of"
+                    },
+                    app.release_group_title,
+                    app.releases.len(),
+                    if app.releases_searched {
+                        "add this release"
+                    } else {
+                        "use this release"
+                    }
+                ))
+                .borders(Borders::ALL)
+                .border_style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+        )
+        .highlight_style(
+            Style::default()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+
+    frame.render_stateful_widget(list, dialog_area, &mut app.release_state);
+}
+
+fn diff_line(line: &str) -> Line<'static> {
+    let style = if line.starts_with("+++") || line.starts_with("---") {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else if line.starts_with('+') {
+        Style::default().fg(Color::Green)
+    } else if line.starts_with('-') {
+        Style::default().fg(Color::Red)
+    } else if line.starts_with("@@") {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Line::from(line.to_string()).style(style)
+}
+
 fn artist_line(artist: &Artist) -> Line<'static> {
     let text = artist.display_name();
     Line::from(text)
@@ -244,13 +423,82 @@ fn album_lines(album: &Album, selected: bool) -> Vec<Line<'static>> {
     ]
 }
 
-fn library_lines(record: &AlbumRecord) -> Vec<Line<'static>> {
+/// A release in the picker; `with_title` adds its artist and title, for
+/// search results spanning several release groups.
+fn release_lines(
+    release: &Release,
+    current_release_id: &str,
+    with_title: bool,
+) -> Vec<Line<'static>> {
+    let or_unknown = |value: &str| {
+        if value.is_empty() {
+            "?".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    let art = if release.has_front_cover {
+        "Art: ✔"
+    } else {
+        "Art: ✖"
+    };
+    let current = if release.id == current_release_id {
+        " (current)"
+    } else {
+        ""
+    };
+
+    let mut details = vec![or_unknown(&release.label)];
+    if !release.catalog_number.is_empty() {
+        details.push(release.catalog_number.clone());
+    }
+    if !release.barcode.is_empty() {
+        details.push(release.barcode.clone());
+    }
+    if !release.status.is_empty() {
+        details.push(release.status.clone());
+    }
+    if !release.disambiguation.is_empty() {
+        details.push(release.disambiguation.clone());
+    }
+
+    let mut lines = Vec::new();
+    if with_title {
+        lines.push(Line::from(format!(
+            "{} — {}",
+            release.artist, release.title
+        )));
+    }
+    lines.extend([
+        Line::from(format!(
+            "{:<10}  {:<3}  {}  {} tracks  {art}{current}",
+            or_unknown(&release.date),
+            or_unknown(&release.country),
+            or_unknown(&release.format),
+            release.track_count
+        )),
+        Line::from(format!("   {}", details.join(" • "))).style(dim_style()),
+    ]);
+    lines
+}
+
+fn library_lines(record: &AlbumRecord, template: &str) -> Vec<Line<'static>> {
     let status = match record.cover_art_status {
         CoverArtStatus::Completed => "Art: ✔",
         CoverArtStatus::Queued | CoverArtStatus::Pending => "Art: ⏳",
         CoverArtStatus::Downloading => "Art: ↓",
         CoverArtStatus::Unavailable => "Art: ✖",
     };
+    let source = match &record.cover_art_source {
+        Some(source) if *source != CoverArtSource::Release => format!(" (from {source})"),
+        _ => String::new(),
+    };
+
+    let gallery = if record.gallery {
+        format!(" • Gallery: {}", record.gallery_images.len())
+    } else {
+        String::new()
+    };
 
     let notes = if record.note_path.is_some() {
         "Notes: ✔"
@@ -260,7 +508,9 @@ fn library_lines(record: &AlbumRecord) -> Vec<Line<'static>> {
 
     vec![
         Line::from(format!("{} — {}", record.artist, record.title)),
-        Line::from(format!("   {status} • {notes}")),
+        Line::from(format!(
+            "   {status}{source}{gallery} • {notes} • Template: {template}"
+        )),
     ]
 }
 
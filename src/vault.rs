@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+/// How Obsidian writes link paths, mirroring the "New link format" setting.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkFormat {
+    /// Just the file name when no other file in the vault has it, otherwise
+    /// the path from the vault root.
+    #[default]
+    Shortest,
+    /// Path relative to the note containing the link.
+    Relative,
+    /// Path from the vault root.
+    Absolute,
+}
+
+/// The subset of `.obsidian/app.json` that affects attachment placement and links.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ObsidianAppSettings {
+    attachment_folder_path: Option<String>,
+    use_markdown_links: bool,
+    new_link_format: LinkFormat,
+}
+
+/// An Obsidian vault that notes and cover art are written into.
+#[derive(Debug, Clone)]
+pub struct Vault {
+    notes_dir: PathBuf,
+    attachments: AttachmentFolder,
+    link_style: LinkStyle,
+}
+
+/// Where new attachments go.
+#[derive(Debug, Clone)]
+enum AttachmentFolder {
+    /// One folder for the whole vault.
+    Fixed(PathBuf),
+    /// A folder relative to the folder of the note the attachment belongs to
+    /// (`./` in Obsidian's settings).
+    BesideNote(PathBuf),
+}
+
+impl Vault {
+    /// Opens the vault at `root`, reading `.obsidian/app.json` when present.
+    ///
+    /// `notes_folder` and `attachments_folder` are relative to the vault root.
+    /// Without an explicit attachments folder the vault's own "Default location
+    /// for new attachments" is used; note-relative locations (`./`) are
+    /// resolved per note by [`attachments_dir_for`](Self::attachments_dir_for).
+    pub fn open(
+        root: &Path,
+        notes_folder: Option<&Path>,
+        attachments_folder: Option<&Path>,
+    ) -> Result<Self> {
+        if !root.is_dir() {
+            bail!("vault root `{}` is not a directory", root.display());
+        }
+
+        let obsidian_dir = root.join(".obsidian");
+        if !obsidian_dir.is_dir() {
+            bail!(
+                "`{}` does not look like an Obsidian vault (no .obsidian folder)",
+                root.display()
+            );
+        }
+
+        let settings = read_app_settings(&obsidian_dir.join("app.json"))?;
+
+        let notes_dir = match notes_folder {
+            Some(folder) => root.join(vault_relative(folder, "vault.notes_folder")?),
+            None => root.to_path_buf(),
+        };
+
+        let attachments = match attachments_folder {
+            Some(folder) => AttachmentFolder::Fixed(
+                root.join(vault_relative(folder, "vault.attachments_folder")?),
+            ),
+            None => resolve_attachment_folder(root, settings.attachment_folder_path.as_deref())?,
+        };
+
+        Ok(Self {
+            notes_dir,
+            attachments,
+            link_style: LinkStyle {
+                markdown_links: settings.use_markdown_links,
+                format: settings.new_link_format,
+                root: root.to_path_buf(),
+                files: None,
+            },
+        })
+    }
+
+    pub fn notes_dir(&self) -> &Path {
+        &self.notes_dir
+    }
+
+    /// The attachments folder for notes directly in the notes folder.
+    pub fn attachments_dir(&self) -> PathBuf {
+        self.attachments_dir_for(&self.notes_dir)
+    }
+
+    /// The attachments folder for a note in `note_dir`.
+    pub fn attachments_dir_for(&self, note_dir: &Path) -> PathBuf {
+        match &self.attachments {
+            AttachmentFolder::Fixed(dir) => dir.clone(),
+            AttachmentFolder::BesideNote(folder) => note_dir.join(folder),
+        }
+    }
+
+    pub fn link_style(&self) -> &LinkStyle {
+        &self.link_style
+    }
+}
+
+/// Formats links from a note to another file in the vault.
+#[derive(Debug, Clone)]
+pub struct LinkStyle {
+    markdown_links: bool,
+    format: LinkFormat,
+    root: PathBuf,
+    /// Vault files by lowercased file name, from [`with_files`](Self::with_files).
+    files: Option<Arc<FileIndex>>,
+}
+
+type FileIndex = HashMap<String, Vec<PathBuf>>;
+
+impl LinkStyle {
+    /// Relative markdown links, used when notes are not written into a vault.
+    pub fn relative_markdown(root: &Path) -> Self {
+        Self {
+            markdown_links: true,
+            format: LinkFormat::Relative,
+            root: root.to_path_buf(),
+            files: None,
+        }
+    }
+
+    /// Indexes the files in the vault, plus `planned` files about to be
+    /// written, so shortest links can tell which file names are unique.
+    /// Without an index, every shortest link scans the vault.
+    pub fn with_files(mut self, planned: impl IntoIterator<Item = PathBuf>) -> Self {
+        if self.format == LinkFormat::Shortest {
+            let mut files = scan_files(&self.root);
+            for path in planned {
+                add_file(&mut files, path);
+            }
+            self.files = Some(Arc::new(files));
+        }
+        self
+    }
+
+    /// Whether another file in the vault has the file name of `target`.
+    fn name_is_shared(&self, target: &Path) -> bool {
+        let Some(name) = target.file_name() else {
+            return false;
+        };
+        let scanned;
+        let files = match &self.files {
+            Some(files) => files.as_ref(),
+            None => {
+                scanned = scan_files(&self.root);
+                &scanned
+            }
+        };
+        files
+            .get(&name.to_string_lossy().to_lowercase())
+            .is_some_and(|paths| paths.iter().any(|path| path != target))
+    }
+
+    /// The link path for `target` as seen from a note in `note_dir`.
+    pub fn link_path(&self, target: &Path, note_dir: &Path) -> String {
+        let path = match self.format {
+            LinkFormat::Shortest if !self.name_is_shared(target) => target
+                .file_name()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| target.to_path_buf()),
+            LinkFormat::Relative => {
+                pathdiff::diff_paths(target, note_dir).unwrap_or_else(|| target.to_path_buf())
+            }
+            LinkFormat::Shortest | LinkFormat::Absolute => target
+                .strip_prefix(&self.root)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| target.to_path_buf()),
+        };
+
+        path.components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// A link suitable for a frontmatter property. Obsidian only resolves
+    /// wikilinks inside properties, so this ignores the markdown link setting.
+    pub fn property_link(&self, target: &Path, note_dir: &Path) -> String {
+        format!("[[{}]]", self.link_path(target, note_dir))
+    }
+
+    /// A wikilink to another note, without the `.md` extension, optionally
+    /// showing `alias` instead of the path. Used in properties and note bodies alike.
+    pub fn note_link(&self, target: &Path, note_dir: &Path, alias: Option<&str>) -> String {
+        let path = self.link_path(target, note_dir);
+        let path = path.strip_suffix(".md").unwrap_or(&path);
+        match alias {
+            Some(alias) => format!("[[{path}|{}]]", alias.replace(['|', '[', ']'], " ")),
+            None => format!("[[{path}]]"),
+        }
+    }
+
+    /// An embed for the note body, honoring the vault's link syntax.
+    pub fn embed(&self, target: &Path, note_dir: &Path) -> String {
+        let path = self.link_path(target, note_dir);
+        if self.markdown_links {
+            format!("![]({})", encode_destination(&path))
+        } else {
+            format!("![[{path}]]")
+        }
+    }
+}
+
+/// Percent-encodes the characters that would end or cut short a markdown
+/// link destination: spaces, parentheses, `#` (a heading anchor) and `%` itself.
+fn encode_destination(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for ch in path.chars() {
+        match ch {
+            ' ' | '(' | ')' | '#' | '%' => encoded.push_str(&format!("%{:02X}", ch as u32)),
+            _ => encoded.push(ch),
+        }
+    }
+    encoded
+}
+
+/// Every file under `root`, skipping hidden folders such as `.obsidian`.
+fn scan_files(root: &Path) -> FileIndex {
+    let mut files = FileIndex::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push(path);
+                }
+            } else {
+                add_file(&mut files, path);
+            }
+        }
+    }
+    files
+}
+
+fn add_file(files: &mut FileIndex, path: PathBuf) {
+    let Some(name) = path.file_name() else {
+        return;
+    };
+    let paths = files
+        .entry(name.to_string_lossy().to_lowercase())
+        .or_default();
+    if !paths.contains(&path) {
+        paths.push(path);
+    }
+}
+
+fn read_app_settings(path: &Path) -> Result<ObsidianAppSettings> {
+    if !path.exists() {
+        return Ok(ObsidianAppSettings::default());
+    }
+
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read Obsidian settings at {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid Obsidian settings in {}", path.display()))
+}
+
+/// Interprets Obsidian's `attachmentFolderPath`: `/` is the vault root, `./`
+/// prefixes are relative to each note's folder and anything else is vault-relative.
+fn resolve_attachment_folder(root: &Path, setting: Option<&str>) -> Result<AttachmentFolder> {
+    let setting = setting.unwrap_or("/").trim();
+
+    if setting.is_empty() || setting == "/" {
+        return Ok(AttachmentFolder::Fixed(root.to_path_buf()));
+    }
+
+    if let Some(rest) = setting.strip_prefix("./") {
+        return Ok(AttachmentFolder::BesideNote(
+            vault_relative(Path::new(rest), "attachmentFolderPath")?.to_path_buf(),
+        ));
+    }
+    if setting == "." {
+        return Ok(AttachmentFolder::BesideNote(PathBuf::new()));
+    }
+
+    Ok(AttachmentFolder::Fixed(root.join(vault_relative(
+        Path::new(setting.trim_start_matches('/')),
+        "attachmentFolderPath",
+    )?)))
+}
+
+/// Rejects folders that would escape the vault.
+fn vault_relative<'a>(folder: &'a Path, setting: &str) -> Result<&'a Path> {
+    let escapes = folder.components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+
+    if escapes {
+        bail!(
+            "{setting} `{}` must be a folder inside the vault",
+            folder.display()
+        );
+    }
+
+    Ok(folder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(format: &str) -> (tempfile::TempDir, Vault) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".obsidian")).unwrap();
+        fs::write(
+            dir.path().join(".obsidian/app.json"),
+            format!(r#"{{"newLinkFormat": "{format}", "attachmentFolderPath": "Attachments"}}"#),
+        )
+        .unwrap();
+        let vault = Vault::open(dir.path(), Some(Path::new("Albums")), None).unwrap();
+        (dir, vault)
+    }
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn shortest_links_use_the_file_name_while_it_is_unique() {
+        let (dir, vault) = vault("shortest");
+        let root = dir.path();
+        let cover = root.join("Attachments/dummy.jpg");
+        touch(&cover);
+        let note_dir = root.join("Albums/Portishead");
+
+        let style = vault.link_style();
+        assert_eq!(style.link_path(&cover, &note_dir), "dummy.jpg");
+        assert_eq!(style.property_link(&cover, &note_dir), "[[dummy.jpg]]");
+        assert_eq!(style.embed(&cover, &note_dir), "![[dummy.jpg]]");
+
+        // Hidden folders do not count.
+        touch(&root.join(".trash/dummy.jpg"));
+        assert_eq!(style.link_path(&cover, &note_dir), "dummy.jpg");
+
+        touch(&root.join("Elsewhere/Dummy.jpg"));
+        assert_eq!(style.link_path(&cover, &note_dir), "Attachments/dummy.jpg");
+    }
+
+    #[test]
+    fn shortest_links_account_for_planned_files() {
+        let (dir, vault) = vault("shortest");
+        let root = dir.path();
+        let first = root.join("Albums/Portishead/Third.md");
+        let second = root.join("Albums/Beak/Third.md");
+        let note_dir = root.join("Albums");
+
+        let style = vault.link_style().clone().with_files([first.clone()]);
+        assert_eq!(style.note_link(&first, &note_dir, None), "[[Third]]");
+
+        let style = vault
+            .link_style()
+            .clone()
+            .with_files([first.clone(), second.clone()]);
+        assert_eq!(
+            style.note_link(&first, &note_dir, None),
+            "[[Albums/Portishead/Third]]"
+        );
+        assert_eq!(
+            style.note_link(&second, &note_dir, Some("Third")),
+            "[[Albums/Beak/Third|Third]]"
+        );
+    }
+
+    #[test]
+    fn relative_links_start_from_the_note() {
+        let (dir, vault) = vault("relative");
+        let root = dir.path();
+        let cover = root.join("Attachments/dummy cover.jpg");
+        let note_dir = root.join("Albums/Portishead");
+
+        let style = vault.link_style();
+        assert_eq!(
+            style.link_path(&cover, &note_dir),
+            "../../Attachments/dummy cover.jpg"
+        );
+        assert_eq!(
+            LinkStyle::relative_markdown(root).embed(&cover, &note_dir),
+            "![](../../Attachments/dummy%20cover.jpg)"
+        );
+    }
+
+    #[test]
+    fn note_relative_attachments_follow_each_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".obsidian")).unwrap();
+        fs::write(
+            root.join(".obsidian/app.json"),
+            r#"{"attachmentFolderPath": "./assets"}"#,
+        )
+        .unwrap();
+
+        let vault = Vault::open(root, Some(Path::new("Albums")), None).unwrap();
+        assert_eq!(vault.attachments_dir(), root.join("Albums/assets"));
+        assert_eq!(
+            vault.attachments_dir_for(&root.join("Albums/Portishead")),
+            root.join("Albums/Portishead/assets")
+        );
+
+        let vault = Vault::open(root, None, Some(Path::new("Covers"))).unwrap();
+        assert_eq!(
+            vault.attachments_dir_for(&root.join("Albums/Portishead")),
+            root.join("Covers")
+        );
+    }
+
+    #[test]
+    fn markdown_embeds_encode_characters_that_end_the_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let cover = root.join("Attachments/Dummy (Remastered) #2 100%.jpg");
+
+        assert_eq!(
+            LinkStyle::relative_markdown(root).embed(&cover, root),
+            "![](Attachments/Dummy%20%28Remastered%29%20%232%20100%25.jpg)"
+        );
+    }
+
+    #[test]
+    fn absolute_links_start_from_the_vault_root() {
+        let (dir, vault) = vault("absolute");
+        let root = dir.path();
+        let cover = root.join("Attachments/dummy.jpg");
+        let artist = root.join("Albums/Portishead.md");
+        let note_dir = root.join("Albums/Portishead");
+
+        let style = vault.link_style();
+        assert_eq!(style.link_path(&cover, &note_dir), "Attachments/dummy.jpg");
+        assert_eq!(
+            style.note_link(&artist, &note_dir, Some("Portishead")),
+            "[[Albums/Portishead|Portishead]]"
+        );
+    }
+}